@@ -44,9 +44,9 @@ fn parse_readout(response: &str) -> Result<TecReadout, Box<dyn std::error::Error
 
     // Parse individual sections (skip first section)
     let t_set = parse_section(sections[1])?;
-    let P = parse_section(sections[2])?;
-    let I = parse_section(sections[3])?;
-    let D = parse_section(sections[4])?;
+    let p = parse_section(sections[2])?;
+    let i = parse_section(sections[3])?;
+    let d = parse_section(sections[4])?;
 
     // Parse section 5 (t_min...t_max)
     let temp_range_filtered: String = sections[5]
@@ -67,24 +67,24 @@ fn parse_readout(response: &str) -> Result<TecReadout, Box<dyn std::error::Error
         .chars()
         .filter(|c| !c.is_alphabetic() && !c.is_whitespace())
         .collect();
-    let OC = match oc_filtered.parse::<u8>()? {
+    let oc = match oc_filtered.parse::<u8>()? {
         0 => false,
         1 => true,
         _ => return Err("Invalid OC value".into()),
     };
 
-    let PWM = parse_section(sections[8])?;
+    let pwm = parse_section(sections[8])?;
 
     Ok(TecReadout {
         t_set,
-        p: P,
-        i: I,
-        d: D,
+        p,
+        i,
+        d,
         t_min,
         t_max,
         t_measured,
-        oc: OC,
-        pwm: PWM,
+        oc,
+        pwm,
     })
 }
 
@@ -0,0 +1,374 @@
+use crate::experiment::{LogFormat, PhaseTiming};
+use crate::tec::TecReadout;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+
+/// One temperature log row, reduced to what the plot and analysis use
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogSample {
+    pub timestamp_ms: u64,
+    pub t_set: f32,
+    pub t_measured: f32,
+}
+
+/// Read back the temperature log written in `format`
+pub fn read_log(path: &str, format: LogFormat) -> Result<Vec<LogSample>, Box<dyn Error>> {
+    read_rows(path, |line| parse_log_line(line, format))
+}
+
+/// Read back every readout in the temperature log written in `format`, each
+/// with the timestamp it was logged at, e.g. to replay the log
+pub fn read_log_readouts(path: &str, format: LogFormat) -> Result<Vec<(u64, TecReadout)>, Box<dyn Error>> {
+    read_rows(path, |line| parse_log_readout(line, format))
+}
+
+/// Parse each non-blank line of the log at `path`, skipping those `parse`
+/// returns None for and naming the line of the first error
+fn read_rows<T>(
+    path: &str,
+    parse: impl Fn(&str) -> Result<Option<T>, Box<dyn Error>>,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let mut rows = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse(line) {
+            Ok(Some(row)) => rows.push(row),
+            Ok(None) => {}
+            Err(e) => return Err(format!("line {}: {}", number + 1, e).into()),
+        }
+    }
+    Ok(rows)
+}
+
+/// Columns of a CSV log row, or None for the header. The phase is the only
+/// quoted column and comes after every column read here, so those split
+/// cleanly.
+fn csv_columns(line: &str, needed: usize) -> Result<Option<Vec<&str>>, Box<dyn Error>> {
+    let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+    if columns[0] == "timestamp_ms" || columns[0] == "elapsed_ms" {
+        return Ok(None);
+    }
+    if columns.len() < needed {
+        return Err(format!("expected at least {} columns, found {}", needed, columns.len()).into());
+    }
+    Ok(Some(columns))
+}
+
+/// One log line as a sample; None for the CSV header. The timestamp is
+/// whatever the first time column holds, so a log with relative timestamps
+/// gives milliseconds since the log started.
+fn parse_log_line(line: &str, format: LogFormat) -> Result<Option<LogSample>, Box<dyn Error>> {
+    let sample = match format {
+        LogFormat::Csv => {
+            let Some(columns) = csv_columns(line, 8)? else {
+                return Ok(None);
+            };
+            LogSample {
+                timestamp_ms: columns[0].parse()?,
+                t_set: columns[1].parse()?,
+                t_measured: columns[7].parse()?,
+            }
+        }
+        LogFormat::JsonLines => {
+            let record: serde_json::Value = serde_json::from_str(line)?;
+            let number = |key: &str| {
+                record[key]
+                    .as_f64()
+                    .ok_or_else(|| format!("log record has no {}: {}", key, line))
+            };
+            LogSample {
+                timestamp_ms: json_timestamp(&record, line)?,
+                t_set: number("t_set")? as f32,
+                t_measured: number("t_measured")? as f32,
+            }
+        }
+    };
+    Ok(Some(sample))
+}
+
+/// One log line as its timestamp and readout, like `parse_log_line`. CSV
+/// rows give the columns up to `PWM`; the resync count reads as 0.
+fn parse_log_readout(line: &str, format: LogFormat) -> Result<Option<(u64, TecReadout)>, Box<dyn Error>> {
+    let row = match format {
+        LogFormat::Csv => {
+            let Some(columns) = csv_columns(line, 10)? else {
+                return Ok(None);
+            };
+            let number = |index: usize| {
+                columns[index]
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid number {:?}", columns[index]))
+            };
+            let readout = TecReadout {
+                t_set: number(1)?,
+                p: number(2)?,
+                i: number(3)?,
+                d: number(4)?,
+                t_min: number(5)?,
+                t_max: number(6)?,
+                t_measured: number(7)?,
+                oc: columns[8] == "1",
+                pwm: number(9)?,
+                resync_count: 0,
+                t_measured_device: None,
+            };
+            (columns[0].parse()?, readout)
+        }
+        LogFormat::JsonLines => {
+            let record: serde_json::Value = serde_json::from_str(line)?;
+            let timestamp_ms = json_timestamp(&record, line)?;
+            (timestamp_ms, serde_json::from_value(record)?)
+        }
+    };
+    Ok(Some(row))
+}
+
+/// `timestamp_ms` of a JSON log record; logs with relative timestamps only
+/// carry `elapsed_ms`
+fn json_timestamp(record: &serde_json::Value, line: &str) -> Result<u64, String> {
+    record["timestamp_ms"]
+        .as_f64()
+        .or_else(|| record["elapsed_ms"].as_f64())
+        .map(|timestamp_ms| timestamp_ms as u64)
+        .ok_or_else(|| format!("log record has no timestamp_ms: {}", line))
+}
+
+/// Contents of `analysis.yaml`
+#[derive(Debug, Serialize)]
+pub struct Analysis {
+    /// Band around the target used for settling, in °C
+    pub tolerance: f32,
+    pub transitions: Vec<Transition>,
+}
+
+/// Response to the setpoint change made by one phase
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transition {
+    pub phase_name: String,
+    pub from_temp: f32,
+    pub target_temp: f32,
+    pub start_time_ms: u64,
+    /// Furthest the measured temperature went past the target, in °C
+    pub overshoot_c: f32,
+    /// Seconds from the phase start until the target was first reached
+    pub time_to_cross_s: Option<f64>,
+    /// Seconds from the phase start until the temperature stayed within
+    /// tolerance for the rest of the phase
+    pub settling_time_s: Option<f64>,
+}
+
+/// Measure every phase whose final setpoint differs from the one before it.
+///
+/// Each phase is judged on its own samples, so a ramp counts as one
+/// transition to its final setpoint. Phases with no logged samples, such as
+/// the initial stabilization, are skipped.
+pub fn analyze(samples: &[LogSample], phases: &[PhaseTiming], tolerance: f32) -> Analysis {
+    let mut transitions = Vec::new();
+
+    for phase in phases {
+        let window: Vec<&LogSample> = samples
+            .iter()
+            .filter(|s| s.timestamp_ms >= phase.start_time_ms && s.timestamp_ms <= phase.end_time_ms)
+            .collect();
+        let (Some(first), Some(last)) = (window.first(), window.last()) else {
+            continue;
+        };
+        let from_temp = samples
+            .iter()
+            .rev()
+            .find(|s| s.timestamp_ms < phase.start_time_ms)
+            .map_or(first.t_set, |s| s.t_set);
+        let target_temp = last.t_set;
+        if from_temp == target_temp {
+            continue;
+        }
+
+        // Positive once the measurement is past the target in the direction of travel
+        let direction = (target_temp - from_temp).signum();
+        let past_target = |s: &LogSample| (s.t_measured - target_temp) * direction;
+        let seconds = |s: &LogSample| s.timestamp_ms.saturating_sub(phase.start_time_ms) as f64 / 1000.0;
+
+        let time_to_cross_s = window.iter().find(|s| past_target(s) >= 0.0).map(|s| seconds(s));
+        let overshoot_c = match time_to_cross_s {
+            Some(_) => window.iter().map(|s| past_target(s)).fold(0.0, f32::max),
+            None => 0.0,
+        };
+        // The sample after the last one outside the band starts the settled stretch
+        let settling_time_s = match window
+            .iter()
+            .rposition(|s| (s.t_measured - target_temp).abs() > tolerance)
+        {
+            None => Some(seconds(first)),
+            Some(i) => window.get(i + 1).map(|s| seconds(s)),
+        };
+
+        transitions.push(Transition {
+            phase_name: phase.phase_name.clone(),
+            from_temp,
+            target_temp,
+            start_time_ms: phase.start_time_ms,
+            overshoot_c,
+            time_to_cross_s,
+            settling_time_s,
+        });
+    }
+
+    Analysis {
+        tolerance,
+        transitions,
+    }
+}
+
+/// How closely the measured temperature followed the setpoint over a log
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingSummary {
+    pub samples: usize,
+    /// Root mean square of measured minus setpoint, in °C
+    pub rms_error: f32,
+    /// Largest absolute difference from the setpoint, in °C
+    pub max_deviation: f32,
+    /// Share of samples within tolerance of the setpoint; readouts are
+    /// logged at a fixed interval, so this is also the share of time
+    pub within_tolerance_percent: f32,
+}
+
+/// Summarize tracking over every sample; None for an empty log
+pub fn summarize(samples: &[LogSample], tolerance: f32) -> Option<TrackingSummary> {
+    if samples.is_empty() {
+        return None;
+    }
+    let errors: Vec<f32> = samples.iter().map(|s| s.t_measured - s.t_set).collect();
+    let mean_square = errors.iter().map(|e| e * e).sum::<f32>() / errors.len() as f32;
+    let within = errors.iter().filter(|e| e.abs() <= tolerance).count();
+
+    Some(TrackingSummary {
+        samples: samples.len(),
+        rms_error: mean_square.sqrt(),
+        max_deviation: errors.iter().map(|e| e.abs()).fold(0.0, f32::max),
+        within_tolerance_percent: within as f32 * 100.0 / samples.len() as f32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    #[test]
+    fn test_read_log_formats() {
+        let scratch = ScratchDir::new("analysis");
+        let csv_path = scratch.file("log.csv");
+        let jsonl_path = scratch.file("log.jsonl");
+        fs::write(
+            &csv_path,
+            "timestamp_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM,phase\n\
+             1000,25.00,5.00,2.00,1.00,0.00,50.00,24.50,0,25.0,\"Heat, fast\"\n",
+        )
+        .unwrap();
+        fs::write(
+            &jsonl_path,
+            "{\"timestamp_ms\":1000,\"t_set\":25.0,\"p\":5.0,\"i\":2.0,\"d\":1.0,\"t_min\":0.0,\"t_max\":50.0,\
+             \"t_measured\":24.5,\"oc\":false,\"pwm\":25.0,\"phase\":\"Heat\"}\n",
+        )
+        .unwrap();
+
+        let csv = read_log(&csv_path, LogFormat::Csv).unwrap();
+        let jsonl = read_log(&jsonl_path, LogFormat::JsonLines).unwrap();
+        for (path, format) in [(&csv_path, LogFormat::Csv), (&jsonl_path, LogFormat::JsonLines)] {
+            let readouts = read_log_readouts(path, format).unwrap();
+            assert_eq!(readouts.len(), 1);
+            assert_eq!(readouts[0].0, 1000);
+            assert_eq!((readouts[0].1.t_measured, readouts[0].1.pwm), (24.5, 25.0));
+        }
+
+        let expected = [LogSample {
+            timestamp_ms: 1000,
+            t_set: 25.0,
+            t_measured: 24.5,
+        }];
+        assert_eq!(csv, expected);
+        assert_eq!(jsonl, expected);
+    }
+
+    fn phase(name: &str, start_time_ms: u64, end_time_ms: u64) -> PhaseTiming {
+        PhaseTiming {
+            phase_name: name.to_string(),
+            start_time_ms,
+            end_time_ms,
+            duration_s: crate::time::elapsed_s(start_time_ms, end_time_ms),
+            t_measured_start: None,
+            t_measured_end: None,
+            t_set_start: None,
+            t_set_end: None,
+        }
+    }
+
+    #[test]
+    fn test_analyze_transitions() {
+        // Hold at 25, heat to 30 with a 1.5° overshoot, then cool to 20
+        // without ever getting there
+        let measured = [
+            25.0, 25.0, 25.0, 27.0, 29.0, 31.5, 30.8, 30.2, 30.1, 30.0, 28.0, 25.0, 22.0, 21.0,
+        ];
+        let samples: Vec<LogSample> = measured
+            .iter()
+            .enumerate()
+            .map(|(i, &t_measured)| LogSample {
+                timestamp_ms: i as u64 * 1000,
+                t_set: match i {
+                    0..=2 => 25.0,
+                    3..=9 => 30.0,
+                    _ => 20.0,
+                },
+                t_measured,
+            })
+            .collect();
+        let phases = [
+            phase("Hold", 0, 2000),
+            phase("Heat", 3000, 9000),
+            phase("Cool", 10_000, 13_000),
+        ];
+
+        let analysis = analyze(&samples, &phases, 0.5);
+        assert_eq!(analysis.transitions.len(), 2);
+
+        let heat = &analysis.transitions[0];
+        assert_eq!(heat.phase_name, "Heat");
+        assert_eq!((heat.from_temp, heat.target_temp), (25.0, 30.0));
+        assert_eq!(heat.time_to_cross_s, Some(2.0));
+        assert!((heat.overshoot_c - 1.5).abs() < 1e-6);
+        // 30.8 at 6 s is the last sample outside ±0.5
+        assert_eq!(heat.settling_time_s, Some(4.0));
+
+        let cool = &analysis.transitions[1];
+        assert_eq!((cool.from_temp, cool.target_temp), (30.0, 20.0));
+        assert_eq!(cool.time_to_cross_s, None);
+        assert_eq!(cool.overshoot_c, 0.0);
+        assert_eq!(cool.settling_time_s, None);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let samples: Vec<LogSample> = [25.0, 26.0, 24.0, 25.3]
+            .iter()
+            .enumerate()
+            .map(|(i, &t_measured)| LogSample {
+                timestamp_ms: i as u64 * 100,
+                t_set: 25.0,
+                t_measured,
+            })
+            .collect();
+
+        let summary = summarize(&samples, 0.5).unwrap();
+        assert_eq!(summary.samples, 4);
+        assert!((summary.rms_error - (2.09f32 / 4.0).sqrt()).abs() < 1e-5);
+        assert!((summary.max_deviation - 1.0).abs() < 1e-6);
+        assert_eq!(summary.within_tolerance_percent, 50.0);
+        assert_eq!(summarize(&[], 0.5), None);
+    }
+}
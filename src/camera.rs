@@ -1,34 +1,223 @@
 use serde::{Deserialize, Serialize};
-use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::fs;
+use std::path::Path;
 use std::process::{Child, Command, Stdio};
-use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 use nix::unistd::Pid;
-use nix::sys::signal::{self, Signal};
-use indicatif::{ProgressBar, ProgressStyle};
+use nix::sys::signal::Signal;
+
+/// Recording binaries to try, newest name first
+const CAMERA_BINARIES: [&str; 2] = ["rpicam-vid", "libcamera-vid"];
+
+/// Framerate of simulated timestamps when the config leaves it unset
+const SIMULATED_FPS: f32 = 30.0;
+
+/// Tool that copies the raw stream into a container
+const MUXER: &str = "ffmpeg";
+
+/// File format the recording is left in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoContainer {
+    /// Raw Annex-B stream as the camera writes it; no framerate, hard to seek
+    #[default]
+    H264,
+    Mp4,
+    Mkv,
+}
+
+impl VideoContainer {
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoContainer::H264 => "h264",
+            VideoContainer::Mp4 => "mp4",
+            VideoContainer::Mkv => "mkv",
+        }
+    }
+}
+
+/// Capture settings passed to `rpicam-vid`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraConfig {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub fps: Option<f32>, // None leaves the framerate to the sensor mode
+    pub bit_depth: u32,
+    pub mode_flag: String, // "P" packed or "U" unpacked
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    #[serde(default = "default_stop_timeout_s")]
+    pub stop_timeout_s: f32, // grace period after SIGINT before SIGKILL
+    #[serde(default = "default_start_timeout_s")]
+    pub start_timeout_s: f32, // wait for the first frame before giving up
+    #[serde(default)]
+    pub container: VideoContainer, // remuxed with ffmpeg after stopping
+    #[serde(default)]
+    pub annotation: Option<String>, // text burned into every frame
+}
+
+/// `seconds` of the camera setting `name` as a `Duration`; negative, NaN,
+/// infinite and overlarge values are an error rather than a panic
+fn timeout(name: &str, seconds: f32) -> Result<Duration, String> {
+    Duration::try_from_secs_f32(seconds)
+        .map_err(|_| format!("camera {} must be a non-negative number of seconds, got {}", name, seconds))
+}
+
+fn default_stop_timeout_s() -> f32 {
+    5.0
+}
+
+fn default_start_timeout_s() -> f32 {
+    10.0
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            width: 1920,
+            height: 1080,
+            fps: None,
+            bit_depth: 10,
+            mode_flag: "P".to_string(),
+            extra_args: Vec::new(),
+            stop_timeout_s: default_stop_timeout_s(),
+            start_timeout_s: default_start_timeout_s(),
+            container: VideoContainer::default(),
+            annotation: None,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// Check the timeouts, which become `Duration`s only when the camera
+    /// starts and stops, returning every problem found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let problems: Vec<String> = [
+            ("start_timeout_s", self.start_timeout_s),
+            ("stop_timeout_s", self.stop_timeout_s),
+        ]
+        .into_iter()
+        .filter_map(|(name, seconds)| timeout(name, seconds).err())
+        .collect();
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Capture arguments, excluding the output and timestamp paths
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--mode".to_string(),
+            format!("{}:{}:{}:{}", self.width, self.height, self.bit_depth, self.mode_flag),
+        ];
+        if let Some(fps) = self.fps {
+            args.push("--framerate".to_string());
+            args.push(fps.to_string());
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
 
 /// Camera controller for video recording
 pub struct Camera {
     process: Option<Child>,
     video_path: String,
     pts_path: String,
+    config_path: String,
+    annotate_path: String,
+    config: CameraConfig,
+    binary: Option<&'static str>,
+    /// Write placeholder output instead of running the camera binary
+    simulated: bool,
+    simulated_start: Option<Instant>,
 }
 
 impl Camera {
+    #[allow(dead_code, reason = "the experiment always passes its camera parameters")]
     pub fn new(experiment_dir: &str) -> Self {
+        Camera::with_config(experiment_dir, CameraConfig::default())
+    }
+
+    pub fn with_config(experiment_dir: &str, config: CameraConfig) -> Self {
         Camera {
             process: None,
             video_path: format!("{}/video.h264", experiment_dir),
             pts_path: format!("{}/timestamps.txt", experiment_dir),
+            config_path: format!("{}/camera.yaml", experiment_dir),
+            annotate_path: format!("{}/annotate.json", experiment_dir),
+            config,
+            binary: None,
+            simulated: false,
+            simulated_start: None,
+        }
+    }
+
+    /// Camera that needs no hardware: `start` creates an empty video file and
+    /// `stop` writes frame timestamps at the configured framerate
+    pub fn simulated(experiment_dir: &str, config: CameraConfig) -> Self {
+        let mut camera = Camera::with_config(experiment_dir, config);
+        camera.simulated = true;
+        camera
+    }
+
+    /// Where the recording is written; after `stop`, the container file if
+    /// remuxing succeeded
+    pub fn video_path(&self) -> &str {
+        &self.video_path
+    }
+
+    /// Find an installed recording binary, remembering it for `start`.
+    ///
+    /// Call before enabling hardware so a missing camera stack fails early.
+    pub fn probe(&mut self) -> std::io::Result<&'static str> {
+        if self.simulated {
+            return Ok("simulated");
+        }
+        if let Some(binary) = self.binary {
+            return Ok(binary);
+        }
+        for binary in CAMERA_BINARIES {
+            let found = Command::new(binary)
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+            match found {
+                Ok(_) => {
+                    self.binary = Some(binary);
+                    return Ok(binary);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
         }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "no camera binary found (tried {}); install rpicam-apps",
+                CAMERA_BINARIES.join(", ")
+            ),
+        ))
     }
 
+    /// Start recording, saving the camera settings alongside the video.
+    ///
+    /// Returns once the first frame timestamp has been written, so whatever
+    /// follows is on video; fails, stopping the process, if none appears
+    /// within `start_timeout_s`.
     pub fn start(&mut self) -> std::io::Result<()> {
         println!("Starting video capture...");
+        let binary = self.probe()?;
+        self.save_config()?;
 
-        let process = Command::new("rpicam-vid")
+        if self.simulated {
+            fs::write(&self.video_path, b"")?;
+            self.simulated_start = Some(Instant::now());
+            return Ok(());
+        }
+
+        let process = Command::new(binary)
             .args([
                 "-o",
                 &self.video_path,
@@ -38,18 +227,63 @@ impl Camera {
                 &self.pts_path,
                 "--flush",
                 "--nopreview",
-                "--mode",
-                "1920:1080:10:P",
             ])
+            .args(self.config.args())
+            .args(self.annotation_args()?)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()?;
 
         self.process = Some(process);
+        if let Err(e) = self.wait_for_first_frame() {
+            if let Some(mut process) = self.process.take() {
+                let _ = process.kill();
+                let _ = process.wait();
+            }
+            return Err(e);
+        }
         Ok(())
     }
 
+    /// Poll the PTS file until it holds a frame, the process exits or
+    /// `start_timeout_s` passes
+    fn wait_for_first_frame(&mut self) -> std::io::Result<()> {
+        let started = Instant::now();
+        let timeout = timeout("start_timeout_s", self.config.start_timeout_s).map_err(std::io::Error::other)?;
+        let pts_path = Path::new(&self.pts_path);
+
+        loop {
+            if parse_pts(pts_path).is_ok_and(|pts| !pts.is_empty()) {
+                println!("Camera recording; first frame after {:.1}s", started.elapsed().as_secs_f32());
+                return Ok(());
+            }
+            if let Some(process) = self.process.as_mut()
+                && let Some(status) = process.try_wait()?
+            {
+                return Err(std::io::Error::other(format!(
+                    "camera process exited with {} before writing a frame",
+                    status
+                )));
+            }
+            if started.elapsed() >= timeout {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!(
+                        "no frames in {} within {:.1}s of starting the camera",
+                        self.pts_path,
+                        timeout.as_secs_f32()
+                    ),
+                ));
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
     pub fn stop(&mut self) -> std::io::Result<()> {
+        if let Some(start) = self.simulated_start.take() {
+            return self.write_simulated_pts(start.elapsed());
+        }
+
         if let Some(mut process) = self.process.take() {
             println!("Stopping video recording...");
 
@@ -61,14 +295,193 @@ impl Camera {
 
             // Send SIGINT to gracefully stop recording
             nix::sys::signal::kill(pid, Some(Signal::SIGINT))
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                .map_err(std::io::Error::other)?;
 
             println!("Waiting for camera process to terminate...");
-            let status = process.wait()?;
+            // Rejected when the parameters load; should one get through, kill
+            // without a grace period rather than leave the camera running
+            let grace = timeout("stop_timeout_s", self.config.stop_timeout_s).unwrap_or(Duration::ZERO);
+            let deadline = Instant::now() + grace;
+            let status = loop {
+                if let Some(status) = process.try_wait()? {
+                    break status;
+                }
+                if Instant::now() >= deadline {
+                    eprintln!(
+                        "Warning: camera process did not exit within {:.1}s of SIGINT; killing it. \
+                         The recording may be truncated.",
+                        grace.as_secs_f32()
+                    );
+                    process.kill()?;
+                    break process.wait()?;
+                }
+                thread::sleep(Duration::from_millis(50));
+            };
             println!("Camera process exited with status: {}", status);
+            if self.config.container != VideoContainer::H264 {
+                self.remux();
+            }
         }
         Ok(())
     }
+
+    /// Copy the raw stream into the configured container, stamped with the
+    /// configured framerate or else the one measured from the frame
+    /// timestamps. Keeps the raw file, with a warning, if the muxer is
+    /// missing or fails; on success the raw file is removed.
+    fn remux(&mut self) {
+        let raw = self.video_path.clone();
+        let output = Path::new(&raw).with_extension(self.config.container.extension());
+        let fps = self
+            .config
+            .fps
+            .or_else(|| parse_pts(Path::new(&self.pts_path)).ok().and_then(|pts| measured_fps(&pts)));
+
+        println!("Remuxing {} into {}...", raw, output.display());
+        let mut command = Command::new(MUXER);
+        command.args(["-y", "-loglevel", "error"]);
+        if let Some(fps) = fps {
+            command.args(["-framerate", &fps.to_string()]);
+        }
+        let result = command
+            .args(["-i", &raw, "-c", "copy"])
+            .arg(&output)
+            .stdin(Stdio::null())
+            .output();
+
+        let failure = match result {
+            Ok(out) if out.status.success() => {
+                if let Err(e) = fs::remove_file(&raw) {
+                    eprintln!("Warning: could not remove {}: {}", raw, e);
+                }
+                self.video_path = output.to_string_lossy().to_string();
+                println!("Video saved to {}", self.video_path);
+                return;
+            }
+            Ok(out) => {
+                let _ = fs::remove_file(&output);
+                format!("{} exited with {}: {}", MUXER, out.status, String::from_utf8_lossy(&out.stderr).trim())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => format!("{} not found", MUXER),
+            Err(e) => format!("could not run {}: {}", MUXER, e),
+        };
+        eprintln!("Warning: {}; keeping the raw video {}", failure, raw);
+    }
+
+    fn write_simulated_pts(&self, duration: Duration) -> std::io::Result<()> {
+        let fps = self.config.fps.unwrap_or(SIMULATED_FPS);
+        let frames = (duration.as_secs_f32() * fps) as u64;
+        let mut pts = String::from("# timecode format v2\n");
+        for frame in 0..frames {
+            pts.push_str(&format!("{:.3}\n", frame as f32 * 1000.0 / fps));
+        }
+        fs::write(&self.pts_path, pts)
+    }
+
+    /// Write an `annotate_cv` post-processing stage drawing `annotation` on
+    /// every frame and return the arguments that load it; none without an
+    /// annotation.
+    ///
+    /// The text is fixed once recording starts, since rpicam-vid has no way
+    /// to change it mid-run, but it expands `%frame` and strftime codes per
+    /// frame. Needs rpicam-apps built with OpenCV.
+    fn annotation_args(&self) -> std::io::Result<Vec<String>> {
+        let Some(ref text) = self.config.annotation else {
+            return Ok(Vec::new());
+        };
+        let stage = serde_json::json!({
+            "annotate_cv": {
+                "text": text,
+                "fg": 255,
+                "bg": 0,
+                "scale": 1.0,
+                "thickness": 2,
+                "alpha": 0.3
+            }
+        });
+        let json = serde_json::to_string_pretty(&stage).map_err(std::io::Error::other)?;
+        fs::write(&self.annotate_path, json)?;
+        Ok(vec!["--post-process-file".to_string(), self.annotate_path.clone()])
+    }
+
+    fn save_config(&self) -> std::io::Result<()> {
+        let yaml_string = serde_yaml::to_string(&self.config).map_err(std::io::Error::other)?;
+        fs::write(&self.config_path, yaml_string)
+    }
+}
+
+/// Read the frame timestamps written by `--save-pts`, in microseconds.
+///
+/// The file holds one presentation time per line in milliseconds (timecode
+/// format v2, e.g. `33.333`); comment lines, a leading header and blank lines
+/// are skipped.
+pub fn parse_pts(path: &Path) -> std::io::Result<Vec<u64>> {
+    let contents = fs::read_to_string(path)?;
+    let mut timestamps = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.parse::<f64>() {
+            Ok(ms) => timestamps.push((ms * 1000.0).round() as u64),
+            // Tolerate a header row before the first value
+            Err(_) if timestamps.is_empty() => continue,
+            Err(e) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid PTS on line {}: {:?} ({})", index + 1, line, e),
+                ));
+            }
+        }
+    }
+    Ok(timestamps)
+}
+
+/// Average framerate of frames at `pts_us`, or None with fewer than two
+/// frames or no time between them
+pub fn measured_fps(pts_us: &[u64]) -> Option<f32> {
+    let (first, last) = (pts_us.first()?, pts_us.last()?);
+    let span_s = last.checked_sub(*first)? as f64 / 1_000_000.0;
+    (span_s > 0.0).then(|| ((pts_us.len() - 1) as f64 / span_s) as f32)
+}
+
+/// Pair each frame with the temperature log row closest in time.
+///
+/// `recording_start_ms` is the wall-clock time of PTS zero, on the same clock
+/// as the log's `timestamp_ms` column; `log_timestamps_ms` must be sorted.
+/// Returns `(frame_index, row_index)` pairs, or nothing if the log is empty.
+#[allow(dead_code, reason = "for offline analysis of timestamps.txt; no subcommand calls it yet")]
+pub fn match_frames_to_log(
+    frame_pts_us: &[u64],
+    recording_start_ms: u64,
+    log_timestamps_ms: &[u64],
+) -> Vec<(usize, usize)> {
+    if log_timestamps_ms.is_empty() {
+        return Vec::new();
+    }
+
+    frame_pts_us
+        .iter()
+        .enumerate()
+        .map(|(frame, &pts_us)| {
+            let frame_ms = recording_start_ms + pts_us / 1000;
+            let row = match log_timestamps_ms.binary_search(&frame_ms) {
+                Ok(row) => row,
+                Err(0) => 0,
+                Err(i) if i == log_timestamps_ms.len() => i - 1,
+                Err(i) => {
+                    if frame_ms - log_timestamps_ms[i - 1] <= log_timestamps_ms[i] - frame_ms {
+                        i - 1
+                    } else {
+                        i
+                    }
+                }
+            };
+            (frame, row)
+        })
+        .collect()
 }
 
 impl Drop for Camera {
@@ -76,3 +489,208 @@ impl Drop for Camera {
         let _ = self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    #[test]
+    fn test_default_config_matches_original_mode() {
+        assert_eq!(CameraConfig::default().args(), ["--mode", "1920:1080:10:P"]);
+    }
+
+    #[test]
+    fn test_config_args() {
+        let config = CameraConfig {
+            width: 1332,
+            height: 990,
+            fps: Some(60.0),
+            bit_depth: 10,
+            mode_flag: "U".to_string(),
+            extra_args: vec!["--shutter".to_string(), "5000".to_string()],
+            ..CameraConfig::default()
+        };
+        assert_eq!(
+            config.args(),
+            ["--mode", "1332:990:10:U", "--framerate", "60", "--shutter", "5000"]
+        );
+    }
+
+    #[test]
+    fn test_parse_pts() {
+        let scratch = ScratchDir::new("pts");
+        let path = scratch.path().join("timestamps.txt");
+        fs::write(&path, "# timecode format v2\n0.000\n\n33.333\n66.667\n").unwrap();
+        let pts = parse_pts(&path).unwrap();
+        fs::write(&path, "0.000\nframe\n").unwrap();
+        let invalid = parse_pts(&path);
+
+        assert_eq!(pts, [0, 33_333, 66_667]);
+        assert_eq!(invalid.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_annotation_args() {
+        let scratch = ScratchDir::new("annotate");
+        let dir = scratch.as_str();
+
+        assert!(Camera::new(dir).annotation_args().unwrap().is_empty());
+        let camera = Camera::with_config(
+            dir,
+            CameraConfig {
+                annotation: Some("Snap 27°C %X frame %frame".to_string()),
+                ..CameraConfig::default()
+            },
+        );
+        let args = camera.annotation_args().unwrap();
+        let stage: serde_json::Value = serde_json::from_str(&fs::read_to_string(&camera.annotate_path).unwrap()).unwrap();
+
+        assert_eq!(args, ["--post-process-file", &format!("{}/annotate.json", dir)]);
+        assert_eq!(stage["annotate_cv"]["text"], "Snap 27°C %X frame %frame");
+    }
+
+    #[test]
+    fn test_measured_fps() {
+        assert_eq!(measured_fps(&[0, 33_333, 66_667, 100_000]), Some(30.0));
+        assert_eq!(measured_fps(&[0, 20_000, 40_000]), Some(50.0));
+        assert_eq!(measured_fps(&[5_000]), None);
+        assert_eq!(measured_fps(&[]), None);
+    }
+
+    #[test]
+    fn test_remux_keeps_raw_video_on_failure() {
+        let scratch = ScratchDir::new("remux");
+        let dir = scratch.as_str();
+        let mut camera = Camera::with_config(
+            dir,
+            CameraConfig {
+                container: VideoContainer::Mp4,
+                ..CameraConfig::default()
+            },
+        );
+        // Not an H.264 stream, so the remux fails whether or not ffmpeg is installed
+        fs::write(camera.video_path(), b"not video").unwrap();
+
+        camera.remux();
+        let raw_kept = Path::new(camera.video_path()).exists();
+        let mp4_left = Path::new(&format!("{}/video.mp4", dir)).exists();
+
+        assert!(camera.video_path().ends_with("video.h264"));
+        assert!(raw_kept);
+        assert!(!mp4_left);
+    }
+
+    #[test]
+    fn test_container_from_yaml() {
+        let config: CameraConfig =
+            serde_yaml::from_str("width: 1920\nheight: 1080\nbit_depth: 10\nmode_flag: P\ncontainer: mkv\n").unwrap();
+        assert_eq!(config.container, VideoContainer::Mkv);
+        assert_eq!(config.container.extension(), "mkv");
+        let config: CameraConfig =
+            serde_yaml::from_str("width: 1920\nheight: 1080\nbit_depth: 10\nmode_flag: P\n").unwrap();
+        assert_eq!(config.container, VideoContainer::H264);
+    }
+
+    #[test]
+    fn test_validate_timeouts() {
+        assert!(CameraConfig::default().validate().is_ok());
+        let config = CameraConfig {
+            start_timeout_s: f32::INFINITY,
+            stop_timeout_s: 1e30,
+            ..CameraConfig::default()
+        };
+        assert_eq!(config.validate().unwrap_err().len(), 2);
+        let config = CameraConfig {
+            start_timeout_s: -1.0,
+            ..CameraConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_match_frames_to_log() {
+        let log = [1_000, 1_100, 1_200];
+        let frames = [0, 40_000, 70_000, 500_000];
+        assert_eq!(
+            match_frames_to_log(&frames, 990, &log),
+            [(0, 0), (1, 0), (2, 1), (3, 2)]
+        );
+        assert!(match_frames_to_log(&frames, 990, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_simulated_camera_writes_outputs() {
+        let scratch = ScratchDir::new("camera");
+        let dir = scratch.as_str();
+
+        let mut camera = Camera::simulated(dir, CameraConfig::default());
+        camera.start().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        camera.stop().unwrap();
+
+        let pts = parse_pts(Path::new(&format!("{}/timestamps.txt", dir))).unwrap();
+        assert!(Path::new(&format!("{}/video.h264", dir)).exists());
+        assert!(Path::new(&format!("{}/camera.yaml", dir)).exists());
+        assert!(pts.len() >= 5);
+        assert_eq!(pts[1], 33_333);
+    }
+
+    #[test]
+    fn test_wait_for_first_frame() {
+        let scratch = ScratchDir::new("first-frame");
+        let mut camera = Camera::with_config(
+            scratch.as_str(),
+            CameraConfig {
+                start_timeout_s: 0.5,
+                ..CameraConfig::default()
+            },
+        );
+        let spawn = |script: String| Command::new("sh").args(["-c", &script]).spawn().unwrap();
+
+        // The header alone is not a frame
+        camera.process = Some(spawn(format!(
+            "printf '# timecode format v2\\n' > {0}; sleep 0.2; printf '0.000\\n' >> {0}; sleep 30",
+            camera.pts_path
+        )));
+        camera.wait_for_first_frame().unwrap();
+        let mut process = camera.process.take().unwrap();
+        process.kill().unwrap();
+        process.wait().unwrap();
+
+        fs::remove_file(&camera.pts_path).unwrap();
+        camera.process = Some(spawn("exit 3".to_string()));
+        let exited = camera.wait_for_first_frame().unwrap_err();
+        assert!(exited.to_string().contains("exited"), "{}", exited);
+
+        camera.process = Some(spawn("sleep 30".to_string()));
+        let timed_out = camera.wait_for_first_frame().unwrap_err();
+        assert_eq!(timed_out.kind(), std::io::ErrorKind::TimedOut);
+        let mut process = camera.process.take().unwrap();
+        process.kill().unwrap();
+        process.wait().unwrap();
+    }
+
+    #[test]
+    fn test_stop_kills_process_ignoring_sigint() {
+        let scratch = ScratchDir::new("stop");
+        let mut camera = Camera::with_config(
+            scratch.as_str(),
+            CameraConfig {
+                stop_timeout_s: 0.2,
+                ..CameraConfig::default()
+            },
+        );
+        camera.process = Some(
+            Command::new("sh")
+                .args(["-c", "trap '' INT; sleep 30"])
+                .spawn()
+                .unwrap(),
+        );
+
+        let start = Instant::now();
+        camera.stop().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(camera.process.is_none());
+    }
+}
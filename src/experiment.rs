@@ -1,13 +1,184 @@
-use crate::camera::Camera;
-use crate::fan::Fan;
+use crate::analysis::LogSample;
+use crate::camera::{Camera, CameraConfig};
+use crate::fan::{Fan, FanCurve};
+use crate::manifest::{MANIFEST_FILE, RunManifest, VideoFile};
+use crate::monitor::{MonitorEvent, Subscriptions, TecMonitor};
+use crate::sim::SimulatedLink;
 use crate::tec::*;
+use crate::time::{self, Clock, SystemClock};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 use indicatif::{ProgressBar, ProgressStyle};
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use serialport::TTYPort;
+
+/// Set from the signal handler; checked at phase boundaries and while waiting
+static ABORT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Why an experiment or recording stopped, with the step that failed
+#[derive(Debug)]
+pub enum ExperimentError {
+    /// Interrupted by Ctrl-C / SIGTERM
+    Aborted,
+    /// Problems found by `Parameters::validate` before touching hardware
+    InvalidParameters(Vec<String>),
+    /// The experiment directory, its log or its results could not be written
+    Files(std::io::Error),
+    /// The camera could not be found, started or stopped
+    Camera(std::io::Error),
+    /// A TEC rejected its starting configuration
+    Configure { tec: String, source: TecError },
+    /// A TEC did not switch on
+    Enable { tec: String, source: TecError },
+    /// Changing the setpoint failed during `phase`
+    Setpoint { phase: String, source: TecError },
+    /// `phase` gave up waiting for the temperature to settle at `target`
+    Stabilization { phase: String, target: f32 },
+}
+
+impl std::fmt::Display for ExperimentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExperimentError::Aborted => write!(f, "Experiment aborted"),
+            ExperimentError::InvalidParameters(problems) => {
+                write!(f, "Invalid parameters:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+            ExperimentError::Files(e) => write!(f, "Failed to write experiment files: {}", e),
+            ExperimentError::Camera(e) => write!(f, "Camera failed: {}", e),
+            ExperimentError::Configure { tec, source } => write!(f, "Failed to configure {}: {}", tec, source),
+            ExperimentError::Enable { tec, source } => write!(f, "Failed to enable {}: {}", tec, source),
+            ExperimentError::Setpoint { phase, source } => {
+                write!(f, "Failed to change the setpoint during {}: {}", phase, source)
+            }
+            ExperimentError::Stabilization { phase, target } => write!(
+                f,
+                "Timed out during {} waiting for temperature to reach {:.1}°C",
+                phase, target
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExperimentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExperimentError::Files(e) | ExperimentError::Camera(e) => Some(e),
+            ExperimentError::Configure { source, .. }
+            | ExperimentError::Enable { source, .. }
+            | ExperimentError::Setpoint { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+extern "C" fn request_abort(_signal: nix::libc::c_int) {
+    ABORT_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Route SIGINT and SIGTERM to the abort flag instead of killing the process,
+/// so a running experiment can shut the hardware down before returning
+pub fn install_abort_handler() -> nix::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(request_abort),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // The handler only stores to an atomic, which is async-signal-safe
+    unsafe {
+        signal::sigaction(Signal::SIGINT, &action)?;
+        signal::sigaction(Signal::SIGTERM, &action)?;
+    }
+    Ok(())
+}
+
+/// Abort the running experiment as SIGINT would, e.g. for a stop request
+/// from the control server
+#[cfg(feature = "server")]
+pub fn abort_experiment() {
+    ABORT_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Forget an abort once the experiment it stopped has shut down, so the
+/// process can start another
+#[cfg(feature = "server")]
+pub fn clear_abort() {
+    ABORT_REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// `ExperimentError::Aborted` once SIGINT or SIGTERM has been received
+pub fn check_abort() -> Result<(), ExperimentError> {
+    check_abort_flag(&ABORT_REQUESTED)
+}
+
+/// `ExperimentError::Aborted` once `abort` is set
+fn check_abort_flag(abort: &AtomicBool) -> Result<(), ExperimentError> {
+    if abort.load(Ordering::SeqCst) {
+        Err(ExperimentError::Aborted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Time between fan curve updates
+const FAN_CONTROL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time between setpoint updates while ramping
+const RAMP_STEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time between temperature log rows
+const LOG_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Running temperature logger; stopping it (explicitly or on drop) ends the
+/// polling loop, joins the thread and flushes the log
+pub struct TemperatureLogger {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+    /// One per controller, in `Experiment::tecs` order
+    subscriptions: Vec<Subscriptions>,
+}
+
+impl TemperatureLogger {
+    /// Receive every readout of each controller from now on, in
+    /// `Experiment::tecs` order
+    fn subscribe(&self) -> Vec<Receiver<TecReadout>> {
+        self.subscriptions.iter().map(Subscriptions::subscribe).collect()
+    }
+
+    /// Signal the thread and wait for its final flush; later calls do nothing
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for TemperatureLogger {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// CSV header matching the rows written by `append_log_entry`
+const LOG_HEADER: &str = "timestamp_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM,resyncs,phase,T_measured_raw\n";
+/// `LOG_HEADER` of a log with `LogTimestamps::Relative`
+const RELATIVE_LOG_HEADER: &str = "elapsed_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM,resyncs,phase,T_measured_raw\n";
+/// `LOG_HEADER` of a log with `LogTimestamps::Both`; the extra column goes
+/// before the phase so the readout columns keep their positions
+const BOTH_LOG_HEADER: &str =
+    "timestamp_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM,resyncs,elapsed_ms,phase,T_measured_raw\n";
 
 #[derive(Serialize, Deserialize)]
 pub struct Parameters {
@@ -18,6 +189,48 @@ pub struct Parameters {
     pub postrecord_time: f32,       // in seconds
     pub temperature_tolerance: f32, // tolerance for reaching target temp
     pub max_wait_time: f32,         // max time to wait for temperature stabilization
+    #[serde(default)]
+    pub ramp_rate: Option<f32>,     // °C/s for setpoint changes that wait for stabilization
+    #[serde(default)]
+    pub stabilization: StabilizationMode,
+    #[serde(default)]
+    pub fan_curve: Option<FanCurve>, // drive the fan from TEC PWM; None runs it at full
+    #[serde(default)]
+    pub camera: CameraConfig,
+    #[serde(default = "default_cycles")]
+    pub cycles: u32,                // rest → snap → rest repetitions
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub log_timestamps: LogTimestamps,
+    #[serde(default = "default_save_plot")]
+    pub save_plot: bool,            // temperature_plot.png, with the `plot` feature
+    #[serde(default)]
+    pub display_unit: TemperatureUnit, // console output only; files stay in °C
+    #[serde(default)]
+    pub setpoint_bounds: SetpointBounds, // soft limits every controller enforces
+    #[serde(default)]
+    pub output_dir: Option<String>, // base of the experiment directories; `~` expands
+    #[serde(default)]
+    pub calibration_offset: f32, // °C added to logged temperatures; control is unchanged
+    #[serde(default)]
+    pub config_tolerance: ConfigTolerance, // drift allowed in acknowledged configurations
+    #[serde(default = "default_resync_lines")]
+    pub resync_lines: usize,        // lines read past a corrupted readout; 0 fails at once
+    #[serde(default)]
+    pub ack_policy: AckPolicy,      // whether a mismatched acknowledgment fails the command
+}
+
+fn default_cycles() -> u32 {
+    1
+}
+
+fn default_save_plot() -> bool {
+    true
+}
+
+fn default_resync_lines() -> usize {
+    DEFAULT_RESYNC_LINES
 }
 
 impl Default for Parameters {
@@ -30,367 +243,1498 @@ impl Default for Parameters {
             postrecord_time: 10.0,
             temperature_tolerance: 0.5,
             max_wait_time: 30.0,
+            ramp_rate: None,
+            stabilization: StabilizationMode::Tolerance,
+            fan_curve: None,
+            camera: CameraConfig::default(),
+            cycles: default_cycles(),
+            log_format: LogFormat::Csv,
+            log_timestamps: LogTimestamps::Absolute,
+            save_plot: default_save_plot(),
+            display_unit: TemperatureUnit::Celsius,
+            setpoint_bounds: SetpointBounds::default(),
+            output_dir: None,
+            calibration_offset: 0.0,
+            config_tolerance: ConfigTolerance::default(),
+            resync_lines: default_resync_lines(),
+            ack_policy: AckPolicy::default(),
         }
     }
 }
 
+impl Parameters {
+    /// Load parameters from a YAML file; omitted optional fields take their defaults
+    pub fn from_yaml_file(path: &str) -> std::io::Result<Self> {
+        Parameters::from_yaml_str(&fs::read_to_string(path)?)
+    }
+
+    /// Parse YAML parameters, naming every missing required field at once
+    /// instead of serde's first-failure message
+    pub fn from_yaml_str(contents: &str) -> std::io::Result<Self> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        let value: serde_yaml::Value =
+            serde_yaml::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+        let Some(mapping) = value.as_mapping() else {
+            return Err(invalid("parameters must be a mapping of field names to values".to_string()));
+        };
+        let missing: Vec<String> = Parameters::required_fields()
+            .into_iter()
+            .filter(|field| !mapping.contains_key(field.as_str()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(invalid(format!("missing required fields: {}", missing.join(", "))));
+        }
+
+        // Parse the text again: errors from a `Value` lose the field path and line
+        let params: Parameters = serde_yaml::from_str(contents).map_err(|e| invalid(e.to_string()))?;
+        params.camera.validate().map_err(|problems| invalid(problems.join("; ")))?;
+        Ok(params)
+    }
+
+    /// Fields without a serde default, in declaration order: those whose
+    /// removal from the serialized defaults makes deserialization fail
+    fn required_fields() -> Vec<String> {
+        let Ok(serde_yaml::Value::Mapping(defaults)) = serde_yaml::to_value(Parameters::default()) else {
+            return Vec::new();
+        };
+        defaults
+            .keys()
+            .filter_map(|key| {
+                let mut without = defaults.clone();
+                without.remove(key);
+                let required = serde_yaml::from_value::<Parameters>(serde_yaml::Value::Mapping(without)).is_err();
+                key.as_str().filter(|_| required).map(str::to_string)
+            })
+            .collect()
+    }
+
+    /// Check the parameters against the TEC's temperature limits, returning
+    /// every problem found rather than stopping at the first
+    pub fn validate(&self, config: &TecConfig) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        let bounds = self.setpoint_bounds;
+        if let Err(e) = bounds.validate() {
+            problems.push(e.to_string());
+        }
+        for (name, temp) in [("rest_temp", self.rest_temp), ("snap_temp", self.snap_temp)] {
+            if !(config.t_min..=config.t_max).contains(&temp) {
+                problems.push(format!(
+                    "{} {:.1}°C is outside the TEC limits {:.1}..{:.1}°C",
+                    name, temp, config.t_min, config.t_max
+                ));
+            }
+            if !bounds.contains(temp) {
+                problems.push(format!(
+                    "{} {:.1}°C is outside setpoint_bounds {:.1}..{:.1}°C",
+                    name, temp, bounds.min, bounds.max
+                ));
+            }
+        }
+
+        for (name, duration) in [
+            ("snap_hold_time", self.snap_hold_time),
+            ("prerecord_time", self.prerecord_time),
+            ("postrecord_time", self.postrecord_time),
+        ] {
+            if !duration.is_finite() || duration < 0.0 {
+                problems.push(format!("{} must be non-negative, got {}", name, duration));
+            }
+        }
+
+        if !self.max_wait_time.is_finite() || self.max_wait_time <= 0.0 {
+            problems.push(format!("max_wait_time must be positive, got {}", self.max_wait_time));
+        }
+        if !self.temperature_tolerance.is_finite() || self.temperature_tolerance <= 0.0 {
+            problems.push(format!(
+                "temperature_tolerance must be positive, got {}",
+                self.temperature_tolerance
+            ));
+        }
+        let tolerance = self.config_tolerance;
+        for (name, value) in [("temperature", tolerance.temperature), ("pid", tolerance.pid)] {
+            if !value.is_finite() || value < 0.0 {
+                problems.push(format!("config_tolerance.{} must be non-negative, got {}", name, value));
+            }
+        }
+        if self.cycles == 0 {
+            problems.push("cycles must be at least 1".to_string());
+        }
+        if let Err(camera_problems) = self.camera.validate() {
+            problems.extend(camera_problems);
+        }
+        if let Some(rate) = self.ramp_rate
+            && (!rate.is_finite() || rate <= 0.0)
+        {
+            problems.push(format!("ramp_rate must be positive, got {}", rate));
+        }
+        if let StabilizationMode::Slope { window, max_slope, band } = self.stabilization {
+            if window < 2 {
+                problems.push(format!("stabilization window must be at least 2, got {}", window));
+            }
+            if !max_slope.is_finite() || max_slope <= 0.0 {
+                problems.push(format!("stabilization max_slope must be positive, got {}", max_slope));
+            }
+            if !band.is_finite() || band <= 0.0 {
+                problems.push(format!("stabilization band must be positive, got {}", band));
+            }
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    /// Directory the experiment directories and index go under,
+    /// `DEFAULT_OUTPUT_DIR` unless `output_dir` is set
+    pub fn output_base(&self) -> PathBuf {
+        expand_home(self.output_dir.as_deref().unwrap_or(DEFAULT_OUTPUT_DIR))
+    }
+}
+
+/// `path` with a leading `~` replaced by `$HOME`; unchanged if `HOME` is unset
+fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with('/') => &rest[1..],
+        _ => return PathBuf::from(path),
+    };
+    match std::env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// File format of the temperature log
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// `temperature_log.csv` with a `LOG_HEADER` row
+    #[default]
+    Csv,
+    /// `temperature_log.jsonl`, one `LogRecord` object per line
+    JsonLines,
+}
+
+impl LogFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            LogFormat::Csv => "temperature_log.csv",
+            LogFormat::JsonLines => "temperature_log.jsonl",
+        }
+    }
+
+    /// Log file of one controller when an experiment drives several
+    pub fn labelled_file_name(self, label: &str) -> String {
+        let extension = match self {
+            LogFormat::Csv => "csv",
+            LogFormat::JsonLines => "jsonl",
+        };
+        format!("temperature_log_{}.{}", label, extension)
+    }
+}
+
+/// Which time column the temperature log carries
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTimestamps {
+    /// `timestamp_ms`, wall-clock milliseconds since the Unix epoch
+    #[default]
+    Absolute,
+    /// `elapsed_ms`, milliseconds since the log was started
+    Relative,
+    /// `timestamp_ms` and `elapsed_ms`
+    Both,
+}
+
+impl LogTimestamps {
+    /// CSV header of a log written with these timestamps
+    fn csv_header(self) -> &'static str {
+        match self {
+            LogTimestamps::Absolute => LOG_HEADER,
+            LogTimestamps::Relative => RELATIVE_LOG_HEADER,
+            LogTimestamps::Both => BOTH_LOG_HEADER,
+        }
+    }
+}
+
+/// Everything the logging thread needs to write a row
+#[derive(Clone, Copy, Debug)]
+struct LogSettings {
+    format: LogFormat,
+    timestamps: LogTimestamps,
+    /// Wall-clock time `elapsed_ms` counts from
+    start_ms: u64,
+}
+
+/// Where experiment directories go when `Parameters::output_dir` is unset
+pub const DEFAULT_OUTPUT_DIR: &str = "experiments";
+
+/// Catalog of every run under an output directory, one `IndexEntry` per line
+const INDEX_FILE: &str = "index.jsonl";
+
+/// Line of `index.jsonl` in the output directory. Each run appends a `started` entry
+/// when its directory is created and another once it ends; the latest entry
+/// for a directory is its status.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    /// e.g. `experiments/experiment_1700000000000`
+    pub directory: String,
+    pub timestamp_ms: u64,
+    pub status: RunStatus,
+    /// Key parameters, on the `started` entry only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rest_temp: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snap_temp: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cycles: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Started,
+    Completed,
+    Failed,
+    Aborted,
+}
+
+/// One line of a JSON-lines temperature log
+#[derive(Serialize)]
+struct LogRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>,
+    #[serde(flatten)]
+    readout: &'a TecReadout,
+    /// `t_measured` before the calibration offset
+    t_measured_raw: f32,
+    phase: &'a str,
+}
+
+/// How `wait_for_temperature` decides the sample has settled
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum StabilizationMode {
+    /// Within `temperature_tolerance` of the target on a single readout
+    #[default]
+    Tolerance,
+    /// Least-squares slope over the last `window` readouts (taken ~1 s apart)
+    /// is at most `max_slope` °C/s, and the latest readout is within `band`
+    Slope {
+        window: usize,
+        max_slope: f32,
+        band: f32,
+    },
+}
+
+impl StabilizationMode {
+    /// `samples` are (seconds, °C) pairs, oldest first
+    fn is_stable(&self, samples: &VecDeque<(f32, f32)>, target: f32, tolerance: f32) -> bool {
+        let Some(&(_, latest)) = samples.back() else {
+            return false;
+        };
+        let diff = (latest - target).abs();
+        match *self {
+            StabilizationMode::Tolerance => diff <= tolerance,
+            StabilizationMode::Slope { window, max_slope, band } => {
+                if samples.len() < window.max(2) || diff > band {
+                    return false;
+                }
+                let recent = samples.iter().skip(samples.len() - window.max(2));
+                slope(recent).is_some_and(|rate| rate.abs() <= max_slope)
+            }
+        }
+    }
+
+    /// Readouts worth keeping for `is_stable`
+    fn history_len(&self) -> usize {
+        match *self {
+            StabilizationMode::Tolerance => 1,
+            StabilizationMode::Slope { window, .. } => window.max(2),
+        }
+    }
+}
+
+/// One step of a temperature profile
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProfileStep {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub target_temp: f32,
+    pub hold_time_s: f32, // held after the setpoint is reached (or set)
+    #[serde(default)]
+    pub wait_for_stabilization: bool,
+}
+
+impl ProfileStep {
+    fn named(
+        name: impl Into<String>,
+        target_temp: f32,
+        hold_time_s: f32,
+        wait_for_stabilization: bool,
+    ) -> Self {
+        ProfileStep {
+            name: Some(name.into()),
+            target_temp,
+            hold_time_s,
+            wait_for_stabilization,
+        }
+    }
+
+    /// Phase name used in timings and progress output
+    fn label(&self, number: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("Step {} ({:.1}°C)", number, self.target_temp))
+    }
+}
+
+/// Sequence of temperature steps run while recording
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TemperatureProfile {
+    pub steps: Vec<ProfileStep>,
+}
+
+impl TemperatureProfile {
+    /// The rest → snap → hold → rest protocol described by `Parameters`
+    pub fn classic(params: &Parameters) -> Self {
+        let mut steps = vec![ProfileStep::named(
+            "Pre-record at rest temp",
+            params.rest_temp,
+            params.prerecord_time,
+            false,
+        )];
+
+        for cycle in 1..=params.cycles {
+            let name = |phase: &str| {
+                if params.cycles > 1 {
+                    format!("{} (cycle {})", phase, cycle)
+                } else {
+                    phase.to_string()
+                }
+            };
+            steps.push(ProfileStep::named(name("Heat to snap temp"), params.snap_temp, 0.0, true));
+            steps.push(ProfileStep::named(
+                name("Hold at snap temp"),
+                params.snap_temp,
+                params.snap_hold_time,
+                false,
+            ));
+            // Don't wait for the last cooldown so the cooling is captured in the
+            // post-record; earlier cycles must be back at rest before reheating
+            steps.push(ProfileStep::named(
+                name("Initiate cooling"),
+                params.rest_temp,
+                0.0,
+                cycle < params.cycles,
+            ));
+        }
+
+        steps.push(ProfileStep::named("Post-record", params.rest_temp, params.postrecord_time, false));
+        TemperatureProfile { steps }
+    }
+
+    /// Load a profile from a YAML file with a top-level `steps` list
+    pub fn from_yaml_file(path: &str) -> std::io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_yaml::from_str(&contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PhaseTiming {
     pub phase_name: String,
     pub start_time_ms: u64,
     pub end_time_ms: u64,
     pub duration_s: f64,
+    /// First controller's measured temperature and setpoint as the phase
+    /// started and ended; absent if that readout failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t_measured_start: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t_measured_end: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t_set_start: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t_set_end: Option<f32>,
 }
 
-pub struct Experiment {
-    tec: Arc<Mutex<TecController>>,
-    fan: Fan,
+/// A phase boundary: when it was and what the first controller read then
+struct PhaseMark {
+    time_ms: u64,
+    readout: Option<TecReadout>,
+}
+
+pub struct Experiment<P: SerialLink = TTYPort> {
+    /// Controllers driven in lockstep, each with the label its files are
+    /// named after; the first one feeds the fan curve and metrics
+    tecs: Vec<(String, Arc<Mutex<TecController<P>>>)>,
+    /// None when running without active cooling; fan calls are skipped
+    fan: Option<Arc<Fan>>,
     params: Parameters,
     experiment_dir: String,
     phase_timings: Vec<PhaseTiming>,
+    /// Most recent readout of the first controller from the logging thread
+    latest_readout: Arc<Mutex<Option<TecReadout>>>,
+    /// Name of the running phase, written into each log row
+    current_phase: Arc<Mutex<String>>,
+    /// When the temperature log was started, the zero of `elapsed_ms`
+    start_ms: u64,
+    /// Record placeholder video instead of running the camera
+    simulated: bool,
+    /// Time source of the log rows and phase timings
+    clock: Arc<dyn Clock>,
+}
+
+impl Experiment<SimulatedLink> {
+    /// Experiment against a modelled TEC, a dry-run fan and a placeholder
+    /// camera, for rehearsing a protocol without hardware
+    pub fn new_simulated(params: Parameters) -> Result<Self, Box<dyn std::error::Error>> {
+        let controller = TecController::with_link(SimulatedLink::new())?;
+        let fan = Fan::dry_run(25_000)?;
+        let mut experiment = Experiment::new(Arc::new(Mutex::new(controller)), Some(fan), params);
+        experiment.simulated = true;
+        Ok(experiment)
+    }
 }
 
-impl Experiment {
-    pub fn new(tec_controller: Arc<Mutex<TecController>>, fan: Fan, params: Parameters) -> Self {
+impl<P: SerialLink + 'static> Experiment<P> {
+    pub fn new(tec_controller: Arc<Mutex<TecController<P>>>, fan: Option<Fan>, params: Parameters) -> Self {
+        Experiment::with_controllers(vec![("tec".to_string(), tec_controller)], fan, params)
+    }
+
+    /// Drive several labelled controllers through the same setpoints.
+    ///
+    /// Each gets its own `temperature_log_<label>` file and analysis, and
+    /// stabilization waits for all of them. Panics if `tecs` is empty.
+    pub fn with_controllers(
+        tecs: Vec<(String, Arc<Mutex<TecController<P>>>)>,
+        fan: Option<Fan>,
+        params: Parameters,
+    ) -> Self {
+        assert!(!tecs.is_empty(), "an experiment needs at least one TEC controller");
+        for (_, tec) in &tecs {
+            let mut controller = tec.lock().unwrap();
+            controller.set_setpoint_bounds(params.setpoint_bounds);
+            controller.set_calibration_offset(params.calibration_offset);
+            controller.set_config_tolerance(params.config_tolerance);
+            controller.set_resync_lines(params.resync_lines);
+            controller.set_ack_policy(params.ack_policy);
+        }
         Experiment {
-            tec: tec_controller,
-            fan,
+            tecs,
+            fan: fan.map(Arc::new),
             params,
             experiment_dir: String::new(),
             phase_timings: Vec::new(),
+            latest_readout: Arc::new(Mutex::new(None)),
+            current_phase: Arc::new(Mutex::new(String::new())),
+            start_ms: 0,
+            simulated: false,
+            clock: Arc::new(SystemClock),
         }
     }
 
-    fn record_phase_timing(&mut self, phase_name: String, start_time_ms: u64, end_time_ms: u64) {
-        let duration_s = (end_time_ms - start_time_ms) as f64 / 1000.0;
+    /// Take log and phase timestamps from `clock` instead of the system clock
+    #[cfg(test)]
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Readout cache filled by the logging thread, for serving elsewhere
+    #[cfg(feature = "metrics")]
+    pub fn latest_readout(&self) -> Arc<Mutex<Option<TecReadout>>> {
+        Arc::clone(&self.latest_readout)
+    }
+
+    /// Mark a phase boundary, taking a fresh readout of the first
+    /// controller under its lock so the timing carries the temperature at
+    /// that moment without joining against the log
+    fn phase_mark(&self) -> PhaseMark {
+        let time_ms = self.clock.now_ms();
+        let readout = match self.primary().lock().unwrap().get_single_readout() {
+            Ok(readout) => Some(readout),
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to read the temperature at the {} phase boundary: {}",
+                    self.current_phase(),
+                    e
+                );
+                None
+            }
+        };
+        PhaseMark { time_ms, readout }
+    }
+
+    fn record_phase_timing(&mut self, phase_name: String, start: PhaseMark, end: PhaseMark) {
+        let reading = |mark: &PhaseMark, field: fn(&TecReadout) -> f32| mark.readout.as_ref().map(field);
         self.phase_timings.push(PhaseTiming {
             phase_name,
-            start_time_ms,
-            end_time_ms,
-            duration_s,
+            start_time_ms: start.time_ms,
+            end_time_ms: end.time_ms,
+            duration_s: time::elapsed_s(start.time_ms, end.time_ms),
+            t_measured_start: reading(&start, |readout| readout.t_measured),
+            t_measured_end: reading(&end, |readout| readout.t_measured),
+            t_set_start: reading(&start, |readout| readout.t_set),
+            t_set_end: reading(&end, |readout| readout.t_set),
         });
     }
 
+    /// Write `run_manifest.yaml`, hashing the video at `video_path`
+    fn save_run_manifest(&self, profile: &TemperatureProfile, video_path: &str) -> std::io::Result<()> {
+        let manifest = RunManifest {
+            directory: &self.experiment_dir,
+            parameters: &self.params,
+            profile,
+            tec_configs: self
+                .tecs
+                .iter()
+                .map(|(label, tec)| (label.as_str(), tec.lock().unwrap().current_config()))
+                .collect(),
+            camera: &self.params.camera,
+            phase_timings: &self.phase_timings,
+            video: VideoFile::hash(Path::new(video_path))?,
+        };
+        let manifest_path = format!("{}/{}", self.experiment_dir, MANIFEST_FILE);
+        save_yaml(&manifest_path, &manifest)?;
+        println!("Run manifest saved to: {}", manifest_path);
+        Ok(())
+    }
+
     fn save_phase_timings(&self) -> std::io::Result<()> {
         let timings_path = format!("{}/phase_timings.yaml", self.experiment_dir);
-        
-        let yaml_string = serde_yaml::to_string(&self.phase_timings)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-        
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&timings_path)?;
-        file.write_all(yaml_string.as_bytes())?;
+        save_yaml(&timings_path, &self.phase_timings)?;
         println!("Phase timings saved to: {}", timings_path);
         Ok(())
     }
 
-    fn initialize_log_file(&self) -> std::io::Result<()> {
-        let header = "timestamp_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM\n";
-        let log_path = format!("{}/temperature_log.csv", self.experiment_dir);
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&log_path)?;
-        file.write_all(header.as_bytes())?;
+    fn log_settings(&self) -> LogSettings {
+        LogSettings {
+            format: self.params.log_format,
+            timestamps: self.params.log_timestamps,
+            start_ms: self.start_ms,
+        }
+    }
+
+    /// Samples of the log at `index`, on the wall clock of the phase timings
+    fn read_log_samples(&self, index: usize) -> Result<Vec<LogSample>, Box<dyn std::error::Error>> {
+        let mut samples = crate::analysis::read_log(&self.log_path(index), self.params.log_format)?;
+        if self.params.log_timestamps == LogTimestamps::Relative {
+            for sample in &mut samples {
+                sample.timestamp_ms += self.start_ms;
+            }
+        }
+        Ok(samples)
+    }
+
+    fn primary(&self) -> &Arc<Mutex<TecController<P>>> {
+        &self.tecs[0].1
+    }
+
+    /// Temperature log of the controller at `index`; a single controller
+    /// keeps the unlabelled name
+    fn log_path(&self, index: usize) -> String {
+        let file_name = match self.tecs.len() {
+            1 => self.params.log_format.file_name().to_string(),
+            _ => self.params.log_format.labelled_file_name(&self.tecs[index].0),
+        };
+        format!("{}/{}", self.experiment_dir, file_name)
+    }
+
+    /// How console output refers to a controller
+    fn tec_name(&self, label: &str) -> String {
+        match self.tecs.len() {
+            1 => "TEC".to_string(),
+            _ => format!("TEC {}", label),
+        }
+    }
+
+    /// Run `f` on every controller in turn, stopping at the first error
+    fn for_each_tec(
+        &self,
+        mut f: impl FnMut(&mut TecController<P>) -> Result<String, TecError>,
+    ) -> Result<(), TecError> {
+        for (_, tec) in &self.tecs {
+            f(&mut tec.lock().unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// Measure each setpoint transition in the log into `analysis.yaml`;
+    /// like the plot, a failure only warns
+    fn save_analysis(&self) {
+        for (index, (label, _)) in self.tecs.iter().enumerate() {
+            let analysis_path = match self.tecs.len() {
+                1 => format!("{}/analysis.yaml", self.experiment_dir),
+                _ => format!("{}/analysis_{}.yaml", self.experiment_dir, label),
+            };
+            let result = self.read_log_samples(index).and_then(|samples| {
+                    let analysis = crate::analysis::analyze(
+                        &samples,
+                        &self.phase_timings,
+                        self.params.temperature_tolerance,
+                    );
+                    Ok(save_yaml(&analysis_path, &analysis)?)
+                });
+            match result {
+                Ok(()) => println!("Transition analysis saved to: {}", analysis_path),
+                Err(e) => eprintln!("Warning: failed to analyze temperature log: {}", e),
+            }
+        }
+    }
+
+    /// Plot the first controller's log; a failure only warns, the data is
+    /// already saved
+    #[cfg(feature = "plot")]
+    fn save_temperature_plot(&self) {
+        let plot_path = format!("{}/temperature_plot.png", self.experiment_dir);
+        let result = self.read_log_samples(0).and_then(
+            |samples| crate::plot::render_temperature_plot(&plot_path, &samples, &self.phase_timings),
+        );
+        match result {
+            Ok(()) => println!("Temperature plot saved to: {}", plot_path),
+            Err(e) => eprintln!("Warning: failed to plot temperature log: {}", e),
+        }
+    }
+
+    /// Create every temperature log, starting the clock for `elapsed_ms`
+    fn initialize_log_file(&mut self) -> std::io::Result<()> {
+        self.start_ms = self.clock.now_ms();
+        for index in 0..self.tecs.len() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(self.log_path(index))?;
+            if self.params.log_format == LogFormat::Csv {
+                file.write_all(self.params.log_timestamps.csv_header().as_bytes())?;
+            }
+        }
         Ok(())
     }
 
-    fn wait_for_temperature(&self, target_temp: f32) -> Result<(), String> {
+    /// Wait until every controller has settled at `target_temp`, judging
+    /// each on the readouts published to it, in `tecs` order
+    fn wait_for_temperature(
+        &self,
+        target_temp: f32,
+        readouts: &[Receiver<TecReadout>],
+    ) -> Result<(), ExperimentError> {
         let pb = ProgressBar::new_spinner();
         pb.set_style(
             ProgressStyle::default_spinner()
                 .template("{spinner:.green} [{elapsed_precise}] {msg}")
                 .unwrap(),
         );
-        pb.set_message(format!("Waiting for temperature to reach {:.1}°C...", target_temp));
+        let unit = self.params.display_unit;
+        pb.set_message(format!(
+            "Waiting for temperature to reach {}...",
+            unit.format(target_temp, 1)
+        ));
 
-        let start_time = SystemTime::now();
+        let start_time = Instant::now();
         let max_wait = Duration::from_secs_f32(self.params.max_wait_time);
+        let mode = &self.params.stabilization;
+        // Recent samples of each controller, judged separately
+        let mut samples: Vec<VecDeque<(f32, f32)>> = vec![VecDeque::new(); self.tecs.len()];
 
         loop {
             pb.tick();
-            
-            if start_time.elapsed().unwrap() > max_wait {
-                pb.finish_with_message(format!("❌ Timeout waiting for {:.1}°C", target_temp));
-                return Err(format!(
-                    "Timeout waiting for temperature to reach {:.1}°C",
-                    target_temp
-                ));
+
+            if let Err(e) = check_abort() {
+                pb.abandon_with_message(format!("✗ Aborted waiting for {}", unit.format(target_temp, 1)));
+                return Err(e);
             }
 
-            match self.tec.lock() {
-                Ok(mut controller) => match controller.get_single_readout() {
-                    Ok(readout) => {
-                        let temp_diff = (readout.t_measured - target_temp).abs();
-                        pb.set_message(format!(
-                            "Current: {:.1}°C | Target: {:.1}°C | Diff: {:.1}°C",
-                            readout.t_measured, target_temp, temp_diff
-                        ));
-
-                        if temp_diff <= self.params.temperature_tolerance {
-                            pb.finish_with_message(format!(
-                                "✓ Temperature reached: {:.1}°C (target: {:.1}°C)",
-                                readout.t_measured, target_temp
-                            ));
-                            return Ok(());
-                        }
-                    }
-                    Err(e) => eprintln!("Failed to read temperature: {}", e),
-                },
-                Err(e) => eprintln!("Failed to lock TEC controller: {}", e),
+            if start_time.elapsed() > max_wait {
+                pb.finish_with_message(format!("❌ Timeout waiting for {}", unit.format(target_temp, 1)));
+                return Err(ExperimentError::Stabilization {
+                    phase: self.current_phase(),
+                    target: target_temp,
+                });
+            }
+
+            let mut readings = Vec::with_capacity(self.tecs.len());
+            for (index, ((label, _), readouts)) in self.tecs.iter().zip(readouts).enumerate() {
+                // Only the newest counts, keeping the slope window a tick apart;
+                // the watching monitor reports failed reads itself
+                let Some(readout) = readouts.try_iter().last() else {
+                    continue;
+                };
+
+                let elapsed = start_time.elapsed().as_secs_f32();
+                // Judged on the device's own reading, which it regulates to
+                // the setpoint, so a calibration offset can't keep it from settling
+                samples[index].push_back((elapsed, readout.t_measured_raw()));
+                if samples[index].len() > mode.history_len() {
+                    samples[index].pop_front();
+                }
+                readings.push((label, readout.t_measured_raw()));
+            }
+
+            if let Some(&(_, worst)) = readings
+                .iter()
+                .max_by(|a, b| (a.1 - target_temp).abs().total_cmp(&(b.1 - target_temp).abs()))
+            {
+                let current = match self.tecs.len() {
+                    1 => unit.format(worst, 1),
+                    _ => readings
+                        .iter()
+                        .map(|(label, temp)| format!("{} {}", label, unit.format(*temp, 1)))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                pb.set_message(format!(
+                    "Current: {} | Target: {} | Diff: {:.1}{}",
+                    current,
+                    unit.format(target_temp, 1),
+                    unit.convert_delta((worst - target_temp).abs()),
+                    unit.symbol()
+                ));
+
+                let all_stable = readings.len() == self.tecs.len()
+                    && samples
+                        .iter()
+                        .all(|history| mode.is_stable(history, target_temp, self.params.temperature_tolerance));
+                if all_stable {
+                    pb.finish_with_message(format!(
+                        "✓ Temperature reached: {} (target: {})",
+                        current,
+                        unit.format(target_temp, 1)
+                    ));
+                    return Ok(());
+                }
             }
 
             thread::sleep(Duration::from_millis(1000));
         }
     }
 
-    fn start_temperature_logging(&self) -> thread::JoinHandle<()> {
-        let tec_clone = Arc::clone(&self.tec);
-        let log_path = format!("{}/temperature_log.csv", self.experiment_dir);
+    /// Step the setpoint towards `target` at `rate_c_per_s`.
+    ///
+    /// Unlike `TecController::ramp_to` the lock is only held for each step, so
+    /// the logging thread keeps sampling during the ramp.
+    fn ramp_setpoint(&self, target: f32, rate_c_per_s: f32) -> Result<(), ExperimentError> {
+        let start = self.primary().lock().unwrap().current_config.t_set;
+        let setpoints = ramp_setpoints(start, target, rate_c_per_s, RAMP_STEP_INTERVAL);
+        let unit = self.params.display_unit;
+        println!(
+            "Ramping setpoint {} -> {} at {:.2}{}/s",
+            unit.format(start, 1),
+            unit.format(target, 1),
+            unit.convert_delta(rate_c_per_s),
+            unit.symbol()
+        );
+
+        let last = setpoints.len() - 1;
+        for (n, t_set) in setpoints.into_iter().enumerate() {
+            check_abort()?;
+            self.for_each_tec(|controller| controller.set_t(t_set))
+                .map_err(|source| self.setpoint_error(source))?;
+            if n < last {
+                thread::sleep(RAMP_STEP_INTERVAL);
+            }
+        }
+        Ok(())
+    }
 
-        thread::spawn(move || {
-            println!("Starting temperature logging...");
-            let log_interval = Duration::from_millis(100); // Log every 100ms
-
-            loop {
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64;
-
-                match tec_clone.lock() {
-                    Ok(mut controller) => match controller.get_single_readout() {
-                        Ok(readout) => {
-                            let log_entry = format!(
-                                "{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{:.1}\n",
-                                timestamp,
-                                readout.t_set,
-                                readout.p,
-                                readout.i,
-                                readout.d,
-                                readout.t_min,
-                                readout.t_max,
-                                readout.t_measured,
-                                if readout.oc { 1 } else { 0 },
-                                readout.pwm
-                            );
-
-                            match OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(&log_path)
-                            {
-                                Ok(mut file) => {
-                                    if let Err(e) = file.write_all(log_entry.as_bytes()) {
-                                        eprintln!("Failed to write to log file: {}", e);
-                                    }
-                                }
-                                Err(e) => eprintln!("Failed to open log file: {}", e),
-                            }
-                        }
-                        Err(e) => eprintln!("Failed to read TEC data: {}", e),
-                    },
-                    Err(e) => eprintln!("Failed to lock TEC controller: {}", e),
+    /// Append every readout of each controller to its log until the returned
+    /// logger is stopped. A `TecMonitor` per controller takes the readouts
+    /// every `LOG_INTERVAL`; the logging thread only writes what they publish.
+    fn start_temperature_logging(&self) -> std::io::Result<TemperatureLogger> {
+        self.start_watching(true)
+    }
+
+    /// Poll every controller like `start_temperature_logging`, appending to
+    /// the logs only if `write_logs`; without them the readouts still reach
+    /// `latest_readout` and the logger's subscribers
+    fn start_watching(&self, write_logs: bool) -> std::io::Result<TemperatureLogger> {
+        let mut logs = Vec::with_capacity(self.tecs.len());
+        let mut subscriptions = Vec::with_capacity(self.tecs.len());
+        for (index, (_, tec)) in self.tecs.iter().enumerate() {
+            let file = write_logs
+                .then(|| OpenOptions::new().create(true).append(true).open(self.log_path(index)))
+                .transpose()?
+                .map(BufWriter::new);
+            let (monitor, readouts) = TecMonitor::watch(Arc::clone(tec), LOG_INTERVAL);
+            subscriptions.push(monitor.subscriptions());
+            logs.push(ControllerLog { monitor, readouts, file });
+        }
+        let latest_readout = Arc::clone(&self.latest_readout);
+        let current_phase = Arc::clone(&self.current_phase);
+        let settings = self.log_settings();
+        let clock = Arc::clone(&self.clock);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_requested = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            if write_logs {
+                println!("Starting temperature logging...");
+            }
+            let write_pending = |logs: &mut Vec<ControllerLog>| {
+                let phase = current_phase.lock().unwrap().clone();
+                for (index, log) in logs.iter_mut().enumerate() {
+                    if let Some(readout) = log.write_pending(settings, clock.as_ref(), &phase)
+                        && index == 0
+                    {
+                        *latest_readout.lock().unwrap() = Some(readout);
+                    }
                 }
+            };
+
+            let mut logs = logs;
+            while !stop_requested.load(Ordering::SeqCst) {
+                write_pending(&mut logs);
+                thread::sleep(Duration::from_millis(10));
+            }
 
-                thread::sleep(log_interval);
+            // Stop polling, then write whatever was published before that
+            for log in &mut logs {
+                log.monitor.shutdown();
+            }
+            write_pending(&mut logs);
+            for file in logs.iter_mut().filter_map(|log| log.file.as_mut()) {
+                if let Err(e) = file.flush() {
+                    eprintln!("Failed to flush log file: {}", e);
+                }
             }
+        });
+
+        Ok(TemperatureLogger {
+            stop,
+            handle: Some(handle),
+            subscriptions,
         })
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    fn set_current_phase(&self, name: &str) {
+        *self.current_phase.lock().unwrap() = name.to_string();
+    }
+
+    fn current_phase(&self) -> String {
+        self.current_phase.lock().unwrap().clone()
+    }
+
+    /// `ExperimentError::Setpoint` in the current phase
+    fn setpoint_error(&self, source: TecError) -> ExperimentError {
+        ExperimentError::Setpoint { phase: self.current_phase(), source }
+    }
+
+    /// Drive the fan from the logged TEC PWM until `stop` is set
+    fn start_fan_control(
+        &self,
+        fan: Arc<Fan>,
+        curve: FanCurve,
+        stop: Arc<AtomicBool>,
+    ) -> thread::JoinHandle<()> {
+        let latest_readout = Arc::clone(&self.latest_readout);
+
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                let pwm = latest_readout.lock().unwrap().as_ref().map(|r| r.pwm);
+                if let Some(pwm) = pwm
+                    && let Err(e) = fan.apply_curve(pwm, &curve)
+                {
+                    eprintln!("Failed to set fan speed: {}", e);
+                }
+                thread::sleep(FAN_CONTROL_INTERVAL);
+            }
+        })
+    }
+
+    /// Run the classic rest → snap → hold → rest protocol described by `Parameters`
+    pub fn run(&mut self) -> Result<(), ExperimentError> {
         println!("Starting experiment...");
         println!("Parameters:");
-        println!("  Rest temperature: {:.1}°C", self.params.rest_temp);
-        println!("  Snap temperature: {:.1}°C", self.params.snap_temp);
+        let unit = self.params.display_unit;
+        println!("  Rest temperature: {}", unit.format(self.params.rest_temp, 1));
+        println!("  Snap temperature: {}", unit.format(self.params.snap_temp, 1));
         println!("  Pre-record time: {:.1}s", self.params.prerecord_time);
         println!("  Snap hold time: {:.1}s", self.params.snap_hold_time);
+        println!("  Cycles: {}", self.params.cycles);
         println!("  Post-record time: {:.1}s", self.params.postrecord_time);
+        println!("  Log format: {:?}", self.params.log_format);
+        println!("  Log timestamps: {:?}", self.params.log_timestamps);
+
+        let profile = TemperatureProfile::classic(&self.params);
+        self.run_profile(profile)
+    }
+
+    /// Stabilize at the first step's temperature, then record while stepping
+    /// through `profile`, recording a phase timing for each step
+    pub fn run_profile(
+        &mut self,
+        profile: TemperatureProfile,
+    ) -> Result<(), ExperimentError> {
+        let initial_temp = match profile.steps.first() {
+            Some(step) => step.target_temp,
+            None => {
+                return Err(ExperimentError::InvalidParameters(vec![
+                    "temperature profile has no steps".to_string(),
+                ]));
+            }
+        };
+        println!("Running {}-step temperature profile", profile.steps.len());
+
+        // Validate before creating any files or touching the hardware
+        let mut problems: Vec<String> = Vec::new();
+        for (label, tec) in &self.tecs {
+            let limits = tec.lock().unwrap().current_config();
+            let tec_name = self.tec_name(label);
+            let mut found = self.params.validate(&limits).err().unwrap_or_default();
+            for (index, step) in profile.steps.iter().enumerate() {
+                if !(limits.t_min..=limits.t_max).contains(&step.target_temp) {
+                    found.push(format!(
+                        "{} target {:.1}°C is outside the {} limits {:.1}..{:.1}°C",
+                        step.label(index + 1),
+                        step.target_temp,
+                        tec_name,
+                        limits.t_min,
+                        limits.t_max
+                    ));
+                }
+            }
+            for problem in found {
+                if !problems.contains(&problem) {
+                    problems.push(problem);
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(ExperimentError::InvalidParameters(problems));
+        }
 
         // Create experiment directory
-        self.experiment_dir = create_experiment_directory()?;
+        self.experiment_dir = create_experiment_directory(&self.params, self.clock.as_ref()).map_err(ExperimentError::Files)?;
+        let outcome = self.run_profile_in_directory(profile, initial_temp);
+        record_final_status(&self.experiment_dir, &outcome, self.clock.as_ref());
+        outcome
+    }
 
-        // Save parameters to YAML
-        save_parameters(&self.experiment_dir, &self.params)?;
+    /// Everything `run_profile` does once the experiment directory exists
+    fn run_profile_in_directory(
+        &mut self,
+        profile: TemperatureProfile,
+        initial_temp: f32,
+    ) -> Result<(), ExperimentError> {
+        // Save parameters and profile to YAML
+        save_parameters(&self.experiment_dir, &self.params).map_err(ExperimentError::Files)?;
+        save_yaml(&format!("{}/profile.yaml", self.experiment_dir), &profile).map_err(ExperimentError::Files)?;
 
         // Initialize log file
-        self.initialize_log_file()?;
+        self.initialize_log_file().map_err(ExperimentError::Files)?;
+
+        // Check for the camera before touching the TEC
+        let mut camera = self.camera();
+        let binary = camera.probe().map_err(ExperimentError::Camera)?;
+        println!("Using camera binary: {}", binary);
 
-        // Configure and enable TEC
+        // Configure and enable every TEC, leaving them all off if one fails.
+        // The guards switch them off again should a phase panic.
+        let enabled = match self.configure_and_enable(initial_temp) {
+            Ok(enabled) => enabled,
+            Err(e) => {
+                self.disable_all();
+                return Err(e);
+            }
+        };
+        let fan_stop = Arc::new(AtomicBool::new(false));
+        let fan_control = match (&self.fan, self.params.fan_curve.clone()) {
+            (Some(fan), Some(curve)) => {
+                Some(self.start_fan_control(Arc::clone(fan), curve, Arc::clone(&fan_stop)))
+            }
+            // Without a curve the fan simply runs flat out for the whole run
+            (Some(fan), None) => {
+                if let Err(e) = fan.on_full() {
+                    eprintln!("Warning: failed to run fan at full speed: {}", e);
+                }
+                None
+            }
+            (None, Some(_)) => {
+                eprintln!("Warning: no fan attached; ignoring fan_curve");
+                None
+            }
+            (None, None) => None,
+        };
+
+        let outcome = self.run_phases(&profile, &mut camera);
+
+        if let Some(handle) = fan_control {
+            fan_stop.store(true, Ordering::SeqCst);
+            let _ = handle.join();
+        }
+        if let Some(fan) = &self.fan
+            && let Err(e) = fan.off()
         {
-            let mut controller = self.tec.lock().unwrap();
+            eprintln!("Failed to turn fan off: {}", e);
+        }
+
+        // Always leave the TEC off, even after an error or abort
+        self.disable_all();
+        drop(enabled);
+
+        // Save phase timings; failing to must not hide the run's own outcome
+        let timings = self.save_phase_timings();
+        if let Err(e) = &timings {
+            eprintln!("Warning: failed to write phase_timings.yaml: {}", e);
+        }
+        self.save_analysis();
+
+        #[cfg(feature = "plot")]
+        if self.params.save_plot {
+            self.save_temperature_plot();
+        }
+
+        outcome?;
+        // The run itself succeeded, so a missing manifest must not fail it
+        let manifest = self.save_run_manifest(&profile, camera.video_path());
+        if let Err(e) = &manifest {
+            eprintln!("Warning: failed to write {}: {}", MANIFEST_FILE, e);
+        }
+
+        println!("\n✓ Experiment completed!");
+        println!("Results saved to: {}", self.experiment_dir);
+        println!("  - parameters.yaml");
+        println!("  - profile.yaml");
+        println!("  - camera.yaml");
+        if timings.is_ok() {
+            println!("  - phase_timings.yaml");
+        }
+        if manifest.is_ok() {
+            println!("  - {}", MANIFEST_FILE);
+        }
+        match self.tecs.len() {
+            1 => println!("  - analysis.yaml"),
+            _ => self.tecs.iter().for_each(|(label, _)| println!("  - analysis_{}.yaml", label)),
+        }
+        println!("  - {}", camera.video_path().rsplit('/').next().unwrap_or_default());
+        println!("  - timestamps.txt");
+        for index in 0..self.tecs.len() {
+            println!("  - {}", self.log_path(index).rsplit('/').next().unwrap_or_default());
+        }
+        if cfg!(feature = "plot") && self.params.save_plot {
+            println!("  - temperature_plot.png");
+        }
+
+        Ok(())
+    }
+
+    /// Record video and the temperature log for `duration`, or until Enter is
+    /// pressed when there is none, leaving the TEC as it is. Ctrl-C stops the
+    /// recording early; the camera and logger are always stopped and joined.
+    pub fn record(&mut self, duration: Option<Duration>) -> Result<(), ExperimentError> {
+        self.experiment_dir = create_experiment_directory(&self.params, self.clock.as_ref()).map_err(ExperimentError::Files)?;
+        let outcome = self.record_in_directory(duration);
+        record_final_status(&self.experiment_dir, &outcome, self.clock.as_ref());
+        outcome
+    }
+
+    fn record_in_directory(&mut self, duration: Option<Duration>) -> Result<(), ExperimentError> {
+        save_parameters(&self.experiment_dir, &self.params).map_err(ExperimentError::Files)?;
+        self.initialize_log_file().map_err(ExperimentError::Files)?;
+
+        let mut camera = self.camera();
+        let binary = camera.probe().map_err(ExperimentError::Camera)?;
+        println!("Using camera binary: {}", binary);
+
+        self.set_current_phase("Recording");
+        let mut logger = self.start_temperature_logging().map_err(ExperimentError::Files)?;
+        let start = self.phase_mark();
+        camera.start().map_err(ExperimentError::Camera)?;
+
+        let outcome = wait_for_recording_end(duration);
+
+        let stopped = camera.stop();
+        logger.stop();
+        let end = self.phase_mark();
+        self.record_phase_timing("Recording".to_string(), start, end);
+        if let Err(e) = self.save_phase_timings() {
+            eprintln!("Warning: failed to write phase_timings.yaml: {}", e);
+        }
+
+        #[cfg(feature = "plot")]
+        if self.params.save_plot {
+            self.save_temperature_plot();
+        }
+
+        outcome?;
+        stopped.map_err(ExperimentError::Camera)?;
+        println!("\n✓ Recording saved to: {}", self.experiment_dir);
+        Ok(())
+    }
+
+    fn configure_and_enable(&self, initial_temp: f32) -> Result<Vec<TecEnabledGuard<P>>, ExperimentError> {
+        let mut enabled = Vec::with_capacity(self.tecs.len());
+        for (label, tec) in &self.tecs {
+            let mut controller = tec.lock().unwrap();
+            let name = self.tec_name(label);
 
             // Configure TEC with appropriate PID values
             let config = TecConfig {
-                t_set: self.params.rest_temp,
+                t_set: initial_temp,
                 ..controller.current_config
             };
 
-            println!("Configuring TEC...");
+            println!("Configuring {}...", name);
             match controller.set_configuration(&config) {
-                Ok(response) => println!("TEC configured: {}", response),
-                Err(e) => {
-                    eprintln!("Failed to configure TEC: {}", e);
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("TEC configuration failed: {}", e),
-                    )));
-                }
+                Ok(response) => println!("{} configured: {}", name, response),
+                Err(source) => return Err(ExperimentError::Configure { tec: name, source }),
             }
 
-            println!("Enabling TEC...");
-            match controller.enable() {
-                Ok(response) => println!("TEC enabled: {}", response),
-                Err(e) => {
-                    eprintln!("Failed to enable TEC: {}", e);
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("TEC enable failed: {}", e),
-                    )));
+            println!("Enabling {}...", name);
+            drop(controller);
+            match TecController::enable_scoped(tec) {
+                Ok(guard) => {
+                    println!("{} enabled", name);
+                    enabled.push(guard);
                 }
+                Err(source) => return Err(ExperimentError::Enable { tec: name, source }),
             }
         }
-        // self.fan.on_full();
+        Ok(enabled)
+    }
 
-        // Phase 0: Initial temperature stabilization
-        let phase_start = get_timestamp_ms();
-        {
-            let mut controller = self.tec.lock().unwrap();
-            controller.set_t(self.params.rest_temp);
+    /// Switch every TEC off, carrying on past failures
+    fn disable_all(&self) {
+        for (label, tec) in &self.tecs {
+            let name = self.tec_name(label);
+            let mut controller = tec.lock().unwrap();
+            println!("Disabling {}...", name);
+            match controller.disable() {
+                Ok(response) => println!("{} disabled: {}", name, response),
+                Err(e) => eprintln!("Failed to disable {}: {}", name, e),
+            }
         }
-        self.wait_for_temperature(self.params.rest_temp)?;
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Initial stabilization".to_string(), phase_start, phase_end);
+    }
 
-        // Initialize camera
-        let mut camera = Camera::new(&self.experiment_dir);
-        camera.start()?;
+    fn camera(&self) -> Camera {
+        let mut config = self.params.camera.clone();
+        config.annotation = config
+            .annotation
+            .map(|template| annotation_text(&template, &self.params, &self.experiment_dir));
+        if self.simulated {
+            Camera::simulated(&self.experiment_dir, config)
+        } else {
+            Camera::with_config(&self.experiment_dir, config)
+        }
+    }
+
+    /// Stabilize, then record every profile step. The camera is stopped before
+    /// returning, whether or not the steps completed.
+    fn run_phases(
+        &mut self,
+        profile: &TemperatureProfile,
+        camera: &mut Camera,
+    ) -> Result<(), ExperimentError> {
+        let initial_temp = profile.steps[0].target_temp;
 
-        // Start temperature logging thread
-        let _logging_thread = self.start_temperature_logging();
+        // Phase 0: Initial temperature stabilization
+        self.set_current_phase("Initial stabilization");
+        let phase_start = self.phase_mark();
+        // Watch without logging: the log starts with the recording
+        let watcher = self.start_watching(false).map_err(ExperimentError::Files)?;
+        self.wait_for_temperature(initial_temp, &watcher.subscribe())?;
+        drop(watcher);
+        let phase_end = self.phase_mark();
+        self.record_phase_timing("Initial stabilization".to_string(), phase_start, phase_end);
 
-        // Phase 1: Pre-record at rest temperature
-        let phase_start = get_timestamp_ms();
-        let pb = create_phase_progress_bar(self.params.prerecord_time, "Phase 1: Pre-recording at rest temperature");
-        sleep_with_progress(&pb, self.params.prerecord_time);
-        pb.finish_with_message("✓ Phase 1 complete");
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Pre-record at rest temp".to_string(), phase_start, phase_end);
+        // Start temperature logging thread; dropping it on an early return
+        // stops it too
+        let mut logger = self.start_temperature_logging().map_err(ExperimentError::Files)?;
 
-        // Phase 2: Change to snap temperature
-        let phase_start = get_timestamp_ms();
-        println!("Phase 2: Changing to snap temperature {:.1}°C", self.params.snap_temp);
-        {
-            let mut controller = self.tec.lock().unwrap();
-            controller.set_t(self.params.snap_temp);
-        }
-        self.wait_for_temperature(self.params.snap_temp)?;
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Heat to snap temp".to_string(), phase_start, phase_end);
-
-        // Phase 3: Hold at snap temperature
-        let phase_start = get_timestamp_ms();
-        let pb = create_phase_progress_bar(self.params.snap_hold_time, "Phase 3: Holding at snap temperature");
-        sleep_with_progress(&pb, self.params.snap_hold_time);
-        pb.finish_with_message("✓ Phase 3 complete");
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Hold at snap temp".to_string(), phase_start, phase_end);
-
-        // Phase 4: Return to rest temperature
-        let phase_start = get_timestamp_ms();
-        println!("Phase 4: Returning to rest temperature {:.1}°C", self.params.rest_temp);
-        {
-            let mut controller = self.tec.lock().unwrap();
-            controller.set_t(self.params.rest_temp);
-        }
-        // Note: We don't wait for temperature to stabilize here as we want to capture the cooling
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Initiate cooling".to_string(), phase_start, phase_end);
+        // Start recording
+        camera.start().map_err(ExperimentError::Camera)?;
 
-        // Phase 5: Post-record
-        let phase_start = get_timestamp_ms();
-        let pb = create_phase_progress_bar(self.params.postrecord_time, "Phase 5: Post-recording");
-        sleep_with_progress(&pb, self.params.postrecord_time);
-        pb.finish_with_message("✓ Phase 5 complete");
-        let phase_end = get_timestamp_ms();
-        self.record_phase_timing("Post-record".to_string(), phase_start, phase_end);
+        let outcome = profile
+            .steps
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, step)| self.run_step(index + 1, step, &logger));
 
-        // Stop camera
-        camera.stop()?;
+        // Stop camera, then logging, so the log covers the whole recording
+        // and is flushed before the TEC is disabled
+        let stopped = camera.stop();
+        logger.stop();
+        outcome?;
+        stopped.map_err(ExperimentError::Camera)
+    }
 
-        // self.fan.off();
+    /// Move to the step's setpoint, optionally wait for it, then hold;
+    /// stabilization is judged on `logger`'s readouts
+    fn run_step(
+        &mut self,
+        number: usize,
+        step: &ProfileStep,
+        logger: &TemperatureLogger,
+    ) -> Result<(), ExperimentError> {
+        check_abort()?;
+        let name = step.label(number);
+        self.set_current_phase(&name);
+        let phase_start = self.phase_mark();
 
-        // Disable TEC
-        {
-            let mut controller = self.tec.lock().unwrap();
-            println!("Disabling TEC...");
-            match controller.disable() {
-                Ok(response) => println!("TEC disabled: {}", response),
-                Err(e) => eprintln!("Failed to disable TEC: {}", e),
+        let current_setpoint = self.primary().lock().unwrap().current_config.t_set;
+        if current_setpoint != step.target_temp {
+            println!(
+                "Phase {}: {} - setpoint {}",
+                number,
+                name,
+                self.params.display_unit.format(step.target_temp, 1)
+            );
+            match self.params.ramp_rate {
+                Some(rate) if step.wait_for_stabilization => {
+                    self.ramp_setpoint(step.target_temp, rate)?
+                }
+                _ => self
+                    .for_each_tec(|controller| controller.set_t(step.target_temp))
+                    .map_err(|source| self.setpoint_error(source))?,
             }
         }
 
-        // Save phase timings
-        self.save_phase_timings()?;
+        if step.wait_for_stabilization {
+            self.wait_for_temperature(step.target_temp, &logger.subscribe())?;
+        }
 
-        println!("\n✓ Experiment completed!");
-        println!("Results saved to: {}", self.experiment_dir);
-        println!("  - parameters.yaml");
-        println!("  - phase_timings.yaml");
-        println!("  - video.h264");
-        println!("  - timestamps.txt");
-        println!("  - temperature_log.csv");
+        if step.hold_time_s > 0.0 {
+            let pb = create_phase_progress_bar(
+                step.hold_time_s,
+                &format!("Phase {}: {}", number, name),
+            );
+            if let Err(e) = sleep_with_progress(&pb, step.hold_time_s, &ABORT_REQUESTED) {
+                pb.abandon_with_message(format!("✗ Phase {} aborted", number));
+                return Err(e);
+            }
+            pb.finish_with_message(format!("✓ Phase {} complete", number));
+        }
 
+        let phase_end = self.phase_mark();
+        self.record_phase_timing(name, phase_start, phase_end);
         Ok(())
     }
 }
 
 // Utility functions
 
-/// Create a timestamped experiment directory
-fn create_experiment_directory() -> std::io::Result<String> {
-    // Create experiments base directory if it doesn't exist
-    fs::create_dir_all("experiments")?;
-    
+/// Least-squares slope of (x, y) points, or None if x has no spread
+fn slope<'a>(points: impl Iterator<Item = &'a (f32, f32)> + Clone) -> Option<f32> {
+    let n = points.clone().count() as f32;
+    if n < 2.0 {
+        return None;
+    }
+    let mean_x = points.clone().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.clone().map(|p| p.1).sum::<f32>() / n;
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for &(x, y) in points {
+        sxy += (x - mean_x) * (y - mean_y);
+        sxx += (x - mean_x) * (x - mean_x);
+    }
+    if sxx == 0.0 { None } else { Some(sxy / sxx) }
+}
+
+/// Create an experiment directory under `params.output_base()` named after
+/// the time on `clock`, along with any missing parents, and add it to the
+/// index there
+fn create_experiment_directory(params: &Parameters, clock: &dyn Clock) -> std::io::Result<String> {
+    let base = params.output_base();
+    let unusable = |e: std::io::Error| {
+        std::io::Error::new(
+            e.kind(),
+            format!("cannot create experiment directory under {}: {}", base.display(), e),
+        )
+    };
+    fs::create_dir_all(&base).map_err(unusable)?;
+
     // Create timestamped subdirectory for this experiment
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
-    let experiment_dir = format!("experiments/experiment_{}", timestamp);
-    fs::create_dir_all(&experiment_dir)?;
+    let timestamp = clock.now_ms();
+    let experiment_dir = base
+        .join(format!("experiment_{}", timestamp))
+        .to_string_lossy()
+        .to_string();
+    fs::create_dir_all(&experiment_dir).map_err(unusable)?;
     
     println!("Created experiment directory: {}", experiment_dir);
+    let entry = IndexEntry {
+        directory: experiment_dir.clone(),
+        timestamp_ms: timestamp,
+        status: RunStatus::Started,
+        rest_temp: Some(params.rest_temp),
+        snap_temp: Some(params.snap_temp),
+        cycles: Some(params.cycles),
+    };
+    let index_path = base.join(INDEX_FILE);
+    if let Err(e) = append_index_entry(&index_path, &entry) {
+        eprintln!("Warning: failed to update {}: {}", index_path.display(), e);
+    }
     Ok(experiment_dir)
 }
 
+/// Append how the run in `experiment_dir` ended, and when on `clock`, to
+/// the index next to it
+fn record_final_status(experiment_dir: &str, outcome: &Result<(), ExperimentError>, clock: &dyn Clock) {
+    let status = match outcome {
+        Ok(()) => RunStatus::Completed,
+        Err(ExperimentError::Aborted) => RunStatus::Aborted,
+        Err(_) => RunStatus::Failed,
+    };
+    let entry = IndexEntry {
+        directory: experiment_dir.to_string(),
+        timestamp_ms: clock.now_ms(),
+        status,
+        rest_temp: None,
+        snap_temp: None,
+        cycles: None,
+    };
+    let index_path = Path::new(experiment_dir).with_file_name(INDEX_FILE);
+    if let Err(e) = append_index_entry(&index_path, &entry) {
+        eprintln!("Warning: failed to update {}: {}", index_path.display(), e);
+    }
+}
+
+/// Add one JSON line to the index at `path`.
+///
+/// The line goes out in a single write to a file opened for appending, so
+/// entries from concurrent runs never interleave.
+fn append_index_entry(path: &Path, entry: &IndexEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
 /// Save experiment parameters to YAML file
 fn save_parameters(experiment_dir: &str, params: &Parameters) -> std::io::Result<()> {
     let params_path = format!("{}/parameters.yaml", experiment_dir);
-    
-    let yaml_string = serde_yaml::to_string(params)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    
+    save_yaml(&params_path, params)?;
+    println!("Parameters saved to: {}", params_path);
+    Ok(())
+}
+
+/// Serialize `value` to a YAML file, replacing any existing file
+fn save_yaml<T: Serialize>(path: &str, value: &T) -> std::io::Result<()> {
+    let yaml_string = serde_yaml::to_string(value)
+        .map_err(std::io::Error::other)?;
+
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)
-        .open(&params_path)?;
-    file.write_all(yaml_string.as_bytes())?;
-    println!("Parameters saved to: {}", params_path);
-    Ok(())
+        .open(path)?;
+    file.write_all(yaml_string.as_bytes())
+}
+
+/// Readouts of one controller on their way into its temperature log
+struct ControllerLog {
+    monitor: TecMonitor,
+    readouts: Receiver<TecReadout>,
+    /// None while only watching, e.g. during the initial stabilization
+    file: Option<BufWriter<fs::File>>,
 }
 
-/// Get current timestamp in milliseconds
-fn get_timestamp_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64
+impl ControllerLog {
+    /// Append every readout published since the last call, reporting read
+    /// errors on the way; returns the newest readout
+    fn write_pending(&mut self, settings: LogSettings, clock: &dyn Clock, phase: &str) -> Option<TecReadout> {
+        while let Some(event) = self.monitor.try_recv_event() {
+            if let MonitorEvent::Error(e) = event {
+                eprintln!("{}", e);
+            }
+        }
+        let mut newest = None;
+        while let Ok(readout) = self.readouts.try_recv() {
+            if let Some(file) = &mut self.file
+                && let Err(e) = append_log_entry(file, settings, clock.now_ms(), &readout, phase)
+            {
+                eprintln!("Failed to write to log file: {}", e);
+            }
+            newest = Some(readout);
+        }
+        newest
+    }
+}
+
+/// Append one readout as a row of the temperature log
+fn append_log_entry(
+    log: &mut impl Write,
+    settings: LogSettings,
+    timestamp: u64,
+    readout: &TecReadout,
+    phase: &str,
+) -> std::io::Result<()> {
+    let elapsed = timestamp.saturating_sub(settings.start_ms);
+    let log_entry = match settings.format {
+        LogFormat::Csv => match settings.timestamps {
+            LogTimestamps::Absolute => csv_log_entry(timestamp, None, readout, phase),
+            LogTimestamps::Relative => csv_log_entry(elapsed, None, readout, phase),
+            LogTimestamps::Both => csv_log_entry(timestamp, Some(elapsed), readout, phase),
+        },
+        LogFormat::JsonLines => {
+            let (timestamp_ms, elapsed_ms) = match settings.timestamps {
+                LogTimestamps::Absolute => (Some(timestamp), None),
+                LogTimestamps::Relative => (None, Some(elapsed)),
+                LogTimestamps::Both => (Some(timestamp), Some(elapsed)),
+            };
+            let record = LogRecord {
+                timestamp_ms,
+                elapsed_ms,
+                readout,
+                t_measured_raw: readout.t_measured_raw(),
+                phase,
+            };
+            let mut line = serde_json::to_string(&record).map_err(std::io::Error::other)?;
+            line.push('\n');
+            line
+        }
+    };
+
+    log.write_all(log_entry.as_bytes())
+}
+
+/// CSV row starting with `time`; `elapsed_ms` is the extra column of
+/// `BOTH_LOG_HEADER`
+fn csv_log_entry(time: u64, elapsed_ms: Option<u64>, readout: &TecReadout, phase: &str) -> String {
+    let elapsed_column = elapsed_ms.map_or(String::new(), |elapsed| format!("{},", elapsed));
+    format!(
+        "{},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{:.1},{},{}{},{:.2}\n",
+        time,
+        readout.t_set,
+        readout.p,
+        readout.i,
+        readout.d,
+        readout.t_min,
+        readout.t_max,
+        readout.t_measured,
+        if readout.oc { 1 } else { 0 },
+        readout.pwm,
+        readout.resync_count,
+        elapsed_column,
+        csv_field(phase),
+        readout.t_measured_raw()
+    )
+}
+
+/// Fill the run's details into a camera annotation: `{experiment}` (the
+/// directory name), `{rest_temp}`, `{snap_temp}` and `{cycles}`. Anything
+/// else, such as rpicam's `%frame`, is left for the camera.
+fn annotation_text(template: &str, params: &Parameters, experiment_dir: &str) -> String {
+    let experiment = Path::new(experiment_dir)
+        .file_name()
+        .map_or_else(|| experiment_dir.to_string(), |name| name.to_string_lossy().to_string());
+    template
+        .replace("{experiment}", &experiment)
+        .replace("{rest_temp}", &format!("{:.1}", params.rest_temp))
+        .replace("{snap_temp}", &format!("{:.1}", params.snap_temp))
+        .replace("{cycles}", &params.cycles.to_string())
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
 }
 
 /// Create a progress bar for a timed phase
@@ -406,11 +1750,600 @@ fn create_phase_progress_bar(duration_s: f32, message: &str) -> ProgressBar {
     pb
 }
 
-/// Sleep with progress bar updates
-fn sleep_with_progress(pb: &ProgressBar, duration_s: f32) {
+/// Block for `duration` with a progress bar, or without one until a line is
+/// read from stdin; an abort ends either early
+fn wait_for_recording_end(duration: Option<Duration>) -> Result<(), ExperimentError> {
+    if let Some(duration) = duration {
+        let pb = create_phase_progress_bar(duration.as_secs_f32(), "Recording");
+        let result = sleep_with_progress(&pb, duration.as_secs_f32(), &ABORT_REQUESTED);
+        match result {
+            Ok(()) => pb.finish_with_message("✓ Recording complete"),
+            Err(_) => pb.abandon_with_message("✗ Recording stopped"),
+        }
+        return result;
+    }
+
+    println!("Recording; press Enter to stop");
+    // The reader stays blocked until Enter or EOF, which is what ends the wait
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut line = String::new();
+        let _ = std::io::stdin().read_line(&mut line);
+        let _ = tx.send(());
+    });
+    loop {
+        check_abort()?;
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+/// Sleep with progress bar updates, returning early once `abort` is set
+fn sleep_with_progress(pb: &ProgressBar, duration_s: f32, abort: &AtomicBool) -> Result<(), ExperimentError> {
     let steps = (duration_s * 10.0) as u64;
     for _ in 0..steps {
+        check_abort_flag(abort)?;
         thread::sleep(Duration::from_millis(100));
         pb.inc(1);
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use crate::tec::mock::{DEFAULT_CONFIG_ACK, MockLink};
+    use crate::time::MockClock;
+    use std::path::Path;
+
+    /// Logger that polls nothing, so steps never see a readout
+    fn idle_logger() -> TemperatureLogger {
+        TemperatureLogger {
+            stop: Arc::new(AtomicBool::new(false)),
+            handle: None,
+            subscriptions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_classic_profile_phases() {
+        let params = Parameters::default();
+        let profile = TemperatureProfile::classic(&params);
+        let labels: Vec<String> = profile
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, step)| step.label(i + 1))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "Pre-record at rest temp",
+                "Heat to snap temp",
+                "Hold at snap temp",
+                "Initiate cooling",
+                "Post-record"
+            ]
+        );
+        assert!(profile.steps[1].wait_for_stabilization);
+        assert_eq!(profile.steps[2].hold_time_s, params.snap_hold_time);
+        assert!(!profile.steps[3].wait_for_stabilization);
+    }
+
+    #[test]
+    fn test_classic_profile_repeats_cycles() {
+        let params = Parameters {
+            cycles: 2,
+            ..Parameters::default()
+        };
+        let profile = TemperatureProfile::classic(&params);
+        let names: Vec<&str> = profile
+            .steps
+            .iter()
+            .map(|step| step.name.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Pre-record at rest temp",
+                "Heat to snap temp (cycle 1)",
+                "Hold at snap temp (cycle 1)",
+                "Initiate cooling (cycle 1)",
+                "Heat to snap temp (cycle 2)",
+                "Hold at snap temp (cycle 2)",
+                "Initiate cooling (cycle 2)",
+                "Post-record"
+            ]
+        );
+        // Only the final cooldown runs straight into the post-record
+        assert!(profile.steps[3].wait_for_stabilization);
+        assert!(!profile.steps[6].wait_for_stabilization);
+    }
+
+    #[test]
+    fn test_profile_from_yaml() {
+        let yaml = "steps:\n  - target_temp: 30.0\n    hold_time_s: 60\n    wait_for_stabilization: true\n  - name: Cool\n    target_temp: 20.0\n    hold_time_s: 0\n";
+        let profile: TemperatureProfile = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(profile.steps.len(), 2);
+        assert_eq!(profile.steps[0].label(1), "Step 1 (30.0°C)");
+        assert!(profile.steps[0].wait_for_stabilization);
+        assert_eq!(profile.steps[1].label(2), "Cool");
+        assert!(!profile.steps[1].wait_for_stabilization);
+    }
+
+    #[test]
+    fn test_parameters_from_yaml() {
+        let yaml = "rest_temp: 22.0\nsnap_temp: 30.0\nsnap_hold_time: 5\nprerecord_time: 5\npostrecord_time: 10\ntemperature_tolerance: 0.5\nmax_wait_time: 30\ncycles: 3\n";
+        let params = Parameters::from_yaml_str(yaml).unwrap();
+        assert_eq!(params.rest_temp, 22.0);
+        assert_eq!(params.cycles, 3);
+        assert!(params.save_plot);
+        assert_eq!(params.config_tolerance, ConfigTolerance::default());
+        let rounding = Parameters::from_yaml_str(&format!("{}config_tolerance:\n  pid: 0.05\n", yaml)).unwrap();
+        assert_eq!(rounding.config_tolerance.pid, 0.05);
+        assert_eq!(rounding.config_tolerance.temperature, ConfigTolerance::default().temperature);
+        assert_eq!(params.resync_lines, DEFAULT_RESYNC_LINES);
+        assert_eq!(Parameters::from_yaml_str(&format!("{}resync_lines: 0\n", yaml)).unwrap().resync_lines, 0);
+        assert_eq!(params.ack_policy, AckPolicy::Lenient);
+        let strict = Parameters::from_yaml_str(&format!("{}ack_policy: strict\n", yaml)).unwrap();
+        assert_eq!(strict.ack_policy, AckPolicy::Strict);
+
+        let err = Parameters::from_yaml_str("rest_temp: 22.0\nsnap_hold_time: 5\nmax_wait_time: 30\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "missing required fields: snap_temp, prerecord_time, postrecord_time, temperature_tolerance"
+        );
+
+        let err = Parameters::from_yaml_str(&yaml.replace("22.0", "warm")).err().unwrap();
+        assert!(err.to_string().contains("rest_temp"), "{}", err);
+        assert!(Parameters::from_yaml_str("- 1\n").is_err());
+
+        let camera = "camera:\n  width: 1920\n  height: 1080\n  bit_depth: 10\n  mode_flag: P\n  start_timeout_s: .inf\n";
+        let err = Parameters::from_yaml_str(&format!("{}{}", yaml, camera)).err().unwrap();
+        assert!(err.to_string().contains("start_timeout_s"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let config = TecConfig::default();
+        assert!(Parameters::default().validate(&config).is_ok());
+
+        let params = Parameters {
+            snap_temp: 50.0,
+            snap_hold_time: -1.0,
+            max_wait_time: 0.0,
+            temperature_tolerance: 0.0,
+            ..Parameters::default()
+        };
+        let problems = params.validate(&config).unwrap_err();
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("snap_temp"));
+
+        let params = Parameters {
+            setpoint_bounds: SetpointBounds::new(30.0, 50.0).unwrap(),
+            ..Parameters::default()
+        };
+        let problems = params.validate(&config).unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("rest_temp 25.0°C is outside setpoint_bounds"), "{:?}", problems);
+    }
+
+    #[test]
+    fn test_slope() {
+        let rising = [(0.0, 20.0), (1.0, 20.5), (2.0, 21.0), (3.0, 21.5)];
+        assert!((slope(rising.iter()).unwrap() - 0.5).abs() < 1e-6);
+        assert_eq!(slope([(1.0, 20.0), (1.0, 21.0)].iter()), None);
+        assert_eq!(slope([(0.0, 20.0)].iter()), None);
+    }
+
+    #[test]
+    fn test_slope_mode_waits_for_drift_to_settle() {
+        let mode = StabilizationMode::Slope { window: 3, max_slope: 0.05, band: 1.0 };
+        let drifting: VecDeque<_> = [(0.0, 24.4), (1.0, 24.6), (2.0, 24.8)].into();
+        let settled: VecDeque<_> = [(0.0, 25.7), (1.0, 25.72), (2.0, 25.71)].into();
+        let too_few: VecDeque<_> = [(0.0, 25.0), (1.0, 25.0)].into();
+
+        // Within the classic tolerance but still moving
+        assert!(StabilizationMode::Tolerance.is_stable(&drifting, 25.0, 0.5));
+        assert!(!mode.is_stable(&drifting, 25.0, 0.5));
+        // Flat, outside the tolerance but inside the band
+        assert!(!StabilizationMode::Tolerance.is_stable(&settled, 25.0, 0.5));
+        assert!(mode.is_stable(&settled, 25.0, 0.5));
+        assert!(!mode.is_stable(&too_few, 25.0, 0.5));
+    }
+
+    #[test]
+    fn test_sleep_with_progress_stops_on_abort() {
+        let abort = AtomicBool::new(true);
+        let result = sleep_with_progress(&ProgressBar::hidden(), 60.0, &abort);
+        assert!(matches!(result, Err(ExperimentError::Aborted)));
+    }
+
+    #[test]
+    fn test_log_file_has_header_and_row() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let mut experiment = Experiment::new(Arc::new(Mutex::new(controller)), None, Parameters::default());
+        let scratch = ScratchDir::new("log");
+        experiment.experiment_dir = scratch.as_str().to_string();
+
+        experiment.initialize_log_file().unwrap();
+        link.respond(&[
+            "<o>",
+            "Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+25.01 OC=0 PW=+ 25",
+        ]);
+        let log_path = format!("{}/temperature_log.csv", experiment.experiment_dir);
+        let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+        let readout = experiment.primary().lock().unwrap().get_single_readout().unwrap();
+        append_log_entry(&mut log, experiment.log_settings(), SystemClock.now_ms(), &readout, "Hold at snap temp")
+            .unwrap();
+
+        let contents = fs::read_to_string(&log_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(format!("{}\n", lines[0]), LOG_HEADER);
+        let columns: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(columns.len(), 13);
+        assert_eq!(
+            &columns[1..],
+            [
+                "25.00",
+                "5.00",
+                "2.00",
+                "1.00",
+                "0.00",
+                "50.00",
+                "25.01",
+                "0",
+                "25.0",
+                "0",
+                "Hold at snap temp",
+                "25.01"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_and_phase_times_follow_the_clock() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let params = Parameters {
+            log_timestamps: LogTimestamps::Both,
+            ..Parameters::default()
+        };
+        let mut experiment = Experiment::new(Arc::new(Mutex::new(controller)), None, params);
+        let clock = Arc::new(MockClock::new(1_700_000_000_000));
+        experiment.set_clock(clock.clone());
+        let scratch = ScratchDir::new("clock");
+        experiment.experiment_dir = scratch.as_str().to_string();
+        experiment.initialize_log_file().unwrap();
+
+        clock.advance(2_500);
+        link.respond(&[
+            "<o>",
+            "Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+25.01 OC=0 PW=+ 25",
+        ]);
+        let mut log = Vec::new();
+        let readout = experiment.primary().lock().unwrap().get_single_readout().unwrap();
+        append_log_entry(&mut log, experiment.log_settings(), clock.now_ms(), &readout, "Heat").unwrap();
+        let row = String::from_utf8(log).unwrap();
+        assert!(row.starts_with("1700000002500,"), "{}", row);
+        assert!(row.ends_with(",2500,Heat,25.01\n"), "{}", row);
+
+        let mark = |time_ms| PhaseMark { time_ms, readout: None };
+        link.respond(&[
+            "<o>",
+            "Tz=+30.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+29.80 OC=0 PW=+ 25",
+        ]);
+        let end = experiment.phase_mark();
+        assert_eq!(end.time_ms, 1_700_000_002_500);
+        experiment.record_phase_timing("Heat".to_string(), mark(1_700_000_000_000), end);
+        // A clock stepped back between start and end gives zero, not an underflow
+        experiment.record_phase_timing("Hold".to_string(), mark(1_700_000_002_500), mark(1_700_000_001_000));
+        let durations: Vec<f64> = experiment.phase_timings.iter().map(|timing| timing.duration_s).collect();
+        assert_eq!(durations, [2.5, 0.0]);
+
+        let heat = &experiment.phase_timings[0];
+        assert_eq!((heat.t_measured_start, heat.t_set_start), (None, None));
+        assert_eq!((heat.t_measured_end, heat.t_set_end), (Some(29.8), Some(30.0)));
+        let yaml = serde_yaml::to_string(heat).unwrap();
+        assert!(yaml.contains("t_measured_end: 29.8") && !yaml.contains("t_measured_start"), "{}", yaml);
+    }
+
+    #[test]
+    fn test_temperature_logger_stops_and_flushes() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let mut experiment = Experiment::new(Arc::new(Mutex::new(controller)), None, Parameters::default());
+        let scratch = ScratchDir::new("logger");
+        experiment.experiment_dir = scratch.as_str().to_string();
+        experiment.initialize_log_file().unwrap();
+        for _ in 0..3 {
+            link.respond(&[
+                "<o>",
+                "Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+25.01 OC=0 PW=+ 25",
+            ]);
+        }
+
+        let mut logger = experiment.start_temperature_logging().unwrap();
+        thread::sleep(Duration::from_millis(600));
+        logger.stop();
+        let polls = link.written().matches('o').count();
+        logger.stop();
+        thread::sleep(LOG_INTERVAL * 3);
+
+        // No polls after stopping, and every buffered row reached the file
+        assert_eq!(link.written().matches('o').count(), polls);
+        let contents = fs::read_to_string(experiment.log_path(0)).unwrap();
+        assert_eq!(contents.lines().count(), 4);
+        assert!(experiment.latest_readout.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_controllers_move_and_log_together() {
+        let links = [MockLink::connected(), MockLink::connected()];
+        let tecs = links
+            .iter()
+            .zip(["left", "right"])
+            .map(|(link, label)| {
+                let controller = TecController::with_link(link.clone()).unwrap();
+                (label.to_string(), Arc::new(Mutex::new(controller)))
+            })
+            .collect();
+        let mut experiment = Experiment::with_controllers(tecs, None, Parameters::default());
+        let scratch = ScratchDir::new("lockstep");
+        experiment.experiment_dir = scratch.as_str().to_string();
+
+        let ack = DEFAULT_CONFIG_ACK.replace("eTzc=20.00", "eTzc=30.00");
+        // Only the first controller is read at the phase boundaries
+        let readout = "Tz=+30.00 P= 5.50 I= 2.50 D= 0.50 T=  0...+35 Tr=+29.50 OC=0 PW=+ 25";
+        links[0].respond(&["<o>", readout]);
+        for link in &links {
+            link.respond(&[ack.as_str()]);
+        }
+        links[0].respond(&["<o>", readout]);
+        experiment
+            .run_step(1, &ProfileStep::named("Heat", 30.0, 0.0, false), &idle_logger())
+            .unwrap();
+        assert!(links[0].written().ends_with("o<30 5.5 2.5 0.5 0 35>o"));
+        assert!(links[1].written().ends_with("<30 5.5 2.5 0.5 0 35>"));
+        assert_eq!(experiment.phase_timings[0].t_measured_end, Some(29.5));
+
+        experiment.initialize_log_file().unwrap();
+        for link in &links {
+            for _ in 0..3 {
+                link.respond(&[
+                    "<o>",
+                    "Tz=+30.00 P= 5.50 I= 2.50 D= 0.50 T=  0...+35 Tr=+29.50 OC=0 PW=+ 25",
+                ]);
+            }
+        }
+        let mut logger = experiment.start_temperature_logging().unwrap();
+        thread::sleep(LOG_INTERVAL + LOG_INTERVAL / 2);
+        logger.stop();
+
+        for label in ["left", "right"] {
+            let log_path = format!("{}/temperature_log_{}.csv", experiment.experiment_dir, label);
+            let contents = fs::read_to_string(&log_path).unwrap();
+            assert!(contents.lines().count() >= 2, "{}: {}", log_path, contents);
+        }
+        assert!(!Path::new(&format!("{}/temperature_log.csv", experiment.experiment_dir)).exists());
+    }
+
+    #[test]
+    fn test_step_errors_name_the_phase() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let params = Parameters {
+            max_wait_time: 0.01,
+            ..Parameters::default()
+        };
+        let mut experiment =
+            Experiment::with_controllers(vec![("tec".to_string(), Arc::new(Mutex::new(controller)))], None, params);
+        let logger = idle_logger();
+
+        // No acknowledgment for the new setpoint
+        let e = experiment
+            .run_step(1, &ProfileStep::named("Heat to snap temp", 30.0, 0.0, false), &logger)
+            .unwrap_err();
+        assert!(
+            matches!(e, ExperimentError::Setpoint { ref phase, source: TecError::Timeout } if phase == "Heat to snap temp"),
+            "{:?}",
+            e
+        );
+        assert!(e.to_string().starts_with("Failed to change the setpoint during Heat to snap temp"));
+
+        // Already at the setpoint, but no readouts to show it settled
+        let e = experiment.run_step(2, &ProfileStep::named("Hold", 20.0, 0.0, true), &logger).unwrap_err();
+        assert!(
+            matches!(e, ExperimentError::Stabilization { ref phase, target } if phase == "Hold" && target == 20.0),
+            "{:?}",
+            e
+        );
+    }
+
+    #[test]
+    fn test_json_lines_log() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let params = Parameters {
+            log_format: LogFormat::JsonLines,
+            ..Parameters::default()
+        };
+        let mut experiment = Experiment::new(Arc::new(Mutex::new(controller)), None, params);
+        let scratch = ScratchDir::new("jsonl");
+        experiment.experiment_dir = scratch.as_str().to_string();
+
+        experiment.initialize_log_file().unwrap();
+        link.respond(&[
+            "<o>",
+            "Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+25.01 OC=0 PW=+ 25",
+        ]);
+        let log_path = experiment.log_path(0);
+        assert!(log_path.ends_with("temperature_log.jsonl"));
+        let mut log = OpenOptions::new().create(true).append(true).open(&log_path).unwrap();
+        let readout = experiment.primary().lock().unwrap().get_single_readout().unwrap();
+        append_log_entry(&mut log, experiment.log_settings(), SystemClock.now_ms(), &readout, "Heat").unwrap();
+
+        let contents = fs::read_to_string(&log_path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert!(record["timestamp_ms"].as_u64().unwrap() > 0);
+        assert_eq!(record["t_measured"].as_f64(), Some(25.01));
+        assert_eq!(record["oc"], false);
+        assert_eq!(record["phase"], "Heat");
+    }
+
+    #[test]
+    fn test_annotation_text() {
+        let params = Parameters {
+            rest_temp: 25.0,
+            snap_temp: 42.5,
+            cycles: 3,
+            ..Parameters::default()
+        };
+        assert_eq!(
+            annotation_text(
+                "{experiment}: {rest_temp} -> {snap_temp}°C x{cycles} %frame",
+                &params,
+                "experiments/experiment_1700000000000"
+            ),
+            "experiment_1700000000000: 25.0 -> 42.5°C x3 %frame"
+        );
+    }
+
+    #[test]
+    fn test_log_timestamps() {
+        let readout = TecReadout {
+            t_set: 25.0,
+            p: 5.0,
+            i: 2.0,
+            d: 1.0,
+            t_min: 0.0,
+            t_max: 50.0,
+            t_measured: 25.01,
+            oc: false,
+            pwm: 25.0,
+            resync_count: 1,
+            t_measured_device: Some(25.81),
+        };
+        let row = |format, timestamps| {
+            let settings = LogSettings {
+                format,
+                timestamps,
+                start_ms: 10_000,
+            };
+            let mut log = Vec::new();
+            append_log_entry(&mut log, settings, 12_500, &readout, "Heat").unwrap();
+            String::from_utf8(log).unwrap()
+        };
+
+        assert!(row(LogFormat::Csv, LogTimestamps::Absolute).starts_with("12500,25.00,"));
+        assert!(row(LogFormat::Csv, LogTimestamps::Relative).starts_with("2500,25.00,"));
+        let both = row(LogFormat::Csv, LogTimestamps::Both);
+        assert!(both.starts_with("12500,25.00,"));
+        assert!(both.ends_with(",25.01,0,25.0,1,2500,Heat,25.81\n"));
+        assert_eq!(both.split(',').count(), BOTH_LOG_HEADER.split(',').count());
+
+        let record: serde_json::Value =
+            serde_json::from_str(&row(LogFormat::JsonLines, LogTimestamps::Relative)).unwrap();
+        assert_eq!(record["elapsed_ms"], 2500);
+        assert_eq!(record["resync_count"], 1);
+        assert_eq!(record["t_measured_raw"].as_f64(), Some(25.81));
+        assert!(record.get("t_measured_device").is_none());
+        assert!(record.get("timestamp_ms").is_none());
+        let record: serde_json::Value =
+            serde_json::from_str(&row(LogFormat::JsonLines, LogTimestamps::Both)).unwrap();
+        assert_eq!(record["timestamp_ms"], 12_500);
+        assert_eq!(record["elapsed_ms"], 2500);
+    }
+
+    #[test]
+    fn test_output_directory() {
+        // Setting HOME would race other tests, so use whatever the runner has
+        match std::env::var_os("HOME") {
+            Some(home) => {
+                assert_eq!(expand_home("~"), PathBuf::from(&home));
+                assert_eq!(expand_home("~/runs/tec"), Path::new(&home).join("runs/tec"));
+            }
+            None => assert_eq!(expand_home("~/runs/tec"), PathBuf::from("~/runs/tec")),
+        }
+        assert_eq!(expand_home("~other/runs"), PathBuf::from("~other/runs"));
+        assert_eq!(expand_home("/mnt/usb"), PathBuf::from("/mnt/usb"));
+        assert_eq!(Parameters::default().output_base(), PathBuf::from(DEFAULT_OUTPUT_DIR));
+
+        // Missing parents are created, and the index goes next to the runs
+        let scratch = ScratchDir::new("output");
+        let params = Parameters {
+            output_dir: Some(format!("{}/drive/runs", scratch.as_str())),
+            ..Parameters::default()
+        };
+        let clock = MockClock::new(1_700_000_000_000);
+        let experiment_dir = create_experiment_directory(&params, &clock).unwrap();
+        assert_eq!(experiment_dir, format!("{}/drive/runs/experiment_1700000000000", scratch.as_str()));
+        assert!(Path::new(&experiment_dir).is_dir());
+        clock.advance(90_000);
+        record_final_status(&experiment_dir, &Ok(()), &clock);
+        let index = fs::read_to_string(format!("{}/drive/runs/{}", scratch.as_str(), INDEX_FILE)).unwrap();
+        let timestamps: Vec<u64> = index
+            .lines()
+            .map(|line| serde_json::from_str::<IndexEntry>(line).unwrap().timestamp_ms)
+            .collect();
+        assert_eq!(timestamps, [1_700_000_000_000, 1_700_000_090_000]);
+
+        // A file where the base should be
+        let params = Parameters {
+            output_dir: Some(format!("{}/{}", experiment_dir, INDEX_FILE)),
+            ..Parameters::default()
+        };
+        fs::write(params.output_dir.as_ref().unwrap(), "").unwrap();
+        let e = create_experiment_directory(&params, &clock).unwrap_err();
+        assert!(e.to_string().starts_with("cannot create experiment directory under"), "{}", e);
+    }
+
+    #[test]
+    fn test_index_entries_append() {
+        let scratch = ScratchDir::new("index");
+        let path = scratch.path().join(INDEX_FILE);
+        let started = IndexEntry {
+            directory: "experiments/experiment_1000".to_string(),
+            timestamp_ms: 1000,
+            status: RunStatus::Started,
+            rest_temp: Some(25.0),
+            snap_temp: Some(40.0),
+            cycles: Some(2),
+        };
+        let finished = IndexEntry {
+            timestamp_ms: 5000,
+            status: RunStatus::Aborted,
+            rest_temp: None,
+            snap_temp: None,
+            cycles: None,
+            ..started.clone()
+        };
+        append_index_entry(&path, &started).unwrap();
+        append_index_entry(&path, &finished).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let entries: Vec<IndexEntry> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries, [started, finished]);
+        assert!(contents.lines().nth(1).unwrap().contains("\"status\":\"aborted\""));
+        assert!(!contents.lines().nth(1).unwrap().contains("rest_temp"));
+    }
+
+    #[test]
+    fn test_csv_field_quotes_separators() {
+        assert_eq!(csv_field("Post-record"), "Post-record");
+        assert_eq!(csv_field("Heat, fast"), "\"Heat, fast\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -1,11 +1,74 @@
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Shortest PWM period accepted by `Fan::new` (5 MHz)
+const MIN_PERIOD_NS: u32 = 200;
+
+/// PWM frequency of the station's fan; 25 kHz is above hearing and what
+/// 4-pin PC fans expect
+pub const DEFAULT_FAN_FREQUENCY_HZ: u32 = 25_000;
+
+/// Piecewise-linear map from TEC drive to fan speed.
+///
+/// Breakpoints are `(pwm_threshold, fan_percent)` pairs; the TEC PWM is taken
+/// as a magnitude so heating and cooling both spin the fan up. Below the first
+/// and above the last breakpoint the end values are held.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct FanCurve {
+    points: Vec<(f32, f32)>,
+}
+
+impl FanCurve {
+    pub fn new(mut points: Vec<(f32, f32)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        FanCurve { points }
+    }
+
+    /// Fan speed (0-100) for a TEC PWM reading in percent
+    pub fn fan_percent(&self, tec_pwm_percent: f32) -> u8 {
+        let x = tec_pwm_percent.abs();
+        let percent = match self.points.iter().position(|&(threshold, _)| threshold >= x) {
+            None => self.points.last().map_or(0.0, |&(_, y)| y),
+            Some(0) => self.points[0].1,
+            Some(i) => {
+                let (x0, y0) = self.points[i - 1];
+                let (x1, y1) = self.points[i];
+                y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+            }
+        };
+        percent.clamp(0.0, 100.0).round() as u8
+    }
+}
+
+impl From<Vec<(f32, f32)>> for FanCurve {
+    fn from(points: Vec<(f32, f32)>) -> Self {
+        FanCurve::new(points)
+    }
+}
+
+impl From<FanCurve> for Vec<(f32, f32)> {
+    fn from(curve: FanCurve) -> Self {
+        curve.points
+    }
+}
 
 pub struct Fan {
     pwm_chip: u32,
     pwm_channel: u32,
     period_ns: u32,
+    /// Skip sysfs writes, for running without the PWM hardware
+    dry_run: bool,
+    /// Channel set up by something else, e.g. a device-tree overlay; left
+    /// enabled and exported on drop
+    attached: bool,
+    /// Speed last written, for ramps when the duty cycle can't be read back
+    speed_percent: AtomicU8,
 }
 
 impl Fan {
@@ -16,10 +79,24 @@ impl Fan {
     /// * `pwm_channel` - PWM channel number (typically 0)
     /// * `frequency_hz` - Desired PWM frequency in Hz (e.g., 25000 for 25kHz)
     pub fn new(pwm_chip: u32, pwm_channel: u32, frequency_hz: u32) -> io::Result<Self> {
+        if frequency_hz == 0 || 1_000_000_000 / frequency_hz < MIN_PERIOD_NS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "PWM frequency must be between 1 Hz and {} Hz, got {}",
+                    1_000_000_000 / MIN_PERIOD_NS,
+                    frequency_hz
+                ),
+            ));
+        }
+
         let fan = Fan {
             pwm_chip,
             pwm_channel,
             period_ns: 1_000_000_000 / frequency_hz, // Convert Hz to nanoseconds
+            dry_run: false,
+            attached: false,
+            speed_percent: AtomicU8::new(0),
         };
         
         // // Export the PWM channel if not already exported
@@ -41,19 +118,110 @@ impl Fan {
         Ok(fan)
     }
     
+    /// Use a channel that is already exported, configured and enabled, e.g.
+    /// by a device-tree overlay at boot.
+    ///
+    /// The period is read back from sysfs instead of being set, and nothing
+    /// is exported, enabled, disabled or unexported, so the overlay keeps
+    /// ownership of the channel and no EBUSY conflicts arise.
+    #[allow(dead_code, reason = "for rigs whose device-tree overlay owns the channel; no option selects it yet")]
+    pub fn attach(pwm_chip: u32, pwm_channel: u32) -> io::Result<Self> {
+        // The frequency is a placeholder until the real period is read
+        let mut fan = Fan::new(pwm_chip, pwm_channel, 25_000)?;
+        fan.attached = true;
+
+        let value = fan.read_attribute("period")?;
+        let period_ns: u32 = value.trim().parse().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid period value {:?}: {}", value.trim(), e),
+            )
+        })?;
+        if period_ns < MIN_PERIOD_NS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("PWM period of {} ns is below {} ns; is the channel configured?", period_ns, MIN_PERIOD_NS),
+            ));
+        }
+        fan.period_ns = period_ns;
+        fan.speed_percent.store(fan.get_speed_percent().unwrap_or(0), Ordering::SeqCst);
+        Ok(fan)
+    }
+
+    /// Fan that accepts every command without touching sysfs
+    pub fn dry_run(frequency_hz: u32) -> io::Result<Self> {
+        let mut fan = Fan::new(0, 0, frequency_hz)?;
+        fan.dry_run = true;
+        Ok(fan)
+    }
+
     /// Set fan speed as a percentage (0-100)
     pub fn set_speed_percent(&self, percent: u8) -> io::Result<()> {
         let percent = percent.min(100); // Clamp to 100%
         let duty_cycle = (self.period_ns as u64 * percent as u64 / 100) as u32;
-        self.write_attribute("duty_cycle", &duty_cycle.to_string())
+        self.write_attribute("duty_cycle", &duty_cycle.to_string())?;
+        self.speed_percent.store(percent, Ordering::SeqCst);
+        Ok(())
     }
     
     /// Set fan speed with raw duty cycle value (0 to period_ns)
+    #[allow(dead_code, reason = "raw nanosecond control; callers set speeds in percent")]
     pub fn set_duty_cycle(&self, duty_cycle_ns: u32) -> io::Result<()> {
         let duty_cycle = duty_cycle_ns.min(self.period_ns);
-        self.write_attribute("duty_cycle", &duty_cycle.to_string())
+        self.write_attribute("duty_cycle", &duty_cycle.to_string())?;
+        let percent = (duty_cycle as u64 * 100 / self.period_ns as u64) as u8;
+        self.speed_percent.store(percent, Ordering::SeqCst);
+        Ok(())
     }
     
+    /// Read back the current duty cycle in nanoseconds
+    pub fn get_duty_cycle(&self) -> io::Result<u32> {
+        let value = self.read_attribute("duty_cycle")?;
+        value.trim().parse().map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid duty_cycle value {:?}: {}", value.trim(), e),
+            )
+        })
+    }
+
+    /// Read back the current fan speed as a percentage (0-100)
+    pub fn get_speed_percent(&self) -> io::Result<u8> {
+        let duty_cycle = self.get_duty_cycle()?.min(self.period_ns);
+        Ok((duty_cycle as u64 * 100 / self.period_ns as u64) as u8)
+    }
+
+    /// Step the speed linearly from where it is now to `target_percent` in
+    /// `steps` equal steps spread over `duration`, avoiding the thunk and
+    /// current spike of a sudden change. The starting speed is read back from
+    /// sysfs, or taken from the last write when that fails or in dry-run mode.
+    /// Zero steps jumps straight to the target.
+    #[allow(dead_code, reason = "the fan curve sets speeds directly; kept for manual control")]
+    pub fn ramp_to(&self, target_percent: u8, duration: Duration, steps: u32) -> io::Result<()> {
+        let from = if self.dry_run {
+            self.speed_percent.load(Ordering::SeqCst)
+        } else {
+            self.get_speed_percent()
+                .unwrap_or_else(|_| self.speed_percent.load(Ordering::SeqCst))
+        };
+        let speeds = ramp_percentages(from, target_percent.min(100), steps);
+        let step_delay = duration / steps.max(1);
+
+        let last = speeds.len() - 1;
+        for (n, percent) in speeds.into_iter().enumerate() {
+            self.set_speed_percent(percent)?;
+            if n < last {
+                thread::sleep(step_delay);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the fan speed from the TEC's PWM output via `curve`
+    pub fn apply_curve(&self, tec_pwm_percent: f32, curve: &FanCurve) -> io::Result<()> {
+        self.set_speed_percent(curve.fan_percent(tec_pwm_percent))
+    }
+
     /// Turn fan off
     pub fn off(&self) -> io::Result<()> {
         self.set_speed_percent(0)
@@ -65,6 +233,7 @@ impl Fan {
     }
     
     /// Enable PWM output
+    #[allow(dead_code, reason = "the channel is enabled at export time until the lifecycle in `new` is turned back on")]
     pub fn enable(&self) -> io::Result<()> {
         self.write_attribute("enable", "1")
     }
@@ -74,20 +243,23 @@ impl Fan {
         self.write_attribute("enable", "0")
     }
     
-    // Helper methods
-    
-    fn pwm_path(&self) -> String {
+    /// Sysfs directory of the channel, e.g. `/sys/class/pwm/pwmchip0/pwm0`
+    pub fn pwm_path(&self) -> String {
         format!("/sys/class/pwm/pwmchip{}/pwm{}", self.pwm_chip, self.pwm_channel)
     }
     
-    fn chip_path(&self) -> String {
+    /// Sysfs directory of the PWM chip, e.g. `/sys/class/pwm/pwmchip0`
+    pub fn chip_path(&self) -> String {
         format!("/sys/class/pwm/pwmchip{}", self.pwm_chip)
     }
     
+    // Helper methods
+
     fn is_exported(&self) -> bool {
         Path::new(&self.pwm_path()).exists()
     }
     
+    #[allow(dead_code, reason = "part of the PWM lifecycle left commented out in `new`")]
     fn export(&self) -> io::Result<()> {
         let export_path = format!("{}/export", self.chip_path());
         let mut file = OpenOptions::new().write(true).open(export_path)?;
@@ -96,7 +268,7 @@ impl Fan {
     }
     
     fn unexport(&self) -> io::Result<()> {
-        if self.is_exported() {
+        if !self.dry_run && self.is_exported() {
             let unexport_path = format!("{}/unexport", self.chip_path());
             let mut file = OpenOptions::new().write(true).open(unexport_path)?;
             write!(file, "{}", self.pwm_channel)?;
@@ -104,7 +276,27 @@ impl Fan {
         Ok(())
     }
     
+    fn read_attribute(&self, attribute: &str) -> io::Result<String> {
+        let path = format!("{}/{}", self.pwm_path(), attribute);
+        let mut file = File::open(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} not found; is PWM channel {} exported?", path, self.pwm_channel),
+                )
+            } else {
+                e
+            }
+        })?;
+        let mut value = String::new();
+        file.read_to_string(&mut value)?;
+        Ok(value)
+    }
+
     fn write_attribute(&self, attribute: &str, value: &str) -> io::Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
         let path = format!("{}/{}", self.pwm_path(), attribute);
         let mut file = OpenOptions::new().write(true).open(&path)?;
         write!(file, "{}", value)?;
@@ -112,8 +304,22 @@ impl Fan {
     }
 }
 
+/// Speeds after each of `steps` equal steps from `from` to `to`, ending on
+/// `to`; just `[to]` for zero steps
+fn ramp_percentages(from: u8, to: u8, steps: u32) -> Vec<u8> {
+    let steps = steps.max(1);
+    let delta = to as f32 - from as f32;
+    (1..=steps)
+        .map(|n| (from as f32 + delta * n as f32 / steps as f32).round() as u8)
+        .collect()
+}
+
 impl Drop for Fan {
     fn drop(&mut self) {
+        // An attached channel belongs to whatever configured it
+        if self.attached {
+            return;
+        }
         // Clean shutdown: disable and unexport
         let _ = self.disable();
         let _ = self.unexport();
@@ -124,6 +330,62 @@ impl Drop for Fan {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_fan_curve_interpolates() {
+        let curve = FanCurve::new(vec![(80.0, 100.0), (20.0, 30.0), (50.0, 60.0)]);
+        assert_eq!(curve.fan_percent(0.0), 30);
+        assert_eq!(curve.fan_percent(20.0), 30);
+        assert_eq!(curve.fan_percent(35.0), 45);
+        assert_eq!(curve.fan_percent(-65.0), 80);
+        assert_eq!(curve.fan_percent(100.0), 100);
+        assert_eq!(FanCurve::new(Vec::new()).fan_percent(50.0), 0);
+    }
+
+    #[test]
+    fn test_ramp_percentages() {
+        assert_eq!(ramp_percentages(0, 100, 4), [25, 50, 75, 100]);
+        assert_eq!(ramp_percentages(80, 20, 3), [60, 40, 20]);
+        assert_eq!(ramp_percentages(10, 11, 4), [10, 11, 11, 11]);
+        assert_eq!(ramp_percentages(40, 90, 0), [90]);
+    }
+
+    #[test]
+    fn test_ramp_to_starts_from_last_speed() {
+        let fan = Fan::dry_run(25_000).unwrap();
+        fan.set_speed_percent(40).unwrap();
+        let start = std::time::Instant::now();
+        fan.ramp_to(80, Duration::from_millis(40), 4).unwrap();
+        assert_eq!(fan.speed_percent.load(Ordering::SeqCst), 80);
+        // Three waits between four steps
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        fan.ramp_to(200, Duration::ZERO, 2).unwrap();
+        assert_eq!(fan.speed_percent.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn test_get_duty_cycle_reports_unexported_channel() {
+        let fan = Fan::new(999, 0, 25_000).unwrap();
+        let err = fan.get_duty_cycle().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("exported"));
+    }
+
+    #[test]
+    fn test_attach_requires_exported_channel() {
+        let err = Fan::attach(999, 0).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("/sys/class/pwm/pwmchip999/pwm0/period"));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_frequency() {
+        for frequency_hz in [0, 10_000_000, u32::MAX] {
+            let err = Fan::new(0, 0, frequency_hz).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
     
     #[test]
     #[ignore] // Requires actual hardware
@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+/// One polled readout, reduced to what the charts and exports use
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempData {
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    pub set_temp: f32,
+    pub measured_temp: f32,
+    pub pwm: f32,
+}
+
+/// Readout history holding at most `capacity` samples; pushing onto a full
+/// history drops the oldest one
+#[derive(Debug, Clone)]
+pub struct RollingHistory {
+    samples: VecDeque<TempData>,
+    capacity: usize,
+}
+
+impl RollingHistory {
+    pub fn new(capacity: usize) -> Self {
+        RollingHistory {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: TempData) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &TempData> + ExactSizeIterator {
+        self.samples.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&TempData> {
+        self.samples.back()
+    }
+
+    /// Samples from `span` seconds before `end` up to `end`, oldest first
+    pub fn window(&self, end: f64, span: f64) -> Vec<&TempData> {
+        let start = end - span;
+        self.samples
+            .iter()
+            .filter(|data| data.timestamp >= start && data.timestamp <= end)
+            .collect()
+    }
+}
+
+/// Seconds covered by `samples`, which must be oldest first
+pub fn time_span(samples: &[&TempData]) -> f64 {
+    match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) => last.timestamp - first.timestamp,
+        _ => 0.0,
+    }
+}
+
+/// Lowest and highest of the setpoint and measured temperature
+pub fn temp_range(samples: &[&TempData]) -> Option<(f32, f32)> {
+    min_max(samples.iter().flat_map(|data| [data.set_temp, data.measured_temp]))
+}
+
+pub fn pwm_range(samples: &[&TempData]) -> Option<(f32, f32)> {
+    min_max(samples.iter().map(|data| data.pwm))
+}
+
+fn min_max(values: impl Iterator<Item = f32>) -> Option<(f32, f32)> {
+    values.fold(None, |range, value| match range {
+        None => Some((value, value)),
+        Some((lo, hi)) => Some((lo.min(value), hi.max(value))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: f64, measured_temp: f32) -> TempData {
+        TempData {
+            timestamp,
+            set_temp: 25.0,
+            measured_temp,
+            pwm: measured_temp - 25.0,
+        }
+    }
+
+    #[test]
+    fn test_push_drops_oldest_at_capacity() {
+        let mut history = RollingHistory::new(3);
+        for i in 0..5 {
+            history.push(sample(i as f64, 20.0 + i as f32));
+        }
+
+        assert_eq!(history.len(), 3);
+        let timestamps: Vec<f64> = history.iter().map(|data| data.timestamp).collect();
+        assert_eq!(timestamps, [2.0, 3.0, 4.0]);
+        assert_eq!(history.latest().unwrap().timestamp, 4.0);
+    }
+
+    #[test]
+    fn test_window_and_ranges() {
+        let mut history = RollingHistory::new(10);
+        for (t, temp) in [(0.0, 22.0), (10.0, 24.0), (20.0, 27.0), (30.0, 26.0)] {
+            history.push(sample(t, temp));
+        }
+
+        let window = history.window(20.0, 10.0);
+        assert_eq!(window.len(), 2);
+        assert_eq!(time_span(&window), 10.0);
+        assert_eq!(temp_range(&window), Some((24.0, 27.0)));
+        assert_eq!(pwm_range(&window), Some((-1.0, 2.0)));
+
+        assert!(history.window(100.0, 10.0).is_empty());
+        assert_eq!(temp_range(&[]), None);
+        assert_eq!(time_span(&[]), 0.0);
+    }
+}
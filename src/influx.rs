@@ -0,0 +1,317 @@
+use crate::net;
+use crate::tec::TecReadout;
+use crate::time;
+use log::{debug, warn};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Server port used when the URL has none
+const DEFAULT_INFLUX_PORT: u16 = 8086;
+
+/// Value of the `station` tag when none is given
+pub const DEFAULT_STATION: &str = "glow-station";
+
+/// How often buffered readouts are written
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Most readouts buffered between flushes; the oldest go first
+const MAX_BUFFERED_LINES: usize = 10_000;
+
+/// Deadline for connecting to the server and for each read or write
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where and how readouts are written
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfluxConfig {
+    pub host: String,
+    pub port: u16,
+    /// Path and query of the write endpoint, e.g.
+    /// `/api/v2/write?org=lab&bucket=tec&precision=ms`
+    pub path: String,
+    /// `station` tag on every record
+    pub station: String,
+    /// `port` tag on every record
+    pub serial_port: String,
+    /// Sent as `Authorization: Token <token>` when set
+    pub token: Option<String>,
+    pub flush_interval: Duration,
+}
+
+impl InfluxConfig {
+    /// Parse an `http://host[:port]/path?query` write URL. Timestamps are
+    /// sent in milliseconds, so the URL needs `precision=ms`; it is added
+    /// when missing.
+    pub fn new(url: &str, station: &str, serial_port: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("InfluxDB URL must start with http://: {:?}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/write"),
+        };
+        let (host, port) = match net::split_host_port(authority)? {
+            (host, Some(port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid InfluxDB port: {:?}", port))?;
+                (host, port)
+            }
+            (host, None) => (host, DEFAULT_INFLUX_PORT),
+        };
+        if host.is_empty() {
+            return Err("InfluxDB host is empty".to_string());
+        }
+        let path = if path.contains("precision=") {
+            path.to_string()
+        } else if path.contains('?') {
+            format!("{}&precision=ms", path)
+        } else {
+            format!("{}?precision=ms", path)
+        };
+        Ok(InfluxConfig {
+            host: host.to_string(),
+            port,
+            path,
+            station: station.to_string(),
+            serial_port: serial_port.to_string(),
+            token: None,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+        })
+    }
+}
+
+/// Write every readout to InfluxDB as a `tec` measurement, in batches every
+/// `flush_interval`.
+///
+/// Runs on its own thread so a slow or unreachable server never holds up
+/// the monitor; a batch that cannot be written is dropped with a warning.
+pub fn start(config: InfluxConfig, readouts: Receiver<TecReadout>) {
+    thread::spawn(move || {
+        let mut batch: VecDeque<String> = VecDeque::new();
+        let mut next_flush = Instant::now() + config.flush_interval;
+
+        loop {
+            let wait = next_flush.saturating_duration_since(Instant::now());
+            let done = match readouts.recv_timeout(wait) {
+                Ok(readout) => {
+                    if batch.len() >= MAX_BUFFERED_LINES {
+                        batch.pop_front();
+                    }
+                    batch.push_back(line_protocol(&config, &readout, time::now_ms()));
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if !batch.is_empty() {
+                match write_batch(&config, batch.make_contiguous()) {
+                    Ok(()) => debug!("Wrote {} readouts to InfluxDB", batch.len()),
+                    Err(e) => warn!("Dropping {} readouts, InfluxDB write failed: {}", batch.len(), e),
+                }
+                batch.clear();
+            }
+            if done {
+                return;
+            }
+            next_flush = Instant::now() + config.flush_interval;
+        }
+    });
+}
+
+/// One line-protocol record:
+/// `tec,station=<station>,port=<port> t_set=...,pwm=... <timestamp_ms>`
+fn line_protocol(config: &InfluxConfig, readout: &TecReadout, timestamp_ms: u64) -> String {
+    let numbers = [
+        ("t_set", readout.t_set),
+        ("p", readout.p),
+        ("i", readout.i),
+        ("d", readout.d),
+        ("t_min", readout.t_min),
+        ("t_max", readout.t_max),
+        ("t_measured", readout.t_measured),
+    ];
+    // Line protocol has no NaN or infinity, and one such field would get the
+    // whole batch rejected
+    let mut fields: Vec<String> = numbers
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    fields.push(format!("oc={}", readout.oc));
+    if readout.pwm.is_finite() {
+        fields.push(format!("pwm={}", readout.pwm));
+    }
+    format!(
+        "tec,station={},port={} {} {}",
+        escape_tag(&config.station),
+        escape_tag(&config.serial_port),
+        fields.join(","),
+        timestamp_ms
+    )
+}
+
+/// Backslash the characters line protocol treats as separators in a tag
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// POST `lines` to the write endpoint; anything but a 2xx status is an error
+fn write_batch(config: &InfluxConfig, lines: &[String]) -> Result<(), String> {
+    let body = lines.join("\n");
+    let address = (config.host.as_str(), config.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve {}: {}", config.host, e))?
+        .next()
+        .ok_or_else(|| format!("no address for {}", config.host))?;
+    let mut stream = TcpStream::connect_timeout(&address, HTTP_TIMEOUT).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n",
+        config.path,
+        net::format_host(&config.host),
+        config.port,
+        body.len()
+    );
+    if let Some(ref token) = config.token {
+        request.push_str(&format!("Authorization: Token {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    request.push_str(&body);
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(format!("server answered {:?}", status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    fn readout() -> TecReadout {
+        TecReadout {
+            t_set: 25.0,
+            p: 5.5,
+            i: 2.5,
+            d: 0.5,
+            t_min: 0.0,
+            t_max: 35.0,
+            t_measured: 24.75,
+            oc: false,
+            pwm: -12.5,
+            resync_count: 0,
+            t_measured_device: None,
+        }
+    }
+
+    #[test]
+    fn test_config_new() {
+        let config = InfluxConfig::new("http://influx.local:8087/api/v2/write?org=lab&bucket=tec", "rig 1", "/dev/serial0")
+            .unwrap();
+        assert_eq!(config.host, "influx.local");
+        assert_eq!(config.port, 8087);
+        assert_eq!(config.path, "/api/v2/write?org=lab&bucket=tec&precision=ms");
+
+        let config = InfluxConfig::new("http://influx.local", DEFAULT_STATION, "/dev/serial0").unwrap();
+        assert_eq!(config.port, DEFAULT_INFLUX_PORT);
+        assert_eq!(config.path, "/write?precision=ms");
+        let config = InfluxConfig::new("http://influx.local/write?db=lab&precision=s", "x", "y").unwrap();
+        assert_eq!(config.path, "/write?db=lab&precision=s");
+
+        assert!(InfluxConfig::new("https://influx.local", "x", "y").is_err());
+        assert!(InfluxConfig::new("http://:8086/write", "x", "y").is_err());
+        assert!(InfluxConfig::new("http://influx.local:http/write", "x", "y").is_err());
+
+        let config = InfluxConfig::new("http://[::1]:8087/write", "x", "y").unwrap();
+        assert_eq!((config.host.as_str(), config.port), ("::1", 8087));
+    }
+
+    #[test]
+    fn test_line_protocol() {
+        let config = InfluxConfig::new("http://influx.local", "rig 1", "/dev/tty,USB0").unwrap();
+        assert_eq!(
+            line_protocol(&config, &readout(), 1_700_000_000_000),
+            "tec,station=rig\\ 1,port=/dev/tty\\,USB0 t_set=25,p=5.5,i=2.5,d=0.5,t_min=0,t_max=35,\
+             t_measured=24.75,oc=false,pwm=-12.5 1700000000000"
+        );
+
+        let mut unreadable = readout();
+        unreadable.t_measured = f32::NAN;
+        unreadable.pwm = f32::INFINITY;
+        assert_eq!(
+            line_protocol(&config, &unreadable, 1),
+            "tec,station=rig\\ 1,port=/dev/tty\\,USB0 t_set=25,p=5.5,i=2.5,d=0.5,t_min=0,t_max=35,oc=false 1"
+        );
+    }
+
+    /// Accept one request on `listener`, answer it with `status_line` and
+    /// return its header lines and body
+    fn serve_once(listener: TcpListener, status_line: &'static str) -> thread::JoinHandle<(Vec<String>, String)> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(reader.get_mut(), "{}\r\n\r\n", status_line).unwrap();
+            (head, String::from_utf8(body).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = serve_once(listener, "HTTP/1.1 204 No Content");
+
+        let mut config = InfluxConfig::new(&format!("http://127.0.0.1:{}/write?db=lab", port), "rig", "p").unwrap();
+        config.token = Some("secret".to_string());
+        let lines = vec!["tec a=1 1".to_string(), "tec a=2 2".to_string()];
+        write_batch(&config, &lines).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST /write?db=lab&precision=ms HTTP/1.1\r\n");
+        assert!(head.contains(&"Authorization: Token secret\r\n".to_string()));
+        assert_eq!(body, "tec a=1 1\ntec a=2 2");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        config.port = listener.local_addr().unwrap().port();
+        let server = serve_once(listener, "HTTP/1.1 400 Bad Request");
+        let err = write_batch(&config, &lines).unwrap_err();
+        assert!(err.contains("400"), "{}", err);
+        server.join().unwrap();
+    }
+}
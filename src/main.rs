@@ -1,36 +1,882 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::Path;
+use std::process;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+mod analysis;
 mod experiment;
 mod tec;
+mod time;
 mod fan;
 mod camera;
-use experiment::{Experiment, Parameters};
+mod monitor;
+mod history;
+mod manifest;
+mod replay;
+mod sim;
+mod tui;
+mod tune;
+#[cfg(feature = "influx")]
+mod influx;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(any(feature = "influx", feature = "mqtt"))]
+mod net;
+#[cfg(feature = "plot")]
+mod plot;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(test)]
+mod scratch;
+use experiment::{Experiment, ExperimentError, Parameters, TemperatureProfile};
+use monitor::{MIN_READ_INTERVAL, READ_INTERVAL};
 use tec::*;
 
-use crate::fan::Fan;
-fn main() -> std::io::Result<()> {
-    println!("Initializing TEC Controller...");
+use crate::fan::{DEFAULT_FAN_FREQUENCY_HZ, Fan};
 
+#[derive(Parser)]
+#[command(name = "station", about = "Thermoelectric cooler experiment station")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the full experiment and save its logs
+    Run(RunArgs),
+    /// Record video and temperatures without changing the TEC
+    Record(RecordArgs),
+    /// Watch and control the TEC in the terminal UI
+    Monitor(MonitorArgs),
+    /// Set the setpoint and PID coefficients from a prompt while readouts print
+    Tune(TuneArgs),
+    /// Summarize how well a finished experiment tracked its setpoint
+    Analyze(AnalyzeArgs),
+    /// Measure readout round-trip latency and throughput over the serial link,
+    /// leaving the TEC's supply and configuration as they are
+    Diagnose(DiagnoseArgs),
+    /// Control the TEC over HTTP and stream readouts over a WebSocket
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Serial port of a TEC driver; repeat to drive several in lockstep,
+    /// each labelled LABEL= or after its device name
+    #[arg(long = "port", value_name = "[LABEL=]PORT", default_value = DEFAULT_PORT, value_parser = parse_tec_port)]
+    ports: Vec<(String, String)>,
+    /// YAML parameters file; built-in defaults without one
+    #[arg(long, value_name = "FILE")]
+    params: Option<String>,
+    /// YAML temperature profile; defaults to the classic protocol
+    #[arg(long, value_name = "FILE")]
+    profile: Option<String>,
+    /// Run against the simulated TEC instead of the serial port
+    #[arg(long)]
+    simulate: bool,
+    /// Serve Prometheus gauges on http://<host>:<port>/metrics
+    #[arg(long)]
+    metrics_port: Option<u16>,
+    /// Run without the PWM fan; any fan_curve is ignored
+    #[arg(long)]
+    no_fan: bool,
+    /// Directory to create the experiment directory in, overriding output_dir
+    /// [default: experiments]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<String>,
+}
+
+#[derive(Args)]
+struct RecordArgs {
+    /// Serial port of a TEC driver; repeat to drive several in lockstep,
+    /// each labelled LABEL= or after its device name
+    #[arg(long = "port", value_name = "[LABEL=]PORT", default_value = DEFAULT_PORT, value_parser = parse_tec_port)]
+    ports: Vec<(String, String)>,
+    /// YAML parameters file, for the camera and log settings
+    #[arg(long, value_name = "FILE")]
+    params: Option<String>,
+    /// Stop after this many seconds instead of waiting for Enter
+    #[arg(long, value_name = "SECS", value_parser = parse_duration)]
+    duration: Option<Duration>,
+    /// Record from the simulated TEC instead of the serial port
+    #[arg(long)]
+    simulate: bool,
+    /// Directory to create the experiment directory in, overriding output_dir
+    /// [default: experiments]
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<String>,
+}
+
+#[derive(Args)]
+struct MonitorArgs {
+    /// Serial port of the TEC driver
+    #[arg(long, default_value = DEFAULT_PORT)]
+    port: String,
+    /// Poll interval in milliseconds [default: 500]
+    #[arg(long = "interval", value_name = "MS", value_parser = parse_interval)]
+    read_interval: Option<Duration>,
+    /// MQTT broker (host[:port]) to publish readouts to
+    #[arg(long, value_name = "HOST[:PORT]")]
+    mqtt: Option<String>,
+    /// Topic prefix for MQTT readouts and setpoints
+    #[arg(long, value_name = "PREFIX")]
+    mqtt_prefix: Option<String>,
+    /// InfluxDB write URL to store readouts at, e.g.
+    /// http://influx.local:8086/api/v2/write?org=lab&bucket=tec
+    #[arg(long, value_name = "URL")]
+    influx: Option<String>,
+    /// Value of the station tag on InfluxDB records
+    #[arg(long, value_name = "NAME", requires = "influx")]
+    influx_station: Option<String>,
+    /// Disable the TEC if PWM stays outside MIN:MAX percent, e.g. -100:0
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_pwm_limit, allow_hyphen_values = true)]
+    pwm_limit: Option<PwmLimit>,
+    /// Reject setpoints outside MIN:MAX °C [default: 0:50]
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_setpoint_limits, allow_hyphen_values = true)]
+    setpoint_limits: Option<SetpointBounds>,
+    /// Play back a recorded temperature_log.csv instead of opening the port
+    #[arg(long, value_name = "FILE", conflicts_with = "pwm_limit")]
+    replay: Option<String>,
+    /// Replay this many times faster than recorded
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0, value_parser = parse_speed, requires = "replay")]
+    speed: f64,
+    /// Distance from the setpoint, in °C, that counts as reached; also the
+    /// half-width of the chart's tolerance band
+    #[arg(long, value_name = "DEG", default_value_t = 0.5, value_parser = parse_tolerance)]
+    tolerance: f32,
+    /// Show a fan pane to set the PWM fan's speed by hand
+    #[arg(long)]
+    fan: bool,
+    /// Fan speed change per key press, in percent
+    #[arg(long, value_name = "PERCENT", default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=100), requires = "fan")]
+    fan_step: u8,
+    /// Reload the chart history from FILE on startup and save it there
+    /// every 10 s and on quit, so a restart keeps the plot
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    history_file: Option<String>,
+}
+
+#[derive(Args)]
+struct TuneArgs {
+    /// Serial port of the TEC driver
+    #[arg(long, default_value = DEFAULT_PORT)]
+    port: String,
+    /// Poll interval in milliseconds [default: 500]
+    #[arg(long = "interval", value_name = "MS", value_parser = parse_interval)]
+    read_interval: Option<Duration>,
+    /// Disable the TEC if PWM stays outside MIN:MAX percent, e.g. -100:0
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_pwm_limit, allow_hyphen_values = true)]
+    pwm_limit: Option<PwmLimit>,
+    /// Reject setpoints outside MIN:MAX °C [default: 0:50]
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_setpoint_limits, allow_hyphen_values = true)]
+    setpoint_limits: Option<SetpointBounds>,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    /// Experiment directory holding parameters.yaml and the temperature log
+    dir: String,
+}
+
+#[derive(Args)]
+struct DiagnoseArgs {
+    /// Serial port of the TEC driver
+    #[arg(long, default_value = DEFAULT_PORT)]
+    port: String,
+    /// Number of readouts to time
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..))]
+    samples: u32,
+    /// Also read this many lines of the cyclic print stream, one per second;
+    /// this switches the stream on, so not during a run
+    #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..))]
+    stream: Option<u32>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+struct ServeArgs {
+    /// Serial port of the TEC driver
+    #[arg(long, default_value = DEFAULT_PORT)]
+    port: String,
+    /// Poll interval in milliseconds [default: 500]
+    #[arg(long = "interval", value_name = "MS", value_parser = parse_interval)]
+    read_interval: Option<Duration>,
+    /// Address to listen on; the API has no authentication, so use 0.0.0.0
+    /// only on a trusted network
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: std::net::IpAddr,
+    /// Port for the HTTP and WebSocket API
+    #[arg(long, default_value_t = 8080)]
+    http_port: u16,
+    /// YAML parameters file for experiments started over HTTP, read at each
+    /// start; built-in defaults without one
+    #[arg(long, value_name = "FILE")]
+    params: Option<String>,
+    /// YAML temperature profile for experiments started over HTTP; defaults
+    /// to the classic protocol
+    #[arg(long, value_name = "FILE")]
+    profile: Option<String>,
+    /// Run experiments without the PWM fan
+    #[arg(long)]
+    no_fan: bool,
+    /// Disable the TEC if PWM stays outside MIN:MAX percent, e.g. -100:0
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_pwm_limit, allow_hyphen_values = true)]
+    pwm_limit: Option<PwmLimit>,
+    /// Reject setpoints outside MIN:MAX °C [default: 0:50]
+    #[arg(long, value_name = "MIN:MAX", value_parser = parse_setpoint_limits, allow_hyphen_values = true)]
+    setpoint_limits: Option<SetpointBounds>,
+}
+
+/// Poll interval from milliseconds, no shorter than `MIN_READ_INTERVAL`
+fn parse_interval(ms: &str) -> Result<Duration, String> {
+    let ms: u64 = ms.parse().map_err(|_| format!("invalid interval: {:?}", ms))?;
+    let interval = Duration::from_millis(ms);
+    if interval < MIN_READ_INTERVAL {
+        return Err(format!("must be at least {} ms", MIN_READ_INTERVAL.as_millis()));
+    }
+    Ok(interval)
+}
+
+/// `MIN:MAX` PWM percentages
+fn parse_pwm_limit(range: &str) -> Result<PwmLimit, String> {
+    let (min, max) = range.split_once(':').ok_or("expected MIN:MAX")?;
+    let percent = |value: &str| value.trim().parse::<f32>().map_err(|_| format!("invalid percentage: {:?}", value));
+    PwmLimit::new(percent(min)?, percent(max)?).map_err(|e| e.to_string())
+}
+
+/// `MIN:MAX` setpoint soft limits in °C
+fn parse_setpoint_limits(range: &str) -> Result<SetpointBounds, String> {
+    let (min, max) = range.split_once(':').ok_or("expected MIN:MAX")?;
+    let celsius = |value: &str| value.trim().parse::<f32>().map_err(|_| format!("invalid temperature: {:?}", value));
+    SetpointBounds::new(celsius(min)?, celsius(max)?).map_err(|e| e.to_string())
+}
+
+/// `[LABEL=]PORT` as (label, port); the label defaults to the device name
+/// and ends up in log file names
+fn parse_tec_port(spec: &str) -> Result<(String, String), String> {
+    let (label, port) = match spec.split_once('=') {
+        Some((label, port)) => (label.to_string(), port.to_string()),
+        None => {
+            let name = Path::new(spec).file_name().and_then(|name| name.to_str()).unwrap_or(spec);
+            (name.to_string(), spec.to_string())
+        }
+    };
+    if port.is_empty() {
+        return Err("expected a serial port".to_string());
+    }
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("invalid label {:?}: use letters, digits, - and _", label));
+    }
+    Ok((label, port))
+}
+
+/// Positive number of seconds
+fn parse_duration(secs: &str) -> Result<Duration, String> {
+    match secs.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("invalid duration: {:?}", secs)),
+    }
+}
+
+/// Positive, finite temperature tolerance in °C
+fn parse_tolerance(degrees: &str) -> Result<f32, String> {
+    match degrees.parse::<f32>() {
+        Ok(degrees) if degrees > 0.0 && degrees.is_finite() => Ok(degrees),
+        _ => Err(format!("invalid tolerance: {:?}", degrees)),
+    }
+}
+
+/// Positive, finite playback speed factor
+fn parse_speed(factor: &str) -> Result<f64, String> {
+    match factor.parse::<f64>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(factor),
+        _ => Err(format!("invalid speed: {:?}", factor)),
+    }
+}
+
+/// What to do once the experiment hardware is up
+enum Task {
+    /// The classic protocol, or the given profile
+    Protocol(Option<TemperatureProfile>),
+    /// Record until the duration elapses or Enter is pressed
+    Record(Option<Duration>),
+}
+
+fn main() {
     env_logger::init();
-    // Initialize TEC controller - adjust port name as needed (/dev/ttyUSB0, /dev/ttyACM0, etc.)
-    let tec_controller = match TecController::new("/dev/serial0") {
-        Ok(controller) => Arc::new(Mutex::new(controller)),
-        Err(e) => {
-            eprintln!("Failed to initialize TEC controller: {}", e);
-            return Ok(());
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Run(args) => run(args),
+        Command::Record(args) => record(args),
+        Command::Monitor(args) => {
+            if args.mqtt.is_some() && !cfg!(feature = "mqtt") {
+                eprintln!("--mqtt requires building with --features mqtt");
+                process::exit(2);
+            }
+            if args.influx.is_some() && !cfg!(feature = "influx") {
+                eprintln!("--influx requires building with --features influx");
+                process::exit(2);
+            }
+            tui::run(&tui::TuiOptions {
+                port_name: args.port,
+                read_interval: args.read_interval.unwrap_or(READ_INTERVAL),
+                mqtt_broker: args.mqtt,
+                mqtt_prefix: args.mqtt_prefix,
+                influx_url: args.influx,
+                influx_station: args.influx_station,
+                pwm_limit: args.pwm_limit,
+                setpoint_bounds: args.setpoint_limits,
+                replay: args.replay,
+                replay_speed: args.speed,
+                temp_tolerance: args.tolerance,
+                fan_step: args.fan.then_some(args.fan_step),
+                history_file: args.history_file,
+            })
+        }
+        Command::Tune(args) => tune::run(
+            &args.port,
+            args.read_interval.unwrap_or(READ_INTERVAL),
+            args.pwm_limit,
+            args.setpoint_limits,
+        ),
+        Command::Analyze(args) => analyze(args),
+        Command::Diagnose(args) => diagnose(args),
+        #[cfg(feature = "server")]
+        Command::Serve(args) => serve(args),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
+    experiment::install_abort_handler()
+        .map_err(|e| format!("Failed to install Ctrl-C handler: {}", e))?;
+
+    if args.metrics_port.is_some() && !cfg!(feature = "metrics") {
+        return Err("--metrics-port requires building with --features metrics".into());
+    }
+
+    let mut params = load_parameters(args.params.as_deref())?;
+    if args.output_dir.is_some() {
+        params.output_dir = args.output_dir;
+    }
+    let profile = load_profile(args.profile.as_deref())?;
+
+    let task = Task::Protocol(profile);
+    start_experiment(&args.ports, args.simulate, !args.no_fan, params, task, args.metrics_port)
+}
+
+fn record(args: RecordArgs) -> Result<(), Box<dyn std::error::Error>> {
+    experiment::install_abort_handler()
+        .map_err(|e| format!("Failed to install Ctrl-C handler: {}", e))?;
+    let mut params = load_parameters(args.params.as_deref())?;
+    if args.output_dir.is_some() {
+        params.output_dir = args.output_dir;
+    }
+    start_experiment(&args.ports, args.simulate, false, params, Task::Record(args.duration), None)
+}
+
+/// Print tracking statistics for the log in an experiment directory
+fn analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new(&args.dir);
+    if !dir.is_dir() {
+        return Err(format!("{} is not an experiment directory", args.dir).into());
+    }
+    let params_path = dir.join("parameters.yaml").to_string_lossy().to_string();
+    let params = Parameters::from_yaml_file(&params_path)
+        .map_err(|e| format!("Failed to load parameters {}: {}", params_path, e))?;
+    let mut log_paths = vec![dir.join(params.log_format.file_name())];
+    if !log_paths[0].exists() {
+        // An experiment with several controllers logs each one separately
+        let labelled = params.log_format.labelled_file_name("");
+        let (prefix, suffix) = labelled.split_at(labelled.rfind('.').unwrap_or(labelled.len()));
+        let mut found: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(prefix) && name.ends_with(suffix))
+            })
+            .collect();
+        if !found.is_empty() {
+            found.sort();
+            log_paths = found;
+        }
+    }
+
+    for log_path in log_paths {
+        let log_path = log_path.to_string_lossy().to_string();
+        let samples = analysis::read_log(&log_path, params.log_format)
+            .map_err(|e| format!("Failed to read temperature log {}: {}", log_path, e))?;
+        let summary = analysis::summarize(&samples, params.temperature_tolerance)
+            .ok_or_else(|| format!("{} has no readouts", log_path))?;
+
+        println!("{}", log_path);
+        println!("  Samples:             {}", summary.samples);
+        println!("  RMS tracking error:  {:.3} °C", summary.rms_error);
+        println!("  Max deviation:       {:.3} °C", summary.max_deviation);
+        println!(
+            "  {:<20} {:.1}%",
+            format!("Within ±{} °C:", params.temperature_tolerance),
+            summary.within_tolerance_percent
+        );
+    }
+    Ok(())
+}
+
+/// Time back-to-back readouts and print the link's latency and throughput,
+/// then the spread of the measured temperature over a second batch and,
+/// with `--stream`, how reliably the cyclic print stream arrives
+fn diagnose(args: DiagnoseArgs) -> Result<(), Box<dyn std::error::Error>> {
+    // Polled readouts change nothing, so a run in progress carries on
+    // undisturbed; only `--stream` alters what the device sends
+    let mut tec = TecController::attach_with_retry(&args.port, DEFAULT_OPEN_ATTEMPTS, DEFAULT_OPEN_BACKOFF)
+        .map_err(|e| format!("Failed to connect to TEC controller on {}: {}", args.port, e))?;
+    println!("Timing {} readouts on {}...", args.samples, args.port);
+    let stats = tec.benchmark(args.samples as usize)?;
+
+    let ms = |latency: Duration| latency.as_secs_f64() * 1000.0;
+    println!("  Failed readouts:     {}/{}", stats.failures, stats.samples);
+    println!("  Latency min:         {:.1} ms", ms(stats.min));
+    println!("  Latency median:      {:.1} ms", ms(stats.median));
+    println!("  Latency max:         {:.1} ms", ms(stats.max));
+    println!("  Latency mean:        {:.1} ms", ms(stats.mean));
+    println!("  Throughput:          {:.1} readouts/s", stats.readouts_per_sec);
+
+    // A batch stops at its first failure, which the counts above already cover
+    match tec.get_readouts(args.samples as usize) {
+        Ok(readouts) => {
+            let temps: Vec<f32> = readouts.iter().map(|readout| readout.t_measured).collect();
+            let min = temps.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = temps.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let mean = temps.iter().sum::<f32>() / temps.len() as f32;
+            let variance = temps.iter().map(|t| (t - mean).powi(2)).sum::<f32>() / temps.len() as f32;
+            println!("  Temperature min/max: {:.2} / {:.2} °C", min, max);
+            println!("  Temperature σ:       {:.3} °C", variance.sqrt());
+        }
+        Err(e) => eprintln!("Warning: temperature noise batch failed: {}", e),
+    }
+
+    if let Some(count) = args.stream {
+        println!("Streaming {} readouts on {}...", count, args.port);
+        let tec = Arc::new(Mutex::new(tec));
+        // Stops the stream even if reading panics; the lock below is
+        // released before the guard takes it again
+        let stream = TecController::cyclic_print_scoped(&tec)?;
+        let start = std::time::Instant::now();
+        let failures = tec
+            .lock()
+            .unwrap()
+            .cyclic_readouts()
+            .take(count as usize)
+            .filter(|result| result.is_err())
+            .count();
+        let elapsed = start.elapsed();
+        drop(stream);
+        println!("  Failed stream lines: {}/{}", failures, count);
+        println!(
+            "  Stream interval:     {:.1} ms (device period {} ms)",
+            elapsed.as_secs_f64() * 1000.0 / count as f64,
+            CYCLIC_PRINT_PERIOD.as_millis()
+        );
+    }
+    Ok(())
+}
+
+fn load_parameters(path: Option<&str>) -> Result<Parameters, String> {
+    match path {
+        Some(path) => Parameters::from_yaml_file(path)
+            .map_err(|e| format!("Failed to load parameters {}: {}", path, e)),
+        None => Ok(Parameters::default()),
+    }
+}
+
+fn load_profile(path: Option<&str>) -> Result<Option<TemperatureProfile>, String> {
+    path.map(|path| {
+        TemperatureProfile::from_yaml_file(path)
+            .map_err(|e| format!("Failed to load temperature profile {}: {}", path, e))
+    })
+    .transpose()
+}
+
+fn start_experiment(
+    ports: &[(String, String)],
+    simulate: bool,
+    with_fan: bool,
+    params: Parameters,
+    task: Task,
+    metrics_port: Option<u16>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let recording = matches!(task, Task::Record(_));
+    let result = if simulate {
+        println!("Initializing simulated hardware...");
+        match Experiment::new_simulated(params) {
+            Ok(mut experiment) => run_experiment(&mut experiment, task, metrics_port),
+            Err(e) => Err(e),
+        }
+    } else {
+        let mut tecs = Vec::with_capacity(ports.len());
+        for (label, port) in ports {
+            if tecs.iter().any(|(existing, _)| existing == label) {
+                return Err(format!("TEC label {:?} is used more than once", label).into());
+            }
+            // Recording observes whatever the TEC is doing, so it must not
+            // reset the supply or configuration
+            let tec_controller = if recording {
+                println!("Connecting to TEC Controller on {}...", port);
+                TecController::attach_with_retry(port, DEFAULT_OPEN_ATTEMPTS, DEFAULT_OPEN_BACKOFF)
+                    .map_err(|e| format!("Failed to connect to TEC controller on {}: {}", port, e))?
+            } else {
+                println!("Initializing TEC Controller on {}...", port);
+                TecController::new_with_retry(port, DEFAULT_OPEN_ATTEMPTS, DEFAULT_OPEN_BACKOFF)
+                    .map_err(|e| format!("Failed to initialize TEC controller on {}: {}", port, e))?
+            };
+            tecs.push((label.clone(), Arc::new(Mutex::new(tec_controller))));
         }
+        let fan = with_fan.then(|| Fan::new(0, 0, DEFAULT_FAN_FREQUENCY_HZ).unwrap());
+
+        // Create and run experiment
+        let mut experiment = Experiment::with_controllers(tecs, fan, params);
+        run_experiment(&mut experiment, task, metrics_port)
     };
 
-    // Set up experiment parameters
-    let params = Parameters::default(); 
-    let fan = Fan::new(0,0,25_000).unwrap();
+    match result {
+        Ok(()) => {
+            println!("Experiment completed successfully!");
+            Ok(())
+        }
+        Err(e) if matches!(e.downcast_ref::<ExperimentError>(), Some(ExperimentError::Aborted)) => {
+            Err(if recording {
+                "Recording aborted; partial results saved".into()
+            } else {
+                "Experiment aborted; TEC disabled and partial results saved".into()
+            })
+        }
+        Err(e) => Err(format!("Experiment failed: {}", e).into()),
+    }
+}
+
+/// Run the control server until SIGINT or SIGTERM, then disable the TEC.
+///
+/// Between experiments a monitor owns the serial port. Starting one over
+/// HTTP shuts the monitor down and runs the experiment on its own thread,
+/// forwarding its readouts to the server; a fresh monitor takes the port
+/// back once it ends.
+#[cfg(feature = "server")]
+fn serve(args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    use server::{ExperimentRequest, ExperimentState};
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    experiment::install_abort_handler()?;
+    let read_interval = args.read_interval.unwrap_or(READ_INTERVAL);
+    let connect = || {
+        let monitor = monitor::TecMonitor::connect(&args.port, read_interval);
+        if let Some(limit) = args.pwm_limit {
+            monitor.set_pwm_limit(limit, monitor::DEFAULT_PWM_GRACE);
+        }
+        if let Some(bounds) = args.setpoint_limits {
+            monitor.set_setpoint_bounds(bounds);
+        }
+        let readouts = monitor.subscribe();
+        (monitor, readouts)
+    };
 
-    // Create and run experiment
-    let mut experiment = Experiment::new(tec_controller, fan, params);
+    let (readout_tx, readout_rx) = mpsc::channel();
+    let (command_tx, command_rx) = mpsc::channel();
+    let (experiment_tx, experiment_rx) = mpsc::channel();
+    let state = Arc::new(Mutex::new(ExperimentState::Idle));
+    let control = server::Control {
+        commands: command_tx,
+        experiments: experiment_tx,
+        experiment: Arc::clone(&state),
+    };
+    server::serve(args.bind, args.http_port, readout_rx, control)?;
 
-    match experiment.run() {
-        Ok(()) => println!("Experiment completed successfully!"),
-        Err(e) => eprintln!("Experiment failed: {}", e),
+    let mut monitor = Some(connect());
+    let mut running: Option<ServedExperiment> = None;
+    let mut stop_requested = false;
+    let mut last_forward = Instant::now();
+    // An abort from a stop request ends the experiment, not the server
+    while experiment::check_abort().is_ok() || stop_requested {
+        if let Some((monitor, readouts)) = &monitor {
+            for readout in readouts.try_iter() {
+                let _ = readout_tx.send(readout);
+            }
+            for command in command_rx.try_iter() {
+                monitor.send(command);
+            }
+            while let Some(event) = monitor.try_recv_event() {
+                match event {
+                    monitor::MonitorEvent::Readout(_) | monitor::MonitorEvent::Config(_) => {}
+                    monitor::MonitorEvent::Error(e) => eprintln!("{}", e),
+                    monitor::MonitorEvent::Status(status) => println!("{}", status),
+                    monitor::MonitorEvent::Connected => println!("Connected to {}", args.port),
+                    monitor::MonitorEvent::Disconnected(reason) => eprintln!("Disconnected: {}", reason),
+                    monitor::MonitorEvent::PortChanged(port) => println!("Switched to {}", port),
+                }
+            }
+        } else if command_rx.try_iter().count() > 0 {
+            eprintln!("An experiment is running; TEC commands dropped");
+        }
+
+        if let Some(served) = &running {
+            if last_forward.elapsed() >= read_interval {
+                if let Some(readout) = served.latest_readout.lock().unwrap().clone() {
+                    let _ = readout_tx.send(readout);
+                }
+                last_forward = Instant::now();
+            }
+            if served.handle.is_finished() {
+                let outcome = running.take().unwrap().handle.join();
+                *state.lock().unwrap() = match outcome {
+                    Ok(Ok(())) => ExperimentState::Completed,
+                    Ok(Err(ExperimentError::Aborted)) => ExperimentState::Aborted,
+                    Ok(Err(e)) => ExperimentState::Failed(e.to_string()),
+                    Err(_) => ExperimentState::Failed("experiment thread panicked".to_string()),
+                };
+                println!("Experiment finished: {:?}", state.lock().unwrap());
+                if stop_requested {
+                    experiment::clear_abort();
+                    stop_requested = false;
+                }
+                monitor = Some(connect());
+            }
+        }
+
+        for request in experiment_rx.try_iter() {
+            match request {
+                ExperimentRequest::Start if running.is_none() => {
+                    // Release the port before the experiment opens it
+                    drop(monitor.take());
+                    match start_served_experiment(&args) {
+                        Ok(served) => {
+                            println!("Experiment started over HTTP");
+                            running = Some(served);
+                            *state.lock().unwrap() = ExperimentState::Running;
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to start experiment: {}", e);
+                            *state.lock().unwrap() = ExperimentState::Failed(e.to_string());
+                            monitor = Some(connect());
+                        }
+                    }
+                }
+                ExperimentRequest::Stop if running.is_some() => {
+                    println!("Stopping experiment...");
+                    stop_requested = true;
+                    experiment::abort_experiment();
+                }
+                _ => {}
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    if let Some(served) = running {
+        // The abort flag is set, so the experiment disables the TEC itself
+        println!("Shutting down; stopping the experiment...");
+        let _ = served.handle.join();
+    } else if let Some((mut monitor, _)) = monitor {
+        println!("Shutting down; disabling TEC...");
+        monitor.send(monitor::TecCommand::Disable);
+        monitor.shutdown();
+    }
+    Ok(())
+}
+
+/// Experiment started over HTTP, running on its own thread
+#[cfg(feature = "server")]
+struct ServedExperiment {
+    handle: std::thread::JoinHandle<Result<(), ExperimentError>>,
+    latest_readout: Arc<Mutex<Option<TecReadout>>>,
+}
+
+/// Open the TEC and start the `serve` parameters and profile on a thread
+#[cfg(feature = "server")]
+fn start_served_experiment(args: &ServeArgs) -> Result<ServedExperiment, Box<dyn std::error::Error>> {
+    let params = load_parameters(args.params.as_deref())?;
+    let profile = load_profile(args.profile.as_deref())?;
+    let tec_controller = TecController::new_with_retry(&args.port, DEFAULT_OPEN_ATTEMPTS, DEFAULT_OPEN_BACKOFF)
+        .map_err(|e| format!("Failed to initialize TEC controller on {}: {}", args.port, e))?;
+    let fan = (!args.no_fan)
+        .then(|| Fan::new(0, 0, DEFAULT_FAN_FREQUENCY_HZ))
+        .transpose()
+        .map_err(|e| format!("Failed to open the fan: {}", e))?;
+
+    let mut experiment = Experiment::new(Arc::new(Mutex::new(tec_controller)), fan, params);
+    let latest_readout = experiment.latest_readout();
+    let handle = std::thread::spawn(move || match profile {
+        Some(profile) => experiment.run_profile(profile),
+        None => experiment.run(),
+    });
+    Ok(ServedExperiment { handle, latest_readout })
+}
+
+fn run_experiment<P: SerialLink + 'static>(
+    experiment: &mut Experiment<P>,
+    task: Task,
+    metrics_port: Option<u16>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "metrics")]
+    if let Some(port) = metrics_port {
+        metrics::serve(port, experiment.latest_readout())?;
     }
+    #[cfg(not(feature = "metrics"))]
+    let _ = metrics_port;
 
+    match task {
+        Task::Protocol(Some(profile)) => experiment.run_profile(profile)?,
+        Task::Protocol(None) => experiment.run()?,
+        Task::Record(duration) => experiment.record(duration)?,
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli() {
+        let Command::Monitor(args) = Cli::try_parse_from(["station", "monitor"]).unwrap().command else {
+            panic!("expected monitor");
+        };
+        assert_eq!(args.port, DEFAULT_PORT);
+        assert_eq!(args.read_interval, None);
+        assert_eq!(args.mqtt, None);
+
+        let cli = Cli::try_parse_from([
+            "station", "monitor", "--port", "/dev/ttyACM0", "--mqtt-prefix", "lab/tec", "--interval", "200",
+        ])
+        .unwrap();
+        let Command::Monitor(args) = cli.command else {
+            panic!("expected monitor");
+        };
+        assert_eq!(args.port, "/dev/ttyACM0");
+        assert_eq!(args.read_interval, Some(Duration::from_millis(200)));
+        assert_eq!(args.mqtt_prefix.as_deref(), Some("lab/tec"));
+        assert!(!args.fan);
+        assert_eq!(args.tolerance, 0.5);
+        assert!(Cli::try_parse_from(["station", "monitor", "--tolerance", "0"]).is_err());
+
+        let Command::Monitor(args) = Cli::try_parse_from(["station", "monitor", "--fan"]).unwrap().command else {
+            panic!("expected monitor");
+        };
+        assert!(args.fan);
+        assert_eq!(args.fan_step, 10);
+        assert!(Cli::try_parse_from(["station", "monitor", "--fan-step", "5"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--fan", "--fan-step", "0"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--history-file", "h.csv", "--replay", "log.csv"]).is_err());
+
+        let Command::Tune(args) = Cli::try_parse_from(["station", "tune", "--pwm-limit", "-100:0"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected tune");
+        };
+        assert_eq!(args.pwm_limit, Some(PwmLimit::new(-100.0, 0.0).unwrap()));
+        assert!(Cli::try_parse_from(["station", "monitor", "--pwm-limit", "0:-100"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--pwm-limit", "50"]).is_err());
+
+        let Command::Tune(args) = Cli::try_parse_from(["station", "tune", "--setpoint-limits", "-10:80"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected tune");
+        };
+        assert_eq!(args.setpoint_limits, Some(SetpointBounds::new(-10.0, 80.0).unwrap()));
+        assert!(Cli::try_parse_from(["station", "monitor", "--setpoint-limits", "80:-10"]).is_err());
+
+        let Command::Run(args) = Cli::try_parse_from(["station", "run", "--params", "params.yaml", "--simulate"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected run");
+        };
+        assert_eq!(args.params.as_deref(), Some("params.yaml"));
+        assert!(args.simulate);
+        assert_eq!(args.ports, [("serial0".to_string(), DEFAULT_PORT.to_string())]);
+        assert_eq!(args.output_dir, None);
+        let Command::Record(args) = Cli::try_parse_from(["station", "record", "--output-dir", "~/usb/runs"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected record");
+        };
+        assert_eq!(args.output_dir.as_deref(), Some("~/usb/runs"));
+
+        let Command::Run(args) = Cli::try_parse_from([
+            "station", "run", "--port", "left=/dev/ttyUSB0", "--port", "/dev/ttyUSB1",
+        ])
+        .unwrap()
+        .command
+        else {
+            panic!("expected run");
+        };
+        assert_eq!(
+            args.ports,
+            [
+                ("left".to_string(), "/dev/ttyUSB0".to_string()),
+                ("ttyUSB1".to_string(), "/dev/ttyUSB1".to_string())
+            ]
+        );
+        assert!(Cli::try_parse_from(["station", "run", "--port", "a/b=/dev/ttyUSB0"]).is_err());
+        assert!(Cli::try_parse_from(["station", "record", "--port", "left="]).is_err());
+
+        assert!(Cli::try_parse_from(["station", "monitor", "--interval", "20"]).is_err());
+        assert!(Cli::try_parse_from(["station", "tune", "--interval", "fast"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--mqtt"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--influx-station", "rig1"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--verbose"]).is_err());
+        let Command::Record(args) = Cli::try_parse_from(["station", "record", "--duration", "2.5"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected record");
+        };
+        assert_eq!(args.duration, Some(Duration::from_millis(2500)));
+        assert!(Cli::try_parse_from(["station", "record", "--duration", "0"]).is_err());
+
+        let Command::Monitor(args) = Cli::try_parse_from(["station", "monitor", "--replay", "log.csv", "--speed", "10"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected monitor");
+        };
+        assert_eq!(args.replay.as_deref(), Some("log.csv"));
+        assert_eq!(args.speed, 10.0);
+        assert!(Cli::try_parse_from(["station", "monitor", "--speed", "10"]).is_err());
+        assert!(Cli::try_parse_from(["station", "monitor", "--replay", "log.csv", "--speed", "0"]).is_err());
+
+        let Command::Analyze(args) = Cli::try_parse_from(["station", "analyze", "runs/2024"]).unwrap().command else {
+            panic!("expected analyze");
+        };
+        assert_eq!(args.dir, "runs/2024");
+        assert!(Cli::try_parse_from(["station", "analyze"]).is_err());
+
+        let Command::Diagnose(args) = Cli::try_parse_from(["station", "diagnose", "--samples", "200"]).unwrap().command
+        else {
+            panic!("expected diagnose");
+        };
+        assert_eq!(args.samples, 200);
+        assert_eq!(args.stream, None);
+        assert!(Cli::try_parse_from(["station", "diagnose", "--samples", "0"]).is_err());
+        let Command::Diagnose(args) = Cli::try_parse_from(["station", "diagnose", "--stream", "10"]).unwrap().command
+        else {
+            panic!("expected diagnose");
+        };
+        assert_eq!(args.stream, Some(10));
+        assert!(Cli::try_parse_from(["station", "diagnose", "--stream", "0"]).is_err());
+
+        assert!(Cli::try_parse_from(["station"]).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::camera::CameraConfig;
+use crate::experiment::{Parameters, PhaseTiming, TemperatureProfile};
+use crate::tec::TecConfig;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Name of the manifest in the experiment directory
+pub const MANIFEST_FILE: &str = "run_manifest.yaml";
+
+/// One run in a single file: what was asked for, what the hardware was set
+/// to, when each phase ran and which video it produced. The separate YAML
+/// files are still written next to it.
+#[derive(Serialize)]
+pub struct RunManifest<'a> {
+    pub directory: &'a str,
+    pub parameters: &'a Parameters,
+    pub profile: &'a TemperatureProfile,
+    /// Configuration of each controller at the end of the run, by label
+    pub tec_configs: BTreeMap<&'a str, TecConfig>,
+    pub camera: &'a CameraConfig,
+    pub phase_timings: &'a [PhaseTiming],
+    /// None if the camera left no video
+    pub video: Option<VideoFile>,
+}
+
+/// Video of a run, identified by its contents
+#[derive(Serialize, Debug, PartialEq)]
+pub struct VideoFile {
+    /// File name within the experiment directory
+    pub path: String,
+    pub bytes: u64,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: String,
+}
+
+impl VideoFile {
+    /// Size and hash of the video at `path`, or None if there is no file
+    pub fn hash(path: &Path) -> io::Result<Option<VideoFile>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let (bytes, sha256) = sha256_hex(file)?;
+        let path = path
+            .file_name()
+            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+            .to_string();
+        Ok(Some(VideoFile { path, bytes, sha256 }))
+    }
+}
+
+/// Byte count and lowercase hex SHA-256 of everything `reader` yields,
+/// read in chunks so a long video never has to fit in memory
+fn sha256_hex(mut reader: impl Read) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+    let hex = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok((bytes, hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(&b"abc"[..]).unwrap(),
+            (3, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string())
+        );
+        // Larger than one read
+        let (bytes, _) = sha256_hex(&vec![7u8; 200_000][..]).unwrap();
+        assert_eq!(bytes, 200_000);
+    }
+
+    #[test]
+    fn test_video_file_hash() {
+        let scratch = ScratchDir::new("manifest");
+        let path = scratch.path().join("video.h264");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let video = VideoFile::hash(&path).unwrap();
+        let missing = VideoFile::hash(&scratch.path().join("missing.h264")).unwrap();
+
+        assert_eq!(
+            video,
+            Some(VideoFile {
+                path: "video.h264".to_string(),
+                bytes: 3,
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            })
+        );
+        assert_eq!(missing, None);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::tec::TecReadout;
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Response, Server};
+
+/// Serve the latest readout in the Prometheus text format on `/metrics`.
+///
+/// `latest` is the same cache the temperature logger fills on each poll, so
+/// scrapes never touch the serial port.
+pub fn serve(
+    port: u16,
+    latest: Arc<Mutex<Option<TecReadout>>>,
+) -> io::Result<thread::JoinHandle<()>> {
+    let server = Server::http(("0.0.0.0", port)).map_err(io::Error::other)?;
+    println!("Serving metrics on http://0.0.0.0:{}/metrics", port);
+
+    Ok(thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                let body = render(latest.lock().unwrap().as_ref());
+                let content_type =
+                    Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+                Response::from_string(body).with_header(content_type)
+            } else {
+                Response::from_string("Not found\n").with_status_code(404)
+            };
+            if let Err(e) = request.respond(response) {
+                eprintln!("Failed to answer metrics request: {}", e);
+            }
+        }
+    }))
+}
+
+/// Gauges for one readout; only `tec_up` is reported before the first poll
+pub fn render(readout: Option<&TecReadout>) -> String {
+    let mut out = String::new();
+    gauge(&mut out, "tec_up", "Whether a readout has been received", readout.is_some() as u8 as f32);
+
+    if let Some(r) = readout {
+        gauge(&mut out, "tec_t_set", "Temperature setpoint in degrees Celsius", r.t_set);
+        gauge(&mut out, "tec_t_measured", "Measured temperature in degrees Celsius", r.t_measured);
+        gauge(&mut out, "tec_pwm", "Drive in percent, negative when cooling", r.pwm);
+        gauge(&mut out, "tec_oc", "Overcurrent flag", r.oc as u8 as f32);
+        gauge(&mut out, "tec_p", "Proportional coefficient", r.p);
+        gauge(&mut out, "tec_i", "Integral coefficient", r.i);
+        gauge(&mut out, "tec_d", "Derivative coefficient", r.d);
+    }
+    out
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f32) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let readout = TecReadout {
+            t_set: 25.0,
+            p: 5.5,
+            i: 2.5,
+            d: 0.5,
+            t_min: 0.0,
+            t_max: 35.0,
+            t_measured: 24.75,
+            oc: false,
+            pwm: -12.0,
+            resync_count: 0,
+            t_measured_device: None,
+        };
+        let text = render(Some(&readout));
+
+        assert!(text.contains("# TYPE tec_t_measured gauge\ntec_t_measured 24.75\n"));
+        assert!(text.contains("\ntec_pwm -12\n"));
+        assert!(text.contains("\ntec_oc 0\n"));
+        assert!(text.contains("\ntec_up 1\n"));
+        assert_eq!(render(None).lines().last(), Some("tec_up 0"));
+    }
+}
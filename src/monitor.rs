@@ -0,0 +1,975 @@
+use crate::replay::ReplaySource;
+use crate::tec::{
+    CYCLIC_PRINT_PERIOD, PwmLimit, SerialLink, SetpointBounds, TecConfig, TecController, TecError,
+    TecReadout,
+};
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Consecutive failed reads before the link is treated as lost
+const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+
+/// How long the cyclic print stream may go without a readout before a
+/// timed-out read counts toward `MAX_CONSECUTIVE_ERRORS`; the device prints
+/// once per `CYCLIC_PRINT_PERIOD` but a line can run late
+const STREAM_STALL_LIMIT: Duration = Duration::from_secs(3);
+
+/// Delay between attempts to reopen a lost port
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long PWM may stay outside a `PwmLimit` before the TEC is disabled
+pub const DEFAULT_PWM_GRACE: Duration = Duration::from_secs(2);
+
+/// How often the monitor thread polls the controller by default
+pub const READ_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shortest poll interval accepted on the command line
+pub const MIN_READ_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time between setpoint steps of `TecCommand::RampTo`
+const RAMP_STEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Commands sent to the monitor thread, executed in order; everything queued
+/// runs before the next readout is taken
+pub enum MonitorCommand {
+    /// Change or query the controller
+    Tec(TecCommand),
+    /// Read the controller's cyclic print stream instead of polling with `o`
+    SetStreaming(bool),
+    /// Move to another port, or reopen the current one with None, keeping the
+    /// configuration and supply state; see `TecController::change_port`.
+    /// While disconnected this retargets the next reconnect attempt instead.
+    ChangePort(Option<String>),
+    Shutdown,
+}
+
+/// Commands run against the controller, as opposed to those that steer the
+/// monitor loop itself
+pub enum TecCommand {
+    SetConfig(TecConfig),
+    #[cfg_attr(
+        not(any(feature = "mqtt", feature = "server")),
+        allow(dead_code, reason = "sent by the MQTT and HTTP front ends")
+    )]
+    SetT(f32),
+    /// `TecController::set_pid`, holding the setpoint and limits
+    SetPid { p: f32, i: f32, d: f32 },
+    Enable,
+    Disable,
+    /// `TecController::clear_integrator`, to undo integral windup
+    ClearIntegrator,
+    /// `TecController::read_config`, answered with `MonitorEvent::Config`
+    ReadConfig,
+    /// `TecController::ramp_to`, publishing the readout after every step;
+    /// later commands wait until the ramp is done
+    RampTo { target: f32, rate_c_per_s: f32 },
+}
+
+impl From<TecCommand> for MonitorCommand {
+    fn from(command: TecCommand) -> Self {
+        MonitorCommand::Tec(command)
+    }
+}
+
+/// Events published by the monitor thread
+pub enum MonitorEvent {
+    Readout(TecReadout),
+    Error(String),
+    Status(String),
+    /// Configuration read back from the controller
+    Config(TecConfig),
+    /// The port is open and being polled; the controller starts disabled
+    /// with the default configuration
+    Connected,
+    /// The port could not be opened or stopped responding; a reconnect is
+    /// attempted every `RECONNECT_INTERVAL`
+    Disconnected(String),
+    /// `MonitorCommand::ChangePort` reopened the controller on this port or,
+    /// while disconnected, made it the one the next reconnect tries
+    PortChanged(String),
+}
+
+/// State written by the monitor thread or read by it between polls
+#[derive(Default)]
+struct Shared {
+    latest: RwLock<Option<TecReadout>>,
+    /// `TecController::is_enabled` after the last command; false while disconnected
+    enabled: AtomicBool,
+    /// Readouts come from the cyclic print stream rather than polling
+    streaming: AtomicBool,
+    /// PWM range enforced on readouts, with how long it may be exceeded
+    pwm_limit: Mutex<Option<(PwmLimit, Duration)>>,
+    /// Soft setpoint limits handed to every controller before a command
+    setpoint_bounds: Mutex<SetpointBounds>,
+    /// Extra consumers of every readout, dropped once their receiver is gone
+    subscribers: Mutex<Vec<Sender<TecReadout>>>,
+    /// Port the monitor thread opens and reopens; None when its link cannot
+    /// be reopened
+    port_name: Mutex<Option<String>>,
+}
+
+/// Reopen a connected controller, on a new port if given; returns the port
+/// now open
+type Reopen<P> = fn(&mut TecController<P>, Option<&str>) -> Result<String, TecError>;
+
+/// `Reopen` for controllers on a real serial port
+fn reopen_port(controller: &mut TecController, new_port: Option<&str>) -> Result<String, TecError> {
+    match new_port {
+        Some(new_port) => controller.change_port(new_port)?,
+        None => controller.reconnect()?,
+    }
+    Ok(controller.port_name().unwrap_or_default().to_string())
+}
+
+/// `Reopen` for links handed over ready-made, which cannot be reopened
+fn cannot_reopen<P: SerialLink>(_: &mut TecController<P>, _: Option<&str>) -> Result<String, TecError> {
+    Err(TecError::InvalidArgument("link cannot be reopened".to_string()))
+}
+
+/// Cloneable handle for subscribing to a monitor's readouts; see
+/// `TecMonitor::subscriptions`
+#[derive(Clone)]
+pub struct Subscriptions {
+    shared: Arc<Shared>,
+}
+
+impl Subscriptions {
+    /// Receive a copy of every readout from now on
+    pub fn subscribe(&self) -> Receiver<TecReadout> {
+        let (tx, rx) = mpsc::channel();
+        self.shared.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+}
+
+/// Why `monitor_loop` returned
+enum LoopExit {
+    Shutdown,
+    ConnectionLost(String),
+}
+
+/// Owns a `TecController` on a dedicated thread and polls it at a fixed
+/// interval, or shares one with `watch`.
+///
+/// Consumers read the cached readout or drain events without ever touching the
+/// serial port themselves; changes go through the command channel.
+pub struct TecMonitor {
+    command_tx: Sender<MonitorCommand>,
+    event_rx: Receiver<MonitorEvent>,
+    shared: Arc<Shared>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl TecMonitor {
+    /// Take ownership of an open controller and start polling it.
+    ///
+    /// The link cannot be reopened, so once lost the monitor only reports
+    /// `MonitorEvent::Disconnected`.
+    #[allow(dead_code, reason = "the subcommands reopen ports through `connect`; tests hand over mock links")]
+    pub fn spawn<P: SerialLink + 'static>(controller: TecController<P>, interval: Duration) -> Self {
+        let mut controller = Some(controller);
+        TecMonitor::start(
+            move |_| {
+                controller.take().ok_or_else(|| {
+                    TecError::Io(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "link cannot be reopened",
+                    ))
+                })
+            },
+            cannot_reopen,
+            None,
+            interval,
+        )
+    }
+
+    /// Open `port_name` on the monitor thread and start polling it.
+    ///
+    /// The TEC is switched off but its configuration is left as the device
+    /// has it, so `ReadConfig` reports what it booted with. Failing to open
+    /// the port, or `MAX_CONSECUTIVE_ERRORS` failed reads in a row, is
+    /// reported as `MonitorEvent::Disconnected` and the port is reopened
+    /// every `RECONNECT_INTERVAL` until it answers again.
+    pub fn connect(port_name: &str, interval: Duration) -> Self {
+        TecMonitor::start(
+            |port_name| TecController::attach(port_name.unwrap_or_default()).map(disabled),
+            reopen_port,
+            Some(port_name.to_string()),
+            interval,
+        )
+    }
+
+    /// Poll a controller that its owner keeps using through the mutex,
+    /// locking it only for each readout.
+    ///
+    /// Meant for an experiment, which changes the setpoint itself: the
+    /// monitor is the one thread reading for its consumers, so they never
+    /// wait on the serial port. Commands sent to the monitor are rejected.
+    /// The returned subscription is taken before the first poll, so it sees
+    /// every readout.
+    pub fn watch<P: SerialLink + 'static>(
+        tec: Arc<Mutex<TecController<P>>>,
+        interval: Duration,
+    ) -> (Self, Receiver<TecReadout>) {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let (readout_tx, readout_rx) = mpsc::channel();
+        shared.subscribers.lock().unwrap().push(readout_tx);
+        let shared_clone = Arc::clone(&shared);
+
+        let handle = thread::spawn(move || {
+            let _ = event_tx.send(MonitorEvent::Connected);
+            watch_loop(&tec, interval, &command_rx, &event_tx, &shared_clone);
+        });
+
+        let monitor = TecMonitor {
+            command_tx,
+            event_rx,
+            shared,
+            handle: Some(handle),
+        };
+        (monitor, readout_rx)
+    }
+
+    /// Play back a recorded log instead of polling a controller.
+    ///
+    /// Readouts arrive on the schedule of the recording; commands are
+    /// answered with `MonitorEvent::Error` since there is no TEC to change.
+    pub fn replay(source: ReplaySource) -> Self {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let shared_clone = Arc::clone(&shared);
+
+        let handle = thread::spawn(move || {
+            let _ = event_tx.send(MonitorEvent::Connected);
+            replay_loop(&source, &command_rx, &event_tx, &shared_clone);
+        });
+
+        TecMonitor {
+            command_tx,
+            event_rx,
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Run the monitor thread: `connect` opens the controller on the port in
+    /// `Shared::port_name` and is called again after every lost connection
+    fn start<P, F>(mut connect: F, reopen: Reopen<P>, port_name: Option<String>, interval: Duration) -> Self
+    where
+        P: SerialLink + 'static,
+        F: FnMut(Option<&str>) -> Result<TecController<P>, TecError> + Send + 'static,
+    {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            port_name: Mutex::new(port_name),
+            ..Shared::default()
+        });
+        let shared_clone = Arc::clone(&shared);
+
+        let handle = thread::spawn(move || loop {
+            let port_name = shared_clone.port_name.lock().unwrap().clone();
+            let reason = match connect(port_name.as_deref()) {
+                Ok(controller) => {
+                    shared_clone.enabled.store(controller.is_enabled(), Ordering::SeqCst);
+                    let _ = event_tx.send(MonitorEvent::Connected);
+                    let exit = monitor_loop(
+                        controller,
+                        interval,
+                        reopen,
+                        &command_rx,
+                        &event_tx,
+                        &shared_clone,
+                    );
+                    match exit {
+                        LoopExit::Shutdown => return,
+                        LoopExit::ConnectionLost(reason) => reason,
+                    }
+                }
+                Err(e) => format!("Failed to open port: {}", e),
+            };
+
+            if let Ok(mut cached) = shared_clone.latest.write() {
+                *cached = None;
+            }
+            shared_clone.enabled.store(false, Ordering::SeqCst);
+            shared_clone.streaming.store(false, Ordering::SeqCst);
+            let _ = event_tx.send(MonitorEvent::Disconnected(reason));
+            if !wait_for_reconnect(&command_rx, &event_tx, &shared_clone) {
+                return;
+            }
+        });
+
+        TecMonitor {
+            command_tx,
+            event_rx,
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Receive a copy of every readout from now on, independently of the event
+    /// channel
+    #[cfg_attr(
+        not(any(feature = "mqtt", feature = "influx", feature = "server")),
+        allow(dead_code, reason = "used by the optional exporters and server")
+    )]
+    pub fn subscribe(&self) -> Receiver<TecReadout> {
+        self.subscriptions().subscribe()
+    }
+
+    /// Handle for subscribing from another thread, e.g. once the monitor
+    /// itself has moved to a logging thread
+    pub fn subscriptions(&self) -> Subscriptions {
+        Subscriptions {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Handle for queueing commands from another thread
+    #[cfg_attr(
+        not(any(feature = "mqtt", feature = "server")),
+        allow(dead_code, reason = "used by the MQTT bridge and server")
+    )]
+    pub fn command_sender(&self) -> Sender<MonitorCommand> {
+        self.command_tx.clone()
+    }
+
+    /// Most recent readout, without waiting on the serial port
+    #[allow(dead_code, reason = "the TUI and logger take readouts from events and subscriptions")]
+    pub fn latest(&self) -> Option<TecReadout> {
+        self.shared.latest.read().ok().and_then(|latest| latest.clone())
+    }
+
+    /// Whether the TEC supply is on, as acknowledged by the controller
+    pub fn is_enabled(&self) -> bool {
+        self.shared.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Whether readouts come from the cyclic print stream; a reconnect goes
+    /// back to polling
+    pub fn is_streaming(&self) -> bool {
+        self.shared.streaming.load(Ordering::SeqCst)
+    }
+
+    /// Disable the TEC and report an error once readouts show PWM outside
+    /// `limit` for longer than `grace`. Survives reconnects.
+    pub fn set_pwm_limit(&self, limit: PwmLimit, grace: Duration) {
+        *self.shared.pwm_limit.lock().unwrap() = Some((limit, grace));
+    }
+
+    #[allow(dead_code, reason = "limits are set once from the command line and last for the session")]
+    pub fn clear_pwm_limit(&self) {
+        *self.shared.pwm_limit.lock().unwrap() = None;
+    }
+
+    /// Reject setpoints outside `bounds` before they are written; commands
+    /// queued after this call see the new bounds. Survives reconnects.
+    pub fn set_setpoint_bounds(&self, bounds: SetpointBounds) {
+        *self.shared.setpoint_bounds.lock().unwrap() = bounds;
+    }
+
+    pub fn setpoint_bounds(&self) -> SetpointBounds {
+        *self.shared.setpoint_bounds.lock().unwrap()
+    }
+
+    /// Next pending event, if any
+    pub fn try_recv_event(&self) -> Option<MonitorEvent> {
+        self.event_rx.try_recv().ok()
+    }
+
+    /// Queue a command; returns false if the monitor thread has exited
+    pub fn send(&self, command: impl Into<MonitorCommand>) -> bool {
+        self.command_tx.send(command.into()).is_ok()
+    }
+
+    /// Stop polling and wait for the monitor thread to exit
+    pub fn shutdown(&mut self) {
+        let _ = self.command_tx.send(MonitorCommand::Shutdown);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for TecMonitor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// `controller` with the supply switched off, as far as it answers
+fn disabled<P: SerialLink>(mut controller: TecController<P>) -> TecController<P> {
+    let _ = controller.disable();
+    controller
+}
+
+/// Sit out `RECONNECT_INTERVAL`, rejecting commands; false if told to shut
+/// down. `ChangePort` retargets the reconnect and tries it straight away.
+fn wait_for_reconnect(command_rx: &Receiver<MonitorCommand>, event_tx: &Sender<MonitorEvent>, shared: &Shared) -> bool {
+    let deadline = Instant::now() + RECONNECT_INTERVAL;
+    while Instant::now() < deadline {
+        match command_rx.try_recv() {
+            Ok(MonitorCommand::Shutdown) | Err(TryRecvError::Disconnected) => return false,
+            Ok(MonitorCommand::ChangePort(new_port)) => {
+                let mut port_name = shared.port_name.lock().unwrap();
+                if port_name.is_none() {
+                    let _ = event_tx.send(MonitorEvent::Error("Link cannot be reopened".to_string()));
+                    continue;
+                }
+                if let Some(new_port) = new_port {
+                    let _ = event_tx.send(MonitorEvent::PortChanged(new_port.clone()));
+                    *port_name = Some(new_port);
+                }
+                return true;
+            }
+            Ok(_) => {
+                let _ = event_tx.send(MonitorEvent::Error("Not connected; command dropped".to_string()));
+            }
+            Err(TryRecvError::Empty) => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    true
+}
+
+fn monitor_loop<P: SerialLink>(
+    mut controller: TecController<P>,
+    interval: Duration,
+    reopen: Reopen<P>,
+    command_rx: &Receiver<MonitorCommand>,
+    event_tx: &Sender<MonitorEvent>,
+    shared: &Shared,
+) -> LoopExit {
+    let mut last_read: Option<Instant> = None;
+    let mut streaming = false;
+    // Last streamed readout, or when streaming started
+    let mut last_streamed = Instant::now();
+    let mut consecutive_errors = 0;
+    // Whether the current run of skipped polls has been reported
+    let mut busy_reported = false;
+    // When PWM first left the allowed range in the current excursion
+    let mut pwm_outside_since: Option<Instant> = None;
+
+    loop {
+        // Run every queued command (non-blocking) so a burst of key presses
+        // isn't spread over several polls or streamed readouts
+        while let Ok(command) = command_rx.try_recv() {
+            controller.set_setpoint_bounds(*shared.setpoint_bounds.lock().unwrap());
+            let event = match command {
+                MonitorCommand::Shutdown => return LoopExit::Shutdown,
+                MonitorCommand::SetStreaming(true) if !streaming => match controller.enable_cyclic_print() {
+                    Ok(_) => {
+                        streaming = true;
+                        last_streamed = Instant::now();
+                        MonitorEvent::Status(format!(
+                            "Streaming @ {} Hz",
+                            1.0 / CYCLIC_PRINT_PERIOD.as_secs_f64()
+                        ))
+                    }
+                    Err(e) => MonitorEvent::Error(format!("Streaming error: {}", e)),
+                },
+                MonitorCommand::SetStreaming(false) if streaming => match controller.disable_cyclic_print() {
+                    Ok(_) => {
+                        streaming = false;
+                        last_read = None;
+                        MonitorEvent::Status(format!("Polling every {:?}", interval))
+                    }
+                    Err(e) => MonitorEvent::Error(format!("Streaming error: {}", e)),
+                },
+                MonitorCommand::SetStreaming(on) => {
+                    MonitorEvent::Status(if on { "Already streaming" } else { "Already polling" }.to_string())
+                }
+                // An acknowledgment would be lost among the streamed lines,
+                // so the stream is paused around every other command
+                MonitorCommand::Tec(command) if streaming => match controller.disable_cyclic_print() {
+                    Ok(_) => {
+                        let event = execute_command(&mut controller, command, event_tx, shared);
+                        if let Err(e) = controller.enable_cyclic_print() {
+                            streaming = false;
+                            let _ = event_tx.send(MonitorEvent::Error(format!(
+                                "Failed to resume streaming, polling instead: {}",
+                                e
+                            )));
+                        }
+                        event
+                    }
+                    Err(e) => MonitorEvent::Error(format!("Failed to pause streaming: {}", e)),
+                },
+                MonitorCommand::ChangePort(new_port) => match reopen(&mut controller, new_port.as_deref()) {
+                    Ok(port_name) => {
+                        *shared.port_name.lock().unwrap() = Some(port_name.clone());
+                        MonitorEvent::PortChanged(port_name)
+                    }
+                    Err(e) => MonitorEvent::Error(format!("Failed to reopen port: {}", e)),
+                },
+                MonitorCommand::Tec(command) => execute_command(&mut controller, command, event_tx, shared),
+            };
+            // Publish before the event so its handler sees the new state
+            shared.enabled.store(controller.is_enabled(), Ordering::SeqCst);
+            shared.streaming.store(streaming, Ordering::SeqCst);
+            let _ = event_tx.send(event);
+        }
+
+        // Streamed readouts arrive on the device's schedule; otherwise poll
+        if streaming || last_read.is_none_or(|t| t.elapsed() >= interval) {
+            let result = if streaming {
+                controller.read_next_readout()
+            } else {
+                controller.try_get_readout()
+            };
+            match result {
+                // A late line is jitter, not a lost link, until the stream stalls
+                Err(TecError::Timeout) if streaming && last_streamed.elapsed() < STREAM_STALL_LIMIT => {}
+                Ok(readout) => {
+                    consecutive_errors = 0;
+                    busy_reported = false;
+                    last_streamed = Instant::now();
+                    let limit = *shared.pwm_limit.lock().unwrap();
+                    match limit {
+                        Some((limit, grace)) if !limit.contains(readout.pwm) => {
+                            let since = *pwm_outside_since.get_or_insert_with(Instant::now);
+                            if since.elapsed() >= grace && controller.is_enabled() {
+                                let outcome = match controller.disable() {
+                                    Ok(_) => "TEC disabled".to_string(),
+                                    Err(e) => format!("disable failed: {}", e),
+                                };
+                                shared.enabled.store(controller.is_enabled(), Ordering::SeqCst);
+                                let _ = event_tx.send(MonitorEvent::Error(format!(
+                                    "PWM {:.0}% outside {:.0}..{:.0}% for over {:?}; {}",
+                                    readout.pwm, limit.min_percent, limit.max_percent, grace, outcome
+                                )));
+                                pwm_outside_since = None;
+                            }
+                        }
+                        _ => pwm_outside_since = None,
+                    }
+                    publish(readout, event_tx, shared);
+                }
+                // A timed-out reply may still be arriving; the UI keeps the
+                // last readout and a later interval tries again
+                Err(e @ TecError::Busy(_)) => {
+                    if !busy_reported {
+                        let _ = event_tx.send(MonitorEvent::Status(format!("Skipping readouts: {}", e)));
+                        busy_reported = true;
+                    }
+                }
+                Err(e) => {
+                    consecutive_errors += 1;
+                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                        return LoopExit::ConnectionLost(format!(
+                            "{} consecutive read errors, last: {}",
+                            consecutive_errors, e
+                        ));
+                    }
+                    let _ = event_tx.send(MonitorEvent::Error(format!("Read error: {}", e)));
+                }
+            }
+            last_read = Some(Instant::now());
+        }
+
+        // Small sleep to prevent busy-waiting
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Run a command that changes the controller and describe the outcome
+fn execute_command<P: SerialLink>(
+    controller: &mut TecController<P>,
+    command: TecCommand,
+    event_tx: &Sender<MonitorEvent>,
+    shared: &Shared,
+) -> MonitorEvent {
+    match command {
+        TecCommand::SetConfig(config) => match controller.set_configuration(&config) {
+            Ok(_) => MonitorEvent::Status("Config updated".to_string()),
+            Err(e) => MonitorEvent::Error(format!("Config error: {}", e)),
+        },
+        TecCommand::SetT(t_set) => match controller.set_t(t_set) {
+            Ok(_) => MonitorEvent::Status(format!("Setpoint {:.1}°C", t_set)),
+            Err(e) => MonitorEvent::Error(format!("Setpoint error: {}", e)),
+        },
+        TecCommand::SetPid { p, i, d } => match controller.set_pid(p, i, d) {
+            Ok(_) => MonitorEvent::Status(format!("PID P {:.2} I {:.2} D {:.2}", p, i, d)),
+            Err(e) => MonitorEvent::Error(format!("PID error: {}", e)),
+        },
+        TecCommand::Enable => match controller.enable() {
+            Ok(_) => MonitorEvent::Status("TEC ENABLED".to_string()),
+            Err(e) => MonitorEvent::Error(format!("Enable error: {}", e)),
+        },
+        TecCommand::Disable => match controller.disable() {
+            Ok(_) => MonitorEvent::Status("TEC DISABLED".to_string()),
+            Err(e) => MonitorEvent::Error(format!("Disable error: {}", e)),
+        },
+        TecCommand::ClearIntegrator => match controller.clear_integrator() {
+            Ok(_) => MonitorEvent::Status("Integrator cleared".to_string()),
+            Err(e) => MonitorEvent::Error(format!("Clear integrator error: {}", e)),
+        },
+        TecCommand::ReadConfig => match controller.read_config() {
+            Ok(config) => MonitorEvent::Config(config),
+            Err(e) => MonitorEvent::Error(format!("Read config error: {}", e)),
+        },
+        TecCommand::RampTo { target, rate_c_per_s } => {
+            let result = controller.ramp_to(target, rate_c_per_s, RAMP_STEP_INTERVAL, |readout| {
+                publish(readout.clone(), event_tx, shared)
+            });
+            match result {
+                Ok(_) => MonitorEvent::Status(format!("Ramped to {:.1}°C", target)),
+                Err(e) => MonitorEvent::Error(format!("Ramp error: {}", e)),
+            }
+        }
+    }
+}
+
+/// Cache `readout` and hand it to subscribers and the event channel
+fn publish(readout: TecReadout, event_tx: &Sender<MonitorEvent>, shared: &Shared) {
+    if let Ok(mut cached) = shared.latest.write() {
+        *cached = Some(readout.clone());
+    }
+    if let Ok(mut subscribers) = shared.subscribers.lock() {
+        subscribers.retain(|tx| tx.send(readout.clone()).is_ok());
+    }
+    let _ = event_tx.send(MonitorEvent::Readout(readout));
+}
+
+/// Read the shared controller every `interval` until shut down
+fn watch_loop<P: SerialLink>(
+    tec: &Mutex<TecController<P>>,
+    interval: Duration,
+    command_rx: &Receiver<MonitorCommand>,
+    event_tx: &Sender<MonitorEvent>,
+    shared: &Shared,
+) {
+    // Whether the current run of skipped polls has been reported
+    let mut busy_reported = false;
+    loop {
+        match command_rx.try_recv() {
+            Ok(MonitorCommand::Shutdown) | Err(TryRecvError::Disconnected) => return,
+            Ok(_) => {
+                let _ = event_tx.send(MonitorEvent::Error("Controller is shared; command ignored".to_string()));
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let started = Instant::now();
+        // Locked only for the round trip, so the owner's commands get in between polls
+        let result = match tec.lock() {
+            Ok(mut controller) => controller.try_get_readout(),
+            Err(e) => Err(TecError::Io(io::Error::other(e.to_string()))),
+        };
+        match result {
+            Ok(readout) => {
+                busy_reported = false;
+                publish(readout, event_tx, shared);
+            }
+            // The owner is using the port; say so once, not on every poll
+            Err(TecError::Busy(_)) if busy_reported => {}
+            Err(e) => {
+                busy_reported = matches!(e, TecError::Busy(_));
+                let _ = event_tx.send(MonitorEvent::Error(format!("Failed to read TEC data: {}", e)));
+            }
+        }
+
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// Publish each readout of `source` when it falls due, then idle until shut
+/// down
+fn replay_loop(
+    source: &ReplaySource,
+    command_rx: &Receiver<MonitorCommand>,
+    event_tx: &Sender<MonitorEvent>,
+    shared: &Shared,
+) {
+    let start = Instant::now();
+    let mut schedule = source.schedule().peekable();
+
+    loop {
+        match command_rx.try_recv() {
+            Ok(MonitorCommand::Shutdown) | Err(TryRecvError::Disconnected) => return,
+            // Nothing to read back; the UI keeps the configuration it has
+            Ok(MonitorCommand::Tec(TecCommand::ReadConfig)) => {}
+            Ok(_) => {
+                let _ = event_tx.send(MonitorEvent::Error("Replaying a log; command ignored".to_string()));
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+
+        let mut finished = false;
+        while let Some((due, _)) = schedule.peek() {
+            if start.elapsed() < *due {
+                break;
+            }
+            let (_, readout) = schedule.next().unwrap();
+            publish(readout.clone(), event_tx, shared);
+            finished = schedule.peek().is_none();
+        }
+        if finished {
+            let _ = event_tx.send(MonitorEvent::Status("Replay finished".to_string()));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tec::mock::MockLink;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_pwm_limit_disables_tec_after_grace() {
+        const HEATING: &str = "Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+24.00 OC=0 PW=+ 25";
+        let link = MockLink::connected();
+        let mut controller = TecController::with_link(link.clone()).unwrap();
+        link.respond(&["<A>"]);
+        controller.enable().unwrap();
+        for _ in 0..50 {
+            link.respond(&["<o>", HEATING]);
+        }
+
+        let monitor = TecMonitor::spawn(controller, Duration::from_millis(10));
+        monitor.set_pwm_limit(PwmLimit::new(-100.0, 0.0).unwrap(), Duration::from_millis(50));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut tripped = None;
+        while tripped.is_none() && Instant::now() < deadline {
+            while let Some(event) = monitor.try_recv_event() {
+                if let MonitorEvent::Error(message) = event
+                    && message.starts_with("PWM")
+                {
+                    tripped = Some(message);
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let message = tripped.expect("PWM limit never tripped");
+        assert!(message.contains("25% outside -100..0%"), "{}", message);
+        assert!(message.ends_with("TEC disabled"), "{}", message);
+        assert!(!monitor.is_enabled());
+        assert!(link.written().contains("Ao"));
+        assert!(link.written().contains("oa"));
+    }
+
+    #[test]
+    fn test_streaming_replaces_polling_until_turned_off() {
+        let readout = |t_measured: &str| {
+            format!("Tz=+25.00 P= 5.00 I= 2.00 D= 1.00 T=  0...+50 Tr=+{} OC=0 PW=+ 25", t_measured)
+        };
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        let (first, streamed, polled) = (readout("24.00"), readout("24.50"), readout("25.00"));
+        link.respond(&["<o>", &first]);
+        link.respond(&["<R>", &streamed]);
+        link.respond(&["<r>"]);
+        link.respond(&["<o>", &polled]);
+
+        // Long enough that only the first poll and the one after streaming happen
+        let monitor = TecMonitor::spawn(controller, Duration::from_secs(3600));
+        let wait_for = |t_measured: f32| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                while let Some(event) = monitor.try_recv_event() {
+                    if let MonitorEvent::Readout(readout) = event
+                        && readout.t_measured == t_measured
+                    {
+                        return;
+                    }
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            panic!("no readout of {}", t_measured);
+        };
+
+        wait_for(24.0);
+        monitor.send(MonitorCommand::SetStreaming(true));
+        wait_for(24.5);
+        assert!(monitor.is_streaming());
+        monitor.send(MonitorCommand::SetStreaming(false));
+        wait_for(25.0);
+        assert!(!monitor.is_streaming());
+        assert!(link.written().ends_with("oRro"), "{}", link.written());
+    }
+
+    #[test]
+    fn test_connect_keeps_the_device_configuration() {
+        const BOOT_READOUT: &str = "Tz=+30.00 P= 8.00 I= 1.00 D= 0.00 T=  5...+45 Tr=+29.50 OC=0 PW=- 10";
+        let link = MockLink::new();
+        link.respond(&["<r>"]);
+        link.respond(&["<o>", BOOT_READOUT]);
+        link.respond(&["<a>"]);
+        link.respond(&["<o>", BOOT_READOUT]);
+        link.respond(&["<o>", BOOT_READOUT]);
+        let connect_link = link.clone();
+        let mut connected = false;
+        let monitor = TecMonitor::start(
+            move |_| match std::mem::replace(&mut connected, true) {
+                false => TecController::attach_link(connect_link.clone()).map(disabled),
+                true => Err(TecError::Timeout),
+            },
+            cannot_reopen,
+            None,
+            Duration::from_secs(3600),
+        );
+        assert!(monitor.send(TecCommand::ReadConfig));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let config = loop {
+            match monitor.try_recv_event() {
+                Some(MonitorEvent::Config(config)) => break config,
+                Some(_) => {}
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                None => panic!("no configuration read back"),
+            }
+        };
+        assert_eq!((config.t_set, config.p, config.t_min, config.t_max), (30.0, 8.0, 5.0, 45.0));
+        assert!(link.written().starts_with("roao"), "{}", link.written());
+        assert!(!link.written().contains('<'), "{}", link.written());
+    }
+
+    #[test]
+    fn test_queued_commands_run_in_order() {
+        let link = MockLink::connected();
+        let controller = TecController::with_link(link.clone()).unwrap();
+        link.respond(&["<o>", "Tz=+20.00 P= 5.50 I= 2.50 D= 0.50 T=  0...+35 Tr=+21.00 OC=0 PW=+ 25"]);
+        link.respond(&["<A>"]);
+        link.respond(&[crate::tec::mock::DEFAULT_CONFIG_ACK.replace("eTzc=20.00", "eTzc=30.00").as_str()]);
+        link.respond(&["<a>"]);
+
+        let monitor = TecMonitor::spawn(controller, Duration::from_secs(3600));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while monitor.latest().is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        for command in [TecCommand::Enable, TecCommand::SetT(30.0), TecCommand::Disable] {
+            assert!(monitor.send(command));
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut statuses = Vec::new();
+        while statuses.len() < 3 && Instant::now() < deadline {
+            while let Some(event) = monitor.try_recv_event() {
+                match event {
+                    MonitorEvent::Status(status) => statuses.push(status),
+                    MonitorEvent::Error(e) => panic!("unexpected error: {}", e),
+                    _ => {}
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(statuses, ["TEC ENABLED", "Setpoint 30.0°C", "TEC DISABLED"]);
+        assert!(link.written().ends_with("A<30 5.5 2.5 0.5 0 35>a"), "{}", link.written());
+    }
+
+    #[test]
+    fn test_reconnects_after_repeated_read_errors() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        // The first link is never answered again, so every readout times out
+        let monitor = TecMonitor::start(
+            move |_| match attempts_clone.fetch_add(1, Ordering::SeqCst) {
+                0 => TecController::with_link(MockLink::connected()),
+                _ => Err(TecError::Timeout),
+            },
+            cannot_reopen,
+            None,
+            Duration::from_millis(50),
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut events = Vec::new();
+        while attempts.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+            while let Some(event) = monitor.try_recv_event() {
+                events.push(event);
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        assert!(matches!(events.first(), Some(MonitorEvent::Connected)));
+        assert!(events.iter().any(|e| matches!(e, MonitorEvent::Disconnected(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(monitor.latest().is_none());
+    }
+
+    /// Wait for the first event `pick` accepts, failing after a few seconds
+    fn wait_for_event<T>(monitor: &TecMonitor, pick: impl Fn(MonitorEvent) -> Option<T>) -> T {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match monitor.try_recv_event() {
+                Some(event) => {
+                    if let Some(picked) = pick(event) {
+                        return picked;
+                    }
+                }
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                None => panic!("expected event never arrived"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_change_port() {
+        fn reopen(_: &mut TecController<MockLink>, new_port: Option<&str>) -> Result<String, TecError> {
+            Ok(new_port.unwrap_or("/dev/ttyUSB0").to_string())
+        }
+        let link = MockLink::connected();
+        let mut controller = Some(TecController::with_link(link).unwrap());
+        let monitor = TecMonitor::start(
+            move |_| controller.take().ok_or(TecError::Timeout),
+            reopen,
+            Some("/dev/ttyUSB0".to_string()),
+            Duration::from_secs(3600),
+        );
+        assert!(monitor.send(MonitorCommand::ChangePort(Some("/dev/ttyUSB1".to_string()))));
+        let port = wait_for_event(&monitor, |event| match event {
+            MonitorEvent::PortChanged(port) => Some(port),
+            _ => None,
+        });
+        assert_eq!(port, "/dev/ttyUSB1");
+        assert_eq!(monitor.shared.port_name.lock().unwrap().as_deref(), Some("/dev/ttyUSB1"));
+
+        // A link handed over ready-made stays where it is
+        let controller = TecController::with_link(MockLink::connected()).unwrap();
+        let spawned = TecMonitor::spawn(controller, Duration::from_secs(3600));
+        assert!(spawned.send(MonitorCommand::ChangePort(None)));
+        let error = wait_for_event(&spawned, |event| match event {
+            MonitorEvent::Error(e) => Some(e),
+            _ => None,
+        });
+        assert!(error.contains("cannot be reopened"), "{}", error);
+    }
+
+    #[test]
+    fn test_change_port_while_disconnected_retargets_the_reconnect() {
+        let ports = Arc::new(Mutex::new(Vec::new()));
+        let ports_clone = Arc::clone(&ports);
+        let monitor = TecMonitor::start(
+            move |port: Option<&str>| -> Result<TecController<MockLink>, TecError> {
+                ports_clone.lock().unwrap().push(port.map(str::to_string));
+                Err(TecError::Timeout)
+            },
+            cannot_reopen,
+            Some("/dev/ttyUSB0".to_string()),
+            Duration::from_secs(3600),
+        );
+        wait_for_event(&monitor, |event| matches!(event, MonitorEvent::Disconnected(_)).then_some(()));
+        assert!(monitor.send(MonitorCommand::ChangePort(Some("/dev/ttyUSB1".to_string()))));
+
+        // Tried straight away rather than after RECONNECT_INTERVAL
+        let deadline = Instant::now() + RECONNECT_INTERVAL / 2;
+        while ports.lock().unwrap().len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let expected = ["/dev/ttyUSB0", "/dev/ttyUSB1"].map(|port| Some(port.to_string()));
+        assert_eq!(ports.lock().unwrap()[..], expected);
+    }
+}
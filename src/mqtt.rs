@@ -0,0 +1,160 @@
+use crate::monitor::{MonitorCommand, TecCommand};
+use crate::net;
+use crate::tec::TecReadout;
+use log::{debug, warn};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Broker port used when the address has none
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// Topic prefix used when none is given
+pub const DEFAULT_TOPIC_PREFIX: &str = "glow-station";
+
+/// Requests buffered between the publisher and the network thread
+const REQUEST_CAPACITY: usize = 32;
+
+/// First and longest wait between reconnect attempts
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub prefix: String,
+}
+
+impl MqttConfig {
+    /// Parse a `host[:port]` broker address; an IPv6 host goes in brackets
+    pub fn new(broker: &str, prefix: &str) -> Result<Self, String> {
+        let (host, port) = match net::split_host_port(broker)? {
+            (host, Some(port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| format!("Invalid MQTT broker port: {:?}", port))?;
+                (host, port)
+            }
+            (host, None) => (host, DEFAULT_MQTT_PORT),
+        };
+        if host.is_empty() {
+            return Err("MQTT broker host is empty".to_string());
+        }
+        Ok(MqttConfig {
+            host: host.to_string(),
+            port,
+            prefix: prefix.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn readout_topic(&self) -> String {
+        format!("{}/readout", self.prefix)
+    }
+
+    fn set_t_topic(&self) -> String {
+        format!("{}/set_t", self.prefix)
+    }
+}
+
+/// Publish every readout as JSON to `{prefix}/readout` and forward setpoints
+/// received on `{prefix}/set_t` to the monitor.
+///
+/// Runs on two threads: one drains `readouts`, the other drives the broker
+/// connection and reconnects with exponential backoff when it drops.
+pub fn start(config: MqttConfig, readouts: Receiver<TecReadout>, commands: Sender<MonitorCommand>) {
+    let client_id = format!("glow-station-{}", std::process::id());
+    let mut options = MqttOptions::new(client_id, config.host.clone(), config.port);
+    options.set_keep_alive(Duration::from_secs(10));
+    let (client, mut connection) = Client::new(options, REQUEST_CAPACITY);
+
+    let publisher = client.clone();
+    let readout_topic = config.readout_topic();
+    thread::spawn(move || {
+        for readout in readouts {
+            let payload = match serde_json::to_vec(&readout) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Failed to serialize readout: {}", e);
+                    continue;
+                }
+            };
+            // Drop readouts rather than stall while the broker is away
+            if let Err(e) = publisher.try_publish(&readout_topic, QoS::AtMostOnce, false, payload) {
+                debug!("Readout not published: {}", e);
+            }
+        }
+        let _ = publisher.disconnect();
+    });
+
+    let set_t_topic = config.set_t_topic();
+    thread::spawn(move || {
+        let mut backoff = INITIAL_BACKOFF;
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    backoff = INITIAL_BACKOFF;
+                    // Subscriptions do not survive a clean-session reconnect
+                    if let Err(e) = client.try_subscribe(&set_t_topic, QoS::AtLeastOnce) {
+                        warn!("Failed to subscribe to {}: {}", set_t_topic, e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == set_t_topic => {
+                    match parse_setpoint(&publish.payload) {
+                        Some(t_set) => {
+                            if commands.send(TecCommand::SetT(t_set).into()).is_err() {
+                                break;
+                            }
+                        }
+                        None => {
+                            warn!("Ignoring invalid setpoint on {}: {:?}", set_t_topic, publish.payload)
+                        }
+                    }
+                }
+                Ok(Event::Outgoing(rumqttc::Outgoing::Disconnect)) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("MQTT connection error: {}; retrying in {:?}", e, backoff);
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    });
+}
+
+/// Setpoint in °C from a `set_t` payload such as `25.5`
+fn parse_setpoint(payload: &[u8]) -> Option<f32> {
+    let t_set: f32 = std::str::from_utf8(payload).ok()?.trim().parse().ok()?;
+    t_set.is_finite().then_some(t_set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_new() {
+        let config = MqttConfig::new("broker.local:1884", "lab/station1/").unwrap();
+        assert_eq!(config.host, "broker.local");
+        assert_eq!(config.port, 1884);
+        assert_eq!(config.readout_topic(), "lab/station1/readout");
+        assert_eq!(config.set_t_topic(), "lab/station1/set_t");
+
+        assert_eq!(MqttConfig::new("broker.local", "x").unwrap().port, DEFAULT_MQTT_PORT);
+        assert!(MqttConfig::new("broker.local:mqtt", "x").is_err());
+        assert!(MqttConfig::new(":1883", "x").is_err());
+
+        let config = MqttConfig::new("[::1]:1884", "x").unwrap();
+        assert_eq!((config.host.as_str(), config.port), ("::1", 1884));
+    }
+
+    #[test]
+    fn test_parse_setpoint() {
+        assert_eq!(parse_setpoint(b"25.5"), Some(25.5));
+        assert_eq!(parse_setpoint(b" 30\n"), Some(30.0));
+        assert_eq!(parse_setpoint(b"warm"), None);
+        assert_eq!(parse_setpoint(b"NaN"), None);
+    }
+}
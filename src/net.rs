@@ -0,0 +1,53 @@
+/// Split a `host[:port]` address into the host and the port text, if any.
+///
+/// An IPv6 host must be bracketed, as in `[::1]:1883`; the brackets are
+/// removed from the returned host so it can be passed to `ToSocketAddrs`.
+/// The port is left for the caller to parse so its error names the service.
+pub fn split_host_port(address: &str) -> Result<(&str, Option<&str>), String> {
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("Missing ']' in {:?}", address))?;
+        return match after {
+            "" => Ok((host, None)),
+            _ => match after.strip_prefix(':') {
+                Some(port) => Ok((host, Some(port))),
+                None => Err(format!("Expected ':' after ']' in {:?}", address)),
+            },
+        };
+    }
+    match address.rsplit_once(':') {
+        Some((host, _)) if host.contains(':') => {
+            Err(format!("IPv6 address must be in brackets, e.g. [::1]: {:?}", address))
+        }
+        Some((host, port)) => Ok((host, Some(port))),
+        None => Ok((address, None)),
+    }
+}
+
+/// `host` as written in a URL or `Host` header: bracketed when it is an IPv6
+/// address
+#[cfg(feature = "influx")]
+pub fn format_host(host: &str) -> String {
+    if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(split_host_port("broker.local:1884"), Ok(("broker.local", Some("1884"))));
+        assert_eq!(split_host_port("broker.local"), Ok(("broker.local", None)));
+        assert_eq!(split_host_port("[::1]:1883"), Ok(("::1", Some("1883"))));
+        assert_eq!(split_host_port("[fe80::1]"), Ok(("fe80::1", None)));
+        assert!(split_host_port("::1").is_err());
+        assert!(split_host_port("[::1").is_err());
+        assert!(split_host_port("[::1]1883").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::analysis::LogSample;
+use crate::experiment::PhaseTiming;
+use plotters::prelude::*;
+use std::error::Error;
+
+/// Size of `temperature_plot.png` in pixels
+const PLOT_SIZE: (u32, u32) = (1600, 900);
+
+/// Draw setpoint and measured temperature against time since the first
+/// sample, with a labelled vertical line where each phase starts
+pub fn render_temperature_plot(
+    path: &str,
+    samples: &[LogSample],
+    phases: &[PhaseTiming],
+) -> Result<(), Box<dyn Error>> {
+    let Some(first) = samples.first() else {
+        return Err("temperature log is empty".into());
+    };
+    let start_ms = first.timestamp_ms;
+    let seconds = |timestamp_ms: u64| timestamp_ms.saturating_sub(start_ms) as f64 / 1000.0;
+
+    let x_max = seconds(samples[samples.len() - 1].timestamp_ms).max(1.0);
+    let (y_min, y_max) = samples
+        .iter()
+        .flat_map(|s| [s.t_set, s.t_measured])
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    let margin = ((y_max - y_min) * 0.1).max(0.5);
+    let (y_min, y_max) = ((y_min - margin) as f64, (y_max + margin) as f64);
+
+    let root = BitMapBackend::new(path, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Temperature", ("sans-serif", 32))
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(70)
+        .build_cartesian_2d(0.0..x_max, y_min..y_max)?;
+
+    chart
+        .configure_mesh()
+        .x_desc("Time (s)")
+        .y_desc("Temperature (°C)")
+        .draw()?;
+
+    for phase in phases {
+        let x = seconds(phase.start_time_ms);
+        if !(0.0..=x_max).contains(&x) {
+            continue;
+        }
+        chart.draw_series(LineSeries::new([(x, y_min), (x, y_max)], BLACK.mix(0.3)))?;
+        chart.draw_series([Text::new(
+            phase.phase_name.clone(),
+            (x, y_max),
+            ("sans-serif", 14).into_font().color(&BLACK.mix(0.6)),
+        )])?;
+    }
+
+    chart
+        .draw_series(LineSeries::new(
+            samples.iter().map(|s| (seconds(s.timestamp_ms), s.t_set as f64)),
+            MAGENTA.stroke_width(2),
+        ))?
+        .label("Setpoint")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], MAGENTA));
+
+    chart
+        .draw_series(LineSeries::new(
+            samples.iter().map(|s| (seconds(s.timestamp_ms), s.t_measured as f64)),
+            BLUE.stroke_width(2),
+        ))?
+        .label("Measured")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLUE));
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs;
+
+    #[test]
+    fn test_render_temperature_plot() {
+        let scratch = ScratchDir::new("plot");
+        let path = scratch.file("temperature_plot.png");
+        let samples: Vec<LogSample> = (0..100)
+            .map(|i| LogSample {
+                timestamp_ms: 1_000 + i * 100,
+                t_set: if i < 50 { 25.0 } else { 35.0 },
+                t_measured: 25.0 + (i as f32 / 10.0),
+            })
+            .collect();
+        let phases = [PhaseTiming {
+            phase_name: "Heat".to_string(),
+            start_time_ms: 6_000,
+            end_time_ms: 11_000,
+            duration_s: 5.0,
+            t_measured_start: None,
+            t_measured_end: None,
+            t_set_start: None,
+            t_set_end: None,
+        }];
+
+        render_temperature_plot(&path, &samples, &phases).unwrap();
+        let written = fs::metadata(&path).map(|m| m.len());
+
+        assert!(written.unwrap() > 0);
+        assert!(render_temperature_plot(&path, &[], &phases).is_err());
+    }
+}
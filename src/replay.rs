@@ -0,0 +1,75 @@
+use crate::analysis;
+use crate::experiment::LogFormat;
+use crate::tec::TecReadout;
+use std::error::Error;
+use std::time::Duration;
+
+/// Readouts from a recorded `temperature_log.csv`, played back in place of
+/// the serial port
+pub struct ReplaySource {
+    /// Log timestamp in milliseconds and the readout logged with it
+    rows: Vec<(u64, TecReadout)>,
+    /// Playback speed relative to the recording
+    speed: f64,
+}
+
+impl ReplaySource {
+    /// Load every row of the CSV temperature log at `path`.
+    ///
+    /// `speed` scales the gaps between rows: 1.0 plays back in real time,
+    /// 10.0 ten times faster.
+    pub fn from_csv(path: &str, speed: f64) -> Result<ReplaySource, Box<dyn Error>> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(format!("invalid replay speed: {}", speed).into());
+        }
+        let rows = analysis::read_log_readouts(path, LogFormat::Csv).map_err(|e| format!("{} {}", path, e))?;
+        if rows.is_empty() {
+            return Err(format!("{} has no readouts to replay", path).into());
+        }
+        Ok(ReplaySource { rows, speed })
+    }
+
+    /// Readouts in log order, each with how long after the first one it is
+    /// due at the playback speed
+    pub fn schedule(&self) -> impl Iterator<Item = (Duration, &TecReadout)> {
+        let start = self.rows[0].0;
+        self.rows.iter().map(move |(timestamp, readout)| {
+            let elapsed_ms = timestamp.saturating_sub(start) as f64;
+            (Duration::from_secs_f64(elapsed_ms / 1000.0 / self.speed), readout)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use std::fs;
+
+    #[test]
+    fn test_replay_schedule() {
+        let scratch = ScratchDir::new("replay");
+        let path = scratch.file("temperature_log.csv");
+        fs::write(
+            &path,
+            "timestamp_ms,T_setpoint,P,I,D,T_min,T_max,T_measured,OC,PWM,phase\n\
+             1000,25.00,5.00,2.00,1.00,0.00,50.00,24.50,0,25.0,\"Heat, fast\"\n\
+             3000,25.00,5.00,2.00,1.00,0.00,50.00,24.90,1,-12.5,Hold\n",
+        )
+        .unwrap();
+
+        let source = ReplaySource::from_csv(&path, 4.0).unwrap();
+        let schedule: Vec<(Duration, &TecReadout)> = source.schedule().collect();
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(schedule[0].0, Duration::ZERO);
+        assert_eq!(schedule[1].0, Duration::from_millis(500));
+        assert_eq!(schedule[0].1.t_measured, 24.5);
+        assert!(!schedule[0].1.oc);
+        assert!(schedule[1].1.oc);
+        assert_eq!(schedule[1].1.pwm, -12.5);
+
+        assert!(ReplaySource::from_csv(&path, 0.0).is_err());
+        fs::write(&path, "1000,25.00,warm\n").unwrap();
+        assert!(ReplaySource::from_csv(&path, 1.0).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::monitor::TecCommand;
+use crate::tec::{TecConfig, TecReadout};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+/// Largest request body accepted, well above any config payload
+const MAX_BODY_BYTES: u64 = 4096;
+
+#[derive(Debug, Deserialize)]
+struct SetpointRequest {
+    t_set: f32,
+}
+
+/// Experiment started through `POST /experiment/start`, as reported by
+/// `GET /experiment`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "lowercase")]
+pub enum ExperimentState {
+    Idle,
+    Running,
+    Completed,
+    Aborted,
+    Failed(String),
+}
+
+/// Experiment requests for whoever owns the serial port
+#[derive(Debug, PartialEq)]
+pub enum ExperimentRequest {
+    Start,
+    Stop,
+}
+
+/// Where the server sends changes: TEC commands, and experiment requests
+/// answered through `experiment`, which the receiver keeps current
+pub struct Control {
+    pub commands: Sender<TecCommand>,
+    pub experiments: Sender<ExperimentRequest>,
+    pub experiment: Arc<Mutex<ExperimentState>>,
+}
+
+/// Readout cache and live WebSocket clients, both fed from the monitor
+#[derive(Default)]
+struct Shared {
+    latest: Mutex<Option<TecReadout>>,
+    clients: Mutex<Vec<Sender<TecReadout>>>,
+}
+
+/// Serve the TEC over HTTP on `address:port`:
+///
+/// - `GET /readout`: latest readout as JSON, 503 before the first one
+/// - `POST /setpoint`: `{"t_set": 30.0}`
+/// - `POST /config`: a full `TecConfig`, validated before it is sent
+/// - `POST /enable`, `POST /disable`
+/// - `GET /experiment`: `{"state": "running"}`, or `idle`, `completed`,
+///   `aborted` or `failed` with an `error`
+/// - `POST /experiment/start`, `POST /experiment/stop`
+/// - `GET /ws`: WebSocket streaming every readout as a JSON text message
+///
+/// Setpoint, config and enable requests are refused with 409 while an
+/// experiment runs. Readouts come from a monitor subscription and changes go
+/// to `control`, so the server never touches the serial port. There is no
+/// authentication: bind to anything but loopback only on a trusted network.
+/// Returns the bound address, so port 0 picks a free one.
+pub fn serve(
+    address: IpAddr,
+    port: u16,
+    readouts: Receiver<TecReadout>,
+    control: Control,
+) -> io::Result<(SocketAddr, thread::JoinHandle<()>)> {
+    let server = Server::http((address, port)).map_err(io::Error::other)?;
+    let bound = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| io::Error::other("control server is not listening on TCP"))?;
+    println!("Serving control API on http://{}", bound);
+
+    let shared = Arc::new(Shared::default());
+    let fanout = Arc::clone(&shared);
+    thread::spawn(move || {
+        for readout in readouts {
+            *fanout.latest.lock().unwrap() = Some(readout.clone());
+            // Clients whose socket closed have dropped their receiver
+            fanout
+                .clients
+                .lock()
+                .unwrap()
+                .retain(|tx| tx.send(readout.clone()).is_ok());
+        }
+    });
+
+    Ok((
+        bound,
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if request.method() == &Method::Get && path(&request) == "/ws" {
+                    stream_readouts(request, &shared);
+                    continue;
+                }
+                handle(request, &shared, &control);
+            }
+        }),
+    ))
+}
+
+/// Request path without the query string
+fn path(request: &Request) -> &str {
+    let url = request.url();
+    url.split_once('?').map_or(url, |(path, _)| path)
+}
+
+fn handle(mut request: Request, shared: &Shared, control: &Control) {
+    let experiment = control.experiment.lock().unwrap().clone();
+    let running = experiment == ExperimentState::Running;
+    let method = request.method().clone();
+    let (status, body) = match (method, path(&request)) {
+        (Method::Get, "/readout") => match shared.latest.lock().unwrap().as_ref() {
+            Some(readout) => (200, serde_json::to_string(readout).unwrap_or_default()),
+            None => error(503, "no readout yet"),
+        },
+        (Method::Get, "/experiment") => (200, serde_json::to_string(&experiment).unwrap_or_default()),
+        (Method::Post, "/experiment/start") if running => error(409, "an experiment is already running"),
+        (Method::Post, "/experiment/start") => send(&control.experiments, ExperimentRequest::Start),
+        (Method::Post, "/experiment/stop") if running => send(&control.experiments, ExperimentRequest::Stop),
+        (Method::Post, "/experiment/stop") => error(409, "no experiment is running"),
+        (Method::Post, "/setpoint" | "/config" | "/enable" | "/disable") if running => {
+            error(409, "an experiment is running")
+        }
+        (Method::Post, "/setpoint") => match read_json::<SetpointRequest>(&mut request) {
+            Ok(SetpointRequest { t_set }) if t_set.is_finite() => {
+                send(&control.commands, TecCommand::SetT(t_set))
+            }
+            Ok(_) => error(400, "t_set must be a finite number"),
+            Err(e) => error(400, &e),
+        },
+        (Method::Post, "/config") => match read_json::<TecConfig>(&mut request) {
+            Ok(config) => match config.validate() {
+                Ok(()) => send(&control.commands, TecCommand::SetConfig(config)),
+                Err(e) => error(400, &e.to_string()),
+            },
+            Err(e) => error(400, &e),
+        },
+        (Method::Post, "/enable") => send(&control.commands, TecCommand::Enable),
+        (Method::Post, "/disable") => send(&control.commands, TecCommand::Disable),
+        _ => error(404, "not found"),
+    };
+
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Failed to answer control request: {}", e);
+    }
+}
+
+/// Queue `command`; its outcome is reported through the monitor's events or
+/// `GET /experiment`
+fn send<T>(commands: &Sender<T>, command: T) -> (u16, String) {
+    match commands.send(command) {
+        Ok(()) => (202, r#"{"status":"queued"}"#.to_string()),
+        Err(_) => error(503, "monitor has stopped"),
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, serde_json::json!({ "error": message }).to_string())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, String> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_string(&mut body)
+        .map_err(|e| format!("unreadable body: {}", e))?;
+    serde_json::from_str(&body).map_err(|e| format!("invalid JSON: {}", e))
+}
+
+/// Complete the WebSocket handshake and push readouts to the client on a
+/// thread of its own until it goes away
+fn stream_readouts(request: Request, shared: &Shared) {
+    let key = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+        .map(|h| h.value.as_str().to_string());
+    let Some(key) = key else {
+        let _ = request.respond(Response::from_string("expected a WebSocket upgrade\n").with_status_code(400));
+        return;
+    };
+
+    let response = Response::empty(StatusCode(101))
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(
+            Header::from_bytes("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes())).unwrap(),
+        );
+    let stream = request.upgrade("websocket", response);
+
+    let (tx, rx) = mpsc::channel();
+    shared.clients.lock().unwrap().push(tx);
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for readout in rx {
+            let Ok(text) = serde_json::to_string(&readout) else { continue };
+            if socket.send(Message::text(text)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_control_endpoints() {
+        let (readout_tx, readout_rx) = mpsc::channel();
+        let (command_tx, command_rx) = mpsc::channel();
+        let (experiment_tx, experiment_rx) = mpsc::channel();
+        let experiment = Arc::new(Mutex::new(ExperimentState::Idle));
+        let control = Control {
+            commands: command_tx,
+            experiments: experiment_tx,
+            experiment: Arc::clone(&experiment),
+        };
+        let (address, _) = serve(IpAddr::from([127, 0, 0, 1]), 0, readout_rx, control).unwrap();
+        assert_ne!(address.port(), 0);
+
+        assert!(request(address, "GET", "/readout", "").starts_with("HTTP/1.1 503"));
+
+        readout_tx
+            .send(TecReadout {
+                t_set: 25.0,
+                p: 5.0,
+                i: 2.0,
+                d: 1.0,
+                t_min: 0.0,
+                t_max: 50.0,
+                t_measured: 24.5,
+                oc: false,
+                pwm: 10.0,
+                resync_count: 0,
+                t_measured_device: None,
+            })
+            .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let response = request(address, "GET", "/readout?fields=all", "");
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains("\"t_measured\":24.5"));
+
+        assert!(request(address, "POST", "/setpoint", r#"{"t_set": 30.0}"#).starts_with("HTTP/1.1 202"));
+        assert!(matches!(command_rx.try_recv(), Ok(TecCommand::SetT(30.0))));
+        assert!(request(address, "POST", "/enable", "").starts_with("HTTP/1.1 202"));
+        assert!(matches!(command_rx.try_recv(), Ok(TecCommand::Enable)));
+
+        let bad_config = r#"{"t_set": 25.0, "p": 5.0, "i": 2.0, "d": 1.0, "t_min": 40.0, "t_max": 35.0}"#;
+        assert!(request(address, "POST", "/config", bad_config).starts_with("HTTP/1.1 400"));
+        assert!(request(address, "POST", "/setpoint", "warm").starts_with("HTTP/1.1 400"));
+        assert!(request(address, "GET", "/nope", "").starts_with("HTTP/1.1 404"));
+        assert!(command_rx.try_recv().is_err());
+
+        assert!(request(address, "POST", "/experiment/stop", "").starts_with("HTTP/1.1 409"));
+        assert!(request(address, "POST", "/experiment/start", "").starts_with("HTTP/1.1 202"));
+        assert_eq!(experiment_rx.try_recv(), Ok(ExperimentRequest::Start));
+        *experiment.lock().unwrap() = ExperimentState::Running;
+        assert!(request(address, "GET", "/experiment", "").ends_with(r#"{"state":"running"}"#));
+        assert!(request(address, "POST", "/experiment/start", "").starts_with("HTTP/1.1 409"));
+        assert!(request(address, "POST", "/setpoint", r#"{"t_set": 30.0}"#).starts_with("HTTP/1.1 409"));
+        assert!(request(address, "POST", "/experiment/stop", "").starts_with("HTTP/1.1 202"));
+        assert_eq!(experiment_rx.try_recv(), Ok(ExperimentRequest::Stop));
+        *experiment.lock().unwrap() = ExperimentState::Failed("timed out".to_string());
+        let response = request(address, "GET", "/experiment", "");
+        assert!(response.ends_with(r#"{"state":"failed","error":"timed out"}"#), "{}", response);
+        assert!(command_rx.try_recv().is_err());
+    }
+}
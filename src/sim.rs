@@ -0,0 +1,220 @@
+use crate::tec::{SerialLink, TecConfig};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// Temperature the simulated sample drifts to while the TEC is off
+const AMBIENT_TEMP: f32 = 25.0;
+
+/// Time constant of the simulated sample while the TEC is driving it
+const DEFAULT_TIME_CONSTANT: Duration = Duration::from_secs(8);
+
+/// Passive cooling is this many times slower than active control
+const PASSIVE_SLOWDOWN: f32 = 4.0;
+
+/// Software stand-in for the TEC driver.
+///
+/// Speaks the same serial protocol as the device: single-character commands
+/// are acknowledged as `<c>`, `o` is followed by a readout line and a
+/// `<t p i d tmin tmax>` configuration is echoed back. The measured
+/// temperature follows a first-order response towards the setpoint while the
+/// TEC is enabled and towards ambient while it is off.
+pub struct SimulatedLink {
+    config: TecConfig,
+    enabled: bool,
+    t_measured: f32,
+    time_constant: Duration,
+    last_update: Instant,
+    /// Partial `<...>` configuration command
+    pending_command: Option<String>,
+    outgoing: VecDeque<u8>,
+}
+
+impl SerialLink for SimulatedLink {
+    /// The model answers as soon as it is written to
+    fn settle_time(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+impl Default for SimulatedLink {
+    fn default() -> Self {
+        SimulatedLink::new()
+    }
+}
+
+impl SimulatedLink {
+    pub fn new() -> Self {
+        SimulatedLink::with_time_constant(DEFAULT_TIME_CONSTANT)
+    }
+
+    pub fn with_time_constant(time_constant: Duration) -> Self {
+        SimulatedLink {
+            config: TecConfig::default(),
+            enabled: false,
+            t_measured: AMBIENT_TEMP,
+            time_constant,
+            last_update: Instant::now(),
+            pending_command: None,
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    /// Advance the thermal model to now
+    fn update(&mut self) {
+        let dt = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+
+        let (target, tau) = if self.enabled {
+            let target = self.config.t_set.clamp(self.config.t_min, self.config.t_max);
+            (target, self.time_constant.as_secs_f32())
+        } else {
+            (AMBIENT_TEMP, self.time_constant.as_secs_f32() * PASSIVE_SLOWDOWN)
+        };
+        if tau > 0.0 {
+            self.t_measured += (target - self.t_measured) * (1.0 - (-dt / tau).exp());
+        } else {
+            self.t_measured = target;
+        }
+    }
+
+    fn pwm(&self) -> f32 {
+        if self.enabled {
+            (self.config.p * (self.config.t_set - self.t_measured)).clamp(-100.0, 100.0)
+        } else {
+            0.0
+        }
+    }
+
+    fn readout_line(&self) -> String {
+        let pwm = self.pwm();
+        format!(
+            "Tz={:+.2} P={:5.2} I={:5.2} D={:5.2} T={:3.0}...{:+.0} Tr={:+.2} OC=0 PW={}{:3.0}",
+            self.config.t_set,
+            self.config.p,
+            self.config.i,
+            self.config.d,
+            self.config.t_min,
+            self.config.t_max,
+            self.t_measured,
+            if pwm < 0.0 { '-' } else { '+' },
+            pwm.abs()
+        )
+    }
+
+    fn reply(&mut self, line: &str) {
+        self.outgoing.extend(line.as_bytes());
+        self.outgoing.extend(b"\r\n");
+    }
+
+    fn handle_command(&mut self, command: char) {
+        self.update();
+        match command {
+            'A' => self.enabled = true,
+            'a' => self.enabled = false,
+            // Cyclic printing is acknowledged but not simulated
+            'o' | 'R' | 'r' => {}
+            _ => return,
+        }
+        self.reply(&format!("<{}>", command));
+        if command == 'o' {
+            let line = self.readout_line();
+            self.reply(&line);
+        }
+    }
+
+    fn handle_configuration(&mut self, body: &str) {
+        let values: Vec<f32> = body
+            .split_whitespace()
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        if let [t_set, p, i, d, t_min, t_max] = values[..] {
+            self.update();
+            self.config = TecConfig { t_set, p, i, d, t_min, t_max };
+        }
+        let ack = format!(
+            "eTzc={:.2} eKp={:.2} eKi={:.2} eKd={:.2} eTmin={:.2} eTmax={:.2}",
+            self.config.t_set,
+            self.config.p,
+            self.config.i,
+            self.config.d,
+            self.config.t_min,
+            self.config.t_max
+        );
+        self.reply(&ack);
+    }
+}
+
+impl Read for SimulatedLink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.outgoing.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no data"));
+        }
+        let n = buf.len().min(self.outgoing.len());
+        for (slot, byte) in buf.iter_mut().zip(self.outgoing.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SimulatedLink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            let c = byte as char;
+            match (&mut self.pending_command, c) {
+                (None, '<') => self.pending_command = Some(String::new()),
+                (Some(_), '>') => {
+                    let body = self.pending_command.take().unwrap_or_default();
+                    self.handle_configuration(&body);
+                }
+                (Some(body), _) => body.push(c),
+                (None, _) => self.handle_command(c),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tec::TecController;
+    use std::thread;
+
+    #[test]
+    fn test_simulated_controller_tracks_setpoint() {
+        let link = SimulatedLink::with_time_constant(Duration::from_millis(50));
+        let mut controller = TecController::with_link(link).unwrap();
+
+        let idle = controller.get_single_readout().unwrap();
+        assert!((idle.t_measured - AMBIENT_TEMP).abs() < 0.01);
+        assert_eq!(idle.t_set, 20.0);
+
+        controller.enable().unwrap();
+        controller.set_t(30.0).unwrap();
+        thread::sleep(Duration::from_millis(500));
+
+        let readout = controller.get_single_readout().unwrap();
+        assert_eq!(readout.t_set, 30.0);
+        assert!(readout.t_set - readout.t_measured < 0.5, "{:?}", readout);
+    }
+
+    #[test]
+    fn test_readout_respects_limits() {
+        let mut link = SimulatedLink::with_time_constant(Duration::ZERO);
+        link.write_all(b"<50 5.5 2.5 0.5 0 35>A").unwrap();
+        link.write_all(b"o").unwrap();
+
+        let response = String::from_utf8(link.outgoing.drain(..).collect()).unwrap();
+        let lines: Vec<&str> = response.lines().collect();
+        assert_eq!(lines[0], "eTzc=50.00 eKp=5.50 eKi=2.50 eKd=0.50 eTmin=0.00 eTmax=35.00");
+        assert_eq!(lines[1], "<A>");
+        assert_eq!(lines[2], "<o>");
+        assert!(lines[3].contains("Tr=+35.00"), "{}", lines[3]);
+    }
+}
@@ -0,0 +1,115 @@
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+use std::sync::atomic::AtomicU64;
+
+/// Set once the pre-epoch clock has been reported
+static CLOCK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Source of wall-clock time for logs and phase timings, swapped for a
+/// `MockClock` in tests
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch
+    fn now_ms(&self) -> u64;
+}
+
+/// The system clock, via `now_ms`
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        now_ms()
+    }
+}
+
+/// Milliseconds since the Unix epoch, or 0 if the system clock is set
+/// before it, as on a Pi without an RTC that has not synced yet; that is
+/// logged the first time rather than panicking mid-run
+pub fn now_ms() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch.as_millis() as u64,
+        Err(_) => {
+            if !CLOCK_WARNED.swap(true, Ordering::Relaxed) {
+                warn!("System clock is set before 1970; timestamps will read 0 until it is corrected");
+            }
+            0
+        }
+    }
+}
+
+/// Seconds since the Unix epoch that only move forward: the wall-clock time
+/// when created plus monotonic time since, so a clock step while running
+/// cannot reorder chart samples
+pub struct MonotonicSecs {
+    origin_s: f64,
+    origin: Instant,
+}
+
+impl MonotonicSecs {
+    pub fn start() -> Self {
+        MonotonicSecs {
+            origin_s: now_ms() as f64 / 1000.0,
+            origin: Instant::now(),
+        }
+    }
+
+    pub fn now(&self) -> f64 {
+        self.origin_s + self.origin.elapsed().as_secs_f64()
+    }
+}
+
+/// Seconds from `start_ms` to `end_ms`; zero rather than an underflow if the
+/// clock was stepped back in between
+pub fn elapsed_s(start_ms: u64, end_ms: u64) -> f64 {
+    end_ms.saturating_sub(start_ms) as f64 / 1000.0
+}
+
+/// Clock that only moves when told to
+#[cfg(test)]
+pub struct MockClock(AtomicU64);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now_ms: u64) -> Self {
+        MockClock(AtomicU64::new(now_ms))
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.0.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_s() {
+        assert_eq!(elapsed_s(1_000, 3_500), 2.5);
+        assert_eq!(elapsed_s(5_000, 4_000), 0.0);
+
+        let clock = MockClock::new(1_700_000_000_000);
+        let start = clock.now_ms();
+        clock.advance(1_250);
+        assert_eq!(elapsed_s(start, clock.now_ms()), 1.25);
+        assert!(SystemClock.now_ms() > 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_monotonic_secs() {
+        let clock = MonotonicSecs::start();
+        let first = clock.now();
+        assert!((first - now_ms() as f64 / 1000.0).abs() < 1.0);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(clock.now() >= first + 0.02);
+    }
+}
@@ -1,8 +1,3 @@
-use std::process;
-
-mod tec;
-
-
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -16,37 +11,68 @@ use ratatui::{
     symbols,
     text::{Line, Span},
     widgets::{
-        Axis, Block, Borders, Chart, Clear, Dataset, GraphType, List, ListItem, ListState,
-        Paragraph, Wrap,
+        Axis, Block, Borders, Chart, Clear, Dataset, Gauge, GraphType, List, ListItem, ListState,
+        Paragraph,
     },
 };
 use std::{
     collections::VecDeque,
     error::Error,
-    io,
-    sync::{
-        Arc, Mutex,
-        mpsc::{self, Receiver, Sender},
-    },
-    thread,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    fs::File,
+    io::{self, Write},
+    time::{Duration, Instant},
 };
 
-// Import your TEC controller code
-use crate::tec::{TecConfig, TecController, TecReadout};
-
-fn main() {
-    if let Err(e) = run_tui() {
-        eprintln!("Error running TUI: {}", e);
-        process::exit(1);
-    }
+use crate::fan::{DEFAULT_FAN_FREQUENCY_HZ, Fan};
+#[cfg(feature = "influx")]
+use crate::influx;
+#[cfg(feature = "mqtt")]
+use crate::mqtt;
+use crate::history::{RollingHistory, TempData, pwm_range, temp_range, time_span};
+use crate::monitor::{DEFAULT_PWM_GRACE, MonitorCommand, MonitorEvent, TecCommand, TecMonitor};
+use crate::replay::ReplaySource;
+use crate::tec::{
+    CYCLIC_PRINT_PERIOD, PID_RANGE, PwmLimit, SetpointBounds, TecConfig, TecError, TecReadout,
+    TemperatureUnit,
+};
+use crate::time::{self, MonotonicSecs};
+
+/// Settings from the `monitor` subcommand
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuiOptions {
+    pub port_name: String,
+    pub read_interval: Duration,
+    /// Broker to publish readouts to, requires the `mqtt` feature
+    pub mqtt_broker: Option<String>,
+    pub mqtt_prefix: Option<String>,
+    /// InfluxDB write URL, requires the `influx` feature
+    pub influx_url: Option<String>,
+    pub influx_station: Option<String>,
+    /// Disable the TEC when PWM leaves this range
+    pub pwm_limit: Option<PwmLimit>,
+    /// Soft setpoint limits in place of the controller's defaults
+    pub setpoint_bounds: Option<SetpointBounds>,
+    /// Play back this CSV temperature log instead of opening `port_name`
+    pub replay: Option<String>,
+    /// Playback speed of `replay` relative to the recording
+    pub replay_speed: f64,
+    /// Distance from the setpoint that counts as reached, in °C
+    pub temp_tolerance: f32,
+    /// Show the fan pane, changing the speed by this many percent per key
+    pub fan_step: Option<u8>,
+    /// Reload the chart history from this file and checkpoint it there
+    pub history_file: Option<String>,
 }
-#[derive(Debug, Clone)]
-struct TempData {
-    timestamp: f64,
-    set_temp: f32,
-    measured_temp: f32,
-    pwm: f32,
+
+/// Summary of `measured_temp` over the chart window
+#[derive(Debug, Clone, PartialEq)]
+struct TempStats {
+    min: f32,
+    max: f32,
+    mean: f32,
+    std_dev: f32,
+    /// Latest measured minus set temperature
+    deviation: f32,
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +87,9 @@ struct SetpointChange {
 enum InputMode {
     Normal,
     Editing,
+    NamingPreset,
+    /// Typing the port to reopen the controller on
+    ChangingPort,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -82,6 +111,11 @@ impl EditField {
         }
     }
 
+    /// Whether the field holds a temperature, shown in the display unit
+    fn is_temperature(&self) -> bool {
+        matches!(self, EditField::TSet | EditField::TMin | EditField::TMax)
+    }
+
     fn get_value(&self, config: &TecConfig) -> f32 {
         match self {
             EditField::TSet => config.t_set,
@@ -117,31 +151,52 @@ impl EditField {
     }
 }
 
-// Commands sent from UI thread to worker thread
-enum WorkerCommand {
-    SetConfig(TecConfig),
-    Enable,
-    Disable,
-    Shutdown,
-}
+/// Longest wait for a key press before servicing the monitor again
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-// Data sent from worker thread to UI thread
-enum WorkerResponse {
-    Readout(TecReadout),
-    Error(String),
-    Status(String),
-}
+/// Directory holding saved `TecConfig` presets, one YAML file each
+const PRESET_DIR: &str = "presets";
+
+/// Setpoint step presets for ←/→ nudging, selected with the number keys
+const TEMP_STEP_KEYS: [(char, f32); 4] = [('1', 0.1), ('2', 0.5), ('3', 1.0), ('5', 5.0)];
+
+/// Selectable spans of the temperature and PWM charts, cycled with `[` and `]`
+const CHART_WINDOWS_SECS: [f64; 5] = [60.0, 120.0, 300.0, 900.0, 3600.0];
+
+/// Chart span shown at startup (2 min)
+const DEFAULT_CHART_WINDOW: usize = 1;
+
+/// Narrowest time axis of the charts, so a single readout still gets one
+const MIN_CHART_SPAN_SECS: f64 = 10.0;
+
+/// Narrowest temperature axis, in the display unit
+const MIN_TEMP_SPAN: f64 = 4.0;
+
+/// Labels on a value axis, both ends included
+const VALUE_AXIS_TICKS: usize = 5;
+
+/// Readouts kept in memory: the widest chart window at the default `READ_INTERVAL`
+const HISTORY_CAPACITY: usize = 7200;
+
+/// How often the history is rewritten to `TuiOptions::history_file`
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A checkpoint larger than this is not loaded; a full history is about
+/// a quarter of it
+const MAX_CHECKPOINT_BYTES: u64 = 1024 * 1024;
 
 struct App {
     // Data
     current_readout: Option<TecReadout>,
     current_config: TecConfig,
-    temp_history: VecDeque<TempData>,
+    temp_history: RollingHistory,
 
     // Temperature setpoint tracking
     current_setpoint_change: Option<SetpointChange>,
     setpoint_history: VecDeque<SetpointChange>,
     temp_tolerance: f32,
+    /// Draw the setpoint ± `temp_tolerance` band on the chart
+    show_tolerance_band: bool,
 
     // UI State
     input_mode: InputMode,
@@ -150,16 +205,44 @@ struct App {
     parameter_list_state: ListState,
 
     // Status
-    tec_enabled: bool,
+    port_name: String,
+    /// Set once the monitor has opened the port; cleared while reconnecting
+    connected: bool,
+    /// Reason the link was lost, shown until it comes back
+    disconnect_reason: Option<String>,
+    /// Chart end time while the display is frozen; data keeps buffering
+    paused_at: Option<f64>,
+    /// Index into `CHART_WINDOWS_SECS`
+    chart_window_index: usize,
+    /// Timestamps of `temp_history`, immune to wall-clock steps
+    chart_clock: MonotonicSecs,
     last_update: Instant,
     status_message: Option<String>,
 
-    // Communication channels
-    command_tx: Sender<WorkerCommand>,
-    response_rx: Receiver<WorkerResponse>,
+    // Serial communication runs on the monitor thread
+    monitor: TecMonitor,
+
+    /// PWM fan driven from the fan pane; no pane without one
+    fan: Option<Fan>,
+    /// Speed last set successfully
+    fan_percent: u8,
+    fan_step: u8,
+
+    // History checkpoint
+    history_file: Option<String>,
+    last_checkpoint: Instant,
 
     // Settings
     temp_step: f32,
+    /// Unit temperatures are displayed and typed in; everything sent to the
+    /// controller stays in °C
+    unit: TemperatureUnit,
+
+    // Config presets
+    active_preset: Option<String>,
+    /// Loaded preset differs from what was last sent to the controller
+    preset_unapplied: bool,
+    next_preset: usize,
 
     // Redraw flag
     needs_redraw: bool,
@@ -170,33 +253,38 @@ struct App {
 }
 
 impl App {
-    fn new(port_name: &str) -> Result<App, Box<dyn Error>> {
-        let (command_tx, command_rx) = mpsc::channel();
-        let (response_tx, response_rx) = mpsc::channel();
-
-        // Spawn worker thread for serial communication
-        let port_name = port_name.to_string();
-        thread::spawn(move || {
-            worker_thread(port_name, command_rx, response_tx);
-        });
-
+    fn new(port_name: &str, monitor: TecMonitor) -> Result<App, Box<dyn Error>> {
         let mut app = App {
             current_readout: None,
             current_config: TecConfig::default(),
-            temp_history: VecDeque::with_capacity(1000),
+            temp_history: RollingHistory::new(HISTORY_CAPACITY),
             current_setpoint_change: None,
             setpoint_history: VecDeque::with_capacity(100),
             temp_tolerance: 0.5,
+            show_tolerance_band: true,
             input_mode: InputMode::Normal,
             edit_field: EditField::TSet,
             edit_value: String::new(),
             parameter_list_state: ListState::default(),
-            tec_enabled: false,
+            port_name: port_name.to_string(),
+            connected: false,
+            disconnect_reason: None,
+            paused_at: None,
+            chart_window_index: DEFAULT_CHART_WINDOW,
+            chart_clock: MonotonicSecs::start(),
             last_update: Instant::now(),
             status_message: None,
-            command_tx,
-            response_rx,
+            monitor,
+            fan: None,
+            fan_percent: 0,
+            fan_step: 0,
+            history_file: None,
+            last_checkpoint: Instant::now(),
             temp_step: 0.5,
+            unit: TemperatureUnit::Celsius,
+            active_preset: None,
+            preset_unapplied: false,
+            next_preset: 0,
             needs_redraw: true,
             pending_config: false,
             last_config_sent: Instant::now(),
@@ -208,65 +296,254 @@ impl App {
 
     fn process_responses(&mut self) {
         // Process all available responses without blocking
-        while let Ok(response) = self.response_rx.try_recv() {
-            match response {
-                WorkerResponse::Readout(readout) => {
-                    let now = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs_f64();
-
-                    self.temp_history.push_back(TempData {
+        while let Some(event) = self.monitor.try_recv_event() {
+            match event {
+                MonitorEvent::Readout(readout) => {
+                    let now = self.chart_clock.now();
+
+                    self.temp_history.push(TempData {
                         timestamp: now,
                         set_temp: readout.t_set,
                         measured_temp: readout.t_measured,
                         pwm: readout.pwm,
                     });
 
-                    if self.temp_history.len() > 1000 {
-                        self.temp_history.pop_front();
-                    }
-
                     self.check_setpoint_reached(readout.t_measured);
                     self.current_readout = Some(readout);
                     self.last_update = Instant::now();
                     self.needs_redraw = true;
                 }
-                WorkerResponse::Error(msg) => {
+                MonitorEvent::Error(msg) => {
                     self.status_message = Some(format!("Error: {}", msg));
                     self.needs_redraw = true;
                 }
-                WorkerResponse::Status(msg) => {
+                MonitorEvent::Status(msg) => {
                     self.status_message = Some(msg);
                     self.needs_redraw = true;
                 }
+                MonitorEvent::Config(config) => {
+                    // An edit made meanwhile is about to replace it anyway
+                    if !self.pending_config {
+                        self.current_config = config;
+                        self.status_message = Some("Configuration read from device".to_string());
+                        self.needs_redraw = true;
+                    }
+                }
+                MonitorEvent::Connected => {
+                    // The controller comes up disabled and may have been
+                    // power-cycled, so restore our configuration after a
+                    // reconnect; on the first connect, start from what the
+                    // device reports
+                    if self.disconnect_reason.take().is_some() {
+                        self.pending_config = true;
+                        self.status_message = Some(format!(
+                            "Reconnected to {}; TEC disabled, press Space to re-enable",
+                            self.port_name
+                        ));
+                    } else if !self.connected {
+                        self.queue(TecCommand::ReadConfig);
+                    }
+                    self.connected = true;
+                    self.needs_redraw = true;
+                }
+                MonitorEvent::PortChanged(port) => {
+                    self.status_message = Some(format!("Switched to {}", port));
+                    self.port_name = port;
+                    self.needs_redraw = true;
+                }
+                MonitorEvent::Disconnected(reason) => {
+                    self.connected = false;
+                    self.current_readout = None;
+                    self.status_message = Some(format!("Disconnected: {}", reason));
+                    self.disconnect_reason = Some(reason);
+                    self.needs_redraw = true;
+                }
             }
         }
     }
 
-    fn check_setpoint_reached(&mut self, measured_temp: f32) {
-        if let Some(ref mut setpoint_change) = self.current_setpoint_change {
-            if setpoint_change.reached_time.is_none() {
-                let temp_diff = (measured_temp - setpoint_change.target_temp).abs();
-                if temp_diff <= self.temp_tolerance {
-                    let now = Instant::now();
-                    let duration = now.duration_since(setpoint_change.start_time);
-                    setpoint_change.reached_time = Some(now);
-                    setpoint_change.duration = Some(duration);
-
-                    self.setpoint_history.push_back(setpoint_change.clone());
-                    if self.setpoint_history.len() > 100 {
-                        self.setpoint_history.pop_front();
-                    }
+    fn toggle_pause(&mut self) {
+        self.paused_at = match self.paused_at {
+            Some(_) => None,
+            None => Some(
+                self.temp_history
+                    .latest()
+                    .map(|data| data.timestamp)
+                    .unwrap_or(0.0),
+            ),
+        };
+        self.needs_redraw = true;
+    }
 
-                    self.status_message = Some(format!(
-                        "Target {:.1}°C reached in {:.1}s",
-                        setpoint_change.target_temp,
-                        duration.as_secs_f32()
-                    ));
+    fn chart_window_secs(&self) -> f64 {
+        CHART_WINDOWS_SECS[self.chart_window_index]
+    }
 
-                    self.current_setpoint_change = None;
+    /// Zoom the charts out (`wider`) or in by one preset
+    fn change_chart_window(&mut self, wider: bool) {
+        self.chart_window_index = if wider {
+            (self.chart_window_index + 1).min(CHART_WINDOWS_SECS.len() - 1)
+        } else {
+            self.chart_window_index.saturating_sub(1)
+        };
+        self.status_message = Some(format!("Chart window: {}", window_label(self.chart_window_secs())));
+        self.needs_redraw = true;
+    }
+
+    /// Samples in the chart window, ending at the pause point while paused
+    fn chart_window(&self) -> Vec<&TempData> {
+        let Some(latest) = self.temp_history.latest() else {
+            return Vec::new();
+        };
+        let end = self.paused_at.unwrap_or(latest.timestamp);
+        self.temp_history.window(end, self.chart_window_secs())
+    }
+
+    /// Alarm conditions tripped by the latest readout, empty when all is well
+    fn active_alarms(&self) -> Vec<String> {
+        let Some(ref readout) = self.current_readout else {
+            return Vec::new();
+        };
+
+        let mut alarms = Vec::new();
+        if readout.oc {
+            alarms.push("OC alarm tripped".to_string());
+        }
+        if readout.t_measured > readout.t_max {
+            alarms.push(format!(
+                "Over temperature: {} > T max {}",
+                self.unit.format(readout.t_measured, 1),
+                self.unit.format(readout.t_max, 1)
+            ));
+        } else if readout.t_measured < readout.t_min {
+            alarms.push(format!(
+                "Under temperature: {} < T min {}",
+                self.unit.format(readout.t_measured, 1),
+                self.unit.format(readout.t_min, 1)
+            ));
+        }
+        alarms
+    }
+
+    fn save_preset(&mut self, name: &str) {
+        self.status_message = Some(match save_preset(PRESET_DIR, name, &self.current_config) {
+            Ok(path) => {
+                self.active_preset = Some(name.to_string());
+                format!("Saved preset to {}", path)
+            }
+            Err(e) => format!("Error: failed to save preset {}: {}", name, e),
+        });
+        self.needs_redraw = true;
+    }
+
+    /// Load the next preset in `PRESET_DIR` without sending it
+    fn load_next_preset(&mut self) {
+        let names = match list_presets(PRESET_DIR) {
+            Ok(names) if !names.is_empty() => names,
+            Ok(_) => {
+                self.status_message = Some(format!("No presets in {}/", PRESET_DIR));
+                self.needs_redraw = true;
+                return;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Error: failed to list presets: {}", e));
+                self.needs_redraw = true;
+                return;
+            }
+        };
+
+        let name = &names[self.next_preset % names.len()];
+        self.next_preset = (self.next_preset + 1) % names.len();
+        self.status_message = Some(match load_preset(PRESET_DIR, name) {
+            Ok(config) => {
+                self.current_config = config;
+                self.pending_config = false;
+                self.active_preset = Some(name.clone());
+                self.preset_unapplied = true;
+                format!("Loaded preset {} - press a to apply", name)
+            }
+            Err(e) => format!("Error: failed to load preset {}: {}", name, e),
+        });
+        self.needs_redraw = true;
+    }
+
+    fn apply_preset(&mut self) {
+        if let Err(e) = self.current_config.validate() {
+            self.status_message = Some(format!("Error: {}", e));
+        } else {
+            self.preset_unapplied = !self.queue(TecCommand::SetConfig(self.current_config.clone()));
+        }
+        self.needs_redraw = true;
+    }
+
+    /// Dump the buffered history to a timestamped CSV in the working directory
+    fn export_history(&mut self) {
+        let secs = time::now_ms() / 1000;
+        let path = format!("tec_history_{}.csv", secs);
+
+        self.status_message = Some(match write_history_csv(&path, &self.temp_history) {
+            Ok(()) => format!("Saved {} readouts to {}", self.temp_history.len(), path),
+            Err(e) => format!("Error: failed to write {}: {}", path, e),
+        });
+        self.needs_redraw = true;
+    }
+
+    /// Load the chart history checkpointed at `path` by an earlier session,
+    /// then keep checkpointing there. A missing file just starts empty.
+    fn restore_history(&mut self, path: &str) {
+        self.history_file = Some(path.to_string());
+        self.status_message = match read_history_csv(path, HISTORY_CAPACITY, time::now_ms() as f64 / 1000.0) {
+            Ok(history) => {
+                let message = format!("Restored {} readouts from {}", history.len(), path);
+                self.temp_history = history;
+                Some(message)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => Some(format!("Not restoring {}: {}", path, e)),
+        };
+    }
+
+    /// Rewrite the history file once `CHECKPOINT_INTERVAL` has passed, or
+    /// now if `force`. The file is replaced in one rename, so a crash
+    /// mid-write leaves the previous checkpoint.
+    fn checkpoint_history(&mut self, force: bool) {
+        let Some(ref path) = self.history_file else {
+            return;
+        };
+        if !force && self.last_checkpoint.elapsed() < CHECKPOINT_INTERVAL {
+            return;
+        }
+        self.last_checkpoint = Instant::now();
+        let partial = format!("{}.tmp", path);
+        if let Err(e) = write_history_csv(&partial, &self.temp_history).and_then(|()| std::fs::rename(&partial, path)) {
+            self.status_message = Some(format!("Error: failed to checkpoint history to {}: {}", path, e));
+            self.needs_redraw = true;
+        }
+    }
+
+    fn check_setpoint_reached(&mut self, measured_temp: f32) {
+        if let Some(ref mut setpoint_change) = self.current_setpoint_change
+            && setpoint_change.reached_time.is_none()
+        {
+            let temp_diff = (measured_temp - setpoint_change.target_temp).abs();
+            if temp_diff <= self.temp_tolerance {
+                let now = Instant::now();
+                let duration = now.duration_since(setpoint_change.start_time);
+                setpoint_change.reached_time = Some(now);
+                setpoint_change.duration = Some(duration);
+
+                self.setpoint_history.push_back(setpoint_change.clone());
+                if self.setpoint_history.len() > 100 {
+                    self.setpoint_history.pop_front();
                 }
+
+                self.status_message = Some(format!(
+                    "Target {} reached in {:.1}s",
+                    self.unit.format(setpoint_change.target_temp, 1),
+                    duration.as_secs_f32()
+                ));
+
+                self.current_setpoint_change = None;
             }
         }
     }
@@ -288,6 +565,18 @@ impl App {
         }
     }
 
+    /// Hand a command to the monitor thread, which runs it in order and
+    /// answers with a status or error event; false, with the reason in the
+    /// status bar, if the thread has gone
+    fn queue(&mut self, command: impl Into<MonitorCommand>) -> bool {
+        if self.monitor.send(command) {
+            return true;
+        }
+        self.status_message = Some("Error: monitor stopped; command not sent".to_string());
+        self.needs_redraw = true;
+        false
+    }
+
     fn apply_configuration(&mut self) {
         // Mark config as pending instead of sending immediately
         // Reset the timer - we want to wait from the LAST change
@@ -302,94 +591,129 @@ impl App {
         if self.pending_config
             && self.last_config_sent.elapsed() >= Duration::from_millis(DEBOUNCE_MS)
         {
-            let _ = self
-                .command_tx
-                .send(WorkerCommand::SetConfig(self.current_config.clone()));
+            self.queue(TecCommand::SetConfig(self.current_config.clone()));
             self.pending_config = false;
         }
     }
 
+    /// Ask the monitor to flip the supply; the header follows once the
+    /// controller acknowledges
     fn toggle_tec(&mut self) {
-        let command = if self.tec_enabled {
-            WorkerCommand::Disable
+        let (command, action) = if self.monitor.is_enabled() {
+            (TecCommand::Disable, "Disabling")
         } else {
-            WorkerCommand::Enable
+            (TecCommand::Enable, "Enabling")
         };
 
-        if self.command_tx.send(command).is_ok() {
-            self.tec_enabled = !self.tec_enabled;
-            self.status_message = Some(format!(
-                "TEC {}",
-                if self.tec_enabled {
-                    "ENABLED"
-                } else {
-                    "DISABLED"
-                }
-            ));
+        if self.queue(command) {
+            self.status_message = Some(format!("{} TEC...", action));
+            self.needs_redraw = true;
+        }
+    }
+
+    fn clear_integrator(&mut self) {
+        if self.queue(TecCommand::ClearIntegrator) {
+            self.status_message = Some("Clearing integrator...".to_string());
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Switch between the cyclic print stream and polling; the footer
+    /// follows once the controller acknowledges
+    /// Reopen the controller on `port`, or on the current port if empty
+    fn change_port(&mut self, port: String) {
+        let new_port = (!port.is_empty()).then_some(port);
+        let target = new_port.clone().unwrap_or_else(|| self.port_name.clone());
+        if self.queue(MonitorCommand::ChangePort(new_port)) {
+            self.status_message = Some(format!("Reopening {}...", target));
+            self.needs_redraw = true;
+        }
+    }
+
+    fn toggle_streaming(&mut self) {
+        let (on, action) = if self.monitor.is_streaming() {
+            (false, "Stopping stream")
+        } else {
+            (true, "Starting stream")
+        };
+
+        if self.queue(MonitorCommand::SetStreaming(on)) {
+            self.status_message = Some(format!("{}...", action));
             self.needs_redraw = true;
         }
     }
 
+    /// Hand the fan pane its fan, starting from the speed it runs at now
+    fn attach_fan(&mut self, fan: Fan, step: u8) {
+        self.fan_percent = fan.get_speed_percent().unwrap_or(0);
+        self.fan_step = step;
+        self.fan = Some(fan);
+    }
+
+    /// Step the fan speed up or down; ignored without a fan
+    fn adjust_fan(&mut self, up: bool) {
+        let Some(ref fan) = self.fan else { return };
+        let percent = step_fan_percent(self.fan_percent, self.fan_step, up);
+        self.status_message = Some(match fan.set_speed_percent(percent) {
+            Ok(()) => {
+                self.fan_percent = percent;
+                format!("Fan: {}%", percent)
+            }
+            Err(e) => format!("Error: failed to set fan speed to {}%: {}", percent, e),
+        });
+        self.needs_redraw = true;
+    }
+
     fn increment_selected_field(&mut self) {
         let field = self.edit_field;
         let step = field.get_step(self.temp_step);
-        let current = field.get_value(&self.current_config);
-        let new_value = current + step;
-
-        match field {
-            EditField::TSet => self.set_new_temperature(new_value),
-            EditField::P => {
-                self.current_config.p = new_value;
-                self.apply_configuration();
-            }
-            EditField::I => {
-                self.current_config.i = new_value;
-                self.apply_configuration();
-            }
-            EditField::D => {
-                self.current_config.d = new_value;
-                self.apply_configuration();
-            }
-            EditField::TMin => {
-                self.current_config.t_min = new_value.max(0.0);
-                self.apply_configuration();
-            }
-            EditField::TMax => {
-                self.current_config.t_max = new_value.min(100.0);
-                self.apply_configuration();
-            }
-        }
+        let new_value = field.get_value(&self.current_config) + step;
+        self.nudge_field(field, new_value);
     }
 
     fn decrement_selected_field(&mut self) {
         let field = self.edit_field;
         let step = field.get_step(self.temp_step);
-        let current = field.get_value(&self.current_config);
-        let new_value = current - step;
+        let new_value = field.get_value(&self.current_config) - step;
+        self.nudge_field(field, new_value);
+    }
 
-        match field {
-            EditField::TSet => self.set_new_temperature(new_value),
-            EditField::P => {
-                self.current_config.p = new_value;
-                self.apply_configuration();
+    /// Apply an arrow-key adjustment, holding gains at the ends of their range
+    fn nudge_field(&mut self, field: EditField, value: f32) {
+        let value = match field {
+            EditField::P | EditField::I | EditField::D => {
+                value.clamp(*PID_RANGE.start(), *PID_RANGE.end())
             }
-            EditField::I => {
-                self.current_config.i = new_value;
-                self.apply_configuration();
-            }
-            EditField::D => {
-                self.current_config.d = new_value;
-                self.apply_configuration();
-            }
-            EditField::TMin => {
-                self.current_config.t_min = new_value.max(0.0);
-                self.apply_configuration();
-            }
-            EditField::TMax => {
-                self.current_config.t_max = new_value.min(100.0);
-                self.apply_configuration();
+            EditField::TMin => value.max(0.0),
+            EditField::TMax => value.min(100.0),
+            EditField::TSet => value,
+        };
+        if let Err(e) = self.set_field(field, value) {
+            self.status_message = Some(format!("Error: {}", e));
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Validate and apply a new value for `field`, leaving the config
+    /// untouched if the result would be rejected by the controller
+    fn set_field(&mut self, field: EditField, value: f32) -> Result<(), TecError> {
+        let mut config = self.current_config.clone();
+        match field {
+            EditField::TSet => {
+                self.set_new_temperature(value);
+                return Ok(());
             }
+            EditField::P => config.p = value,
+            EditField::I => config.i = value,
+            EditField::D => config.d = value,
+            EditField::TMin => config.t_min = value,
+            EditField::TMax => config.t_max = value,
         }
+        config.validate()?;
+
+        self.current_config = config;
+        self.apply_configuration();
+        Ok(())
     }
 
     fn handle_key_input(&mut self, key: KeyCode) {
@@ -442,57 +766,101 @@ impl App {
                 KeyCode::Char(' ') => {
                     self.toggle_tec();
                 }
-                KeyCode::Char('1') => {
-                    self.temp_step = 0.1;
-                    self.status_message = Some("Step: 0.1°C".to_string());
+                KeyCode::Char('p') => {
+                    self.toggle_pause();
+                }
+                KeyCode::Char('z') => {
+                    self.clear_integrator();
+                }
+                KeyCode::Char('c') => {
+                    self.toggle_streaming();
+                }
+                KeyCode::Char('s') => {
+                    self.export_history();
+                }
+                KeyCode::Char('w') => {
+                    self.input_mode = InputMode::NamingPreset;
+                    self.edit_value.clear();
                     self.needs_redraw = true;
                 }
-                KeyCode::Char('2') => {
-                    self.temp_step = 0.5;
-                    self.status_message = Some("Step: 0.5°C".to_string());
+                KeyCode::Char('o') => {
+                    self.input_mode = InputMode::ChangingPort;
+                    self.edit_value.clear();
                     self.needs_redraw = true;
                 }
-                KeyCode::Char('3') => {
-                    self.temp_step = 1.0;
-                    self.status_message = Some("Step: 1.0°C".to_string());
+                KeyCode::Char('l') => {
+                    self.load_next_preset();
+                }
+                KeyCode::Char('a') => {
+                    self.apply_preset();
+                }
+                KeyCode::Char('u') => {
+                    self.unit = self.unit.next();
+                    self.status_message = Some(format!("Display unit: {}", self.unit.symbol()));
                     self.needs_redraw = true;
                 }
-                KeyCode::Char('5') => {
-                    self.temp_step = 5.0;
-                    self.status_message = Some("Step: 5.0°C".to_string());
+                KeyCode::Char(']') => {
+                    self.change_chart_window(true);
+                }
+                KeyCode::Char('b') => {
+                    self.show_tolerance_band = !self.show_tolerance_band;
+                    self.status_message = Some(format!(
+                        "Tolerance band {}",
+                        if self.show_tolerance_band { "shown" } else { "hidden" }
+                    ));
                     self.needs_redraw = true;
                 }
+                KeyCode::Char('>') | KeyCode::Char('.') => {
+                    self.adjust_fan(true);
+                }
+                KeyCode::Char('<') | KeyCode::Char(',') => {
+                    self.adjust_fan(false);
+                }
+                KeyCode::Char('[') => {
+                    self.change_chart_window(false);
+                }
+                KeyCode::Char(c) => {
+                    if let Some(&(_, step)) = TEMP_STEP_KEYS.iter().find(|&&(key, _)| key == c) {
+                        self.temp_step = step;
+                        self.status_message = Some(format!("Step: {}", format_delta(self.unit, step)));
+                        self.needs_redraw = true;
+                    }
+                }
                 _ => {}
             },
-            InputMode::Editing => match key {
+            InputMode::Editing | InputMode::NamingPreset | InputMode::ChangingPort => match key {
                 KeyCode::Enter => {
-                    if let Ok(value) = self.edit_value.parse::<f32>() {
-                        let field = self.edit_field;
-                        match field {
-                            EditField::TSet => self.set_new_temperature(value),
-                            EditField::P => {
-                                self.current_config.p = value;
-                                self.apply_configuration();
-                            }
-                            EditField::I => {
-                                self.current_config.i = value;
-                                self.apply_configuration();
-                            }
-                            EditField::D => {
-                                self.current_config.d = value;
-                                self.apply_configuration();
-                            }
-                            EditField::TMin => {
-                                self.current_config.t_min = value.max(0.0);
-                                self.apply_configuration();
+                    if self.input_mode == InputMode::ChangingPort {
+                        self.change_port(self.edit_value.trim().to_string());
+                    } else if self.input_mode == InputMode::NamingPreset {
+                        let name = self.edit_value.trim().to_string();
+                        if is_valid_preset_name(&name) {
+                            self.save_preset(&name);
+                        } else {
+                            self.status_message = Some(
+                                "Preset names may only use letters, digits, - and _".to_string(),
+                            );
+                        }
+                    } else {
+                        match check_edit_value(
+                            &self.edit_value,
+                            self.edit_field,
+                            self.unit,
+                            &self.current_config,
+                            self.monitor.setpoint_bounds(),
+                        ) {
+                            Ok(value) => {
+                                if let Err(e) = self.set_field(self.edit_field, value) {
+                                    self.status_message = Some(format!("Error: {}", e));
+                                }
                             }
-                            EditField::TMax => {
-                                self.current_config.t_max = value.min(100.0);
-                                self.apply_configuration();
+                            // Keep the popup and the text so the entry can be fixed
+                            Err(reason) => {
+                                self.status_message = Some(format!("Invalid value: {}", reason));
+                                self.needs_redraw = true;
+                                return;
                             }
                         }
-                    } else {
-                        self.status_message = Some("Invalid value".to_string());
                     }
                     self.input_mode = InputMode::Normal;
                     self.edit_value.clear();
@@ -517,80 +885,6 @@ impl App {
     }
 }
 
-// Worker thread that handles all serial communication
-fn worker_thread(
-    port_name: String,
-    command_rx: Receiver<WorkerCommand>,
-    response_tx: Sender<WorkerResponse>,
-) {
-    let mut controller = match TecController::new(&port_name) {
-        Ok(ctrl) => ctrl,
-        Err(e) => {
-            let _ = response_tx.send(WorkerResponse::Error(format!("Failed to open port: {}", e)));
-            return;
-        }
-    };
-
-    let mut last_read = Instant::now();
-    let read_interval = Duration::from_millis(500);
-
-    loop {
-        // Check for commands (non-blocking)
-        if let Ok(command) = command_rx.try_recv() {
-            match command {
-                WorkerCommand::SetConfig(config) => match controller.set_configuration(&config) {
-                    Ok(_) => {
-                        let _ =
-                            response_tx.send(WorkerResponse::Status("Config updated".to_string()));
-                    }
-                    Err(e) => {
-                        let _ =
-                            response_tx.send(WorkerResponse::Error(format!("Config error: {}", e)));
-                    }
-                },
-                WorkerCommand::Enable => match controller.enable() {
-                    Ok(_) => {
-                        let _ = response_tx.send(WorkerResponse::Status("TEC ENABLED".to_string()));
-                    }
-                    Err(e) => {
-                        let _ =
-                            response_tx.send(WorkerResponse::Error(format!("Enable error: {}", e)));
-                    }
-                },
-                WorkerCommand::Disable => match controller.disable() {
-                    Ok(_) => {
-                        let _ =
-                            response_tx.send(WorkerResponse::Status("TEC DISABLED".to_string()));
-                    }
-                    Err(e) => {
-                        let _ = response_tx
-                            .send(WorkerResponse::Error(format!("Disable error: {}", e)));
-                    }
-                },
-                WorkerCommand::Shutdown => {
-                    break;
-                }
-            }
-        }
-
-        // Periodic data reading
-        if last_read.elapsed() >= read_interval {
-            match controller.get_single_readout() {
-                Ok(readout) => {
-                    let _ = response_tx.send(WorkerResponse::Readout(readout));
-                }
-                Err(e) => {
-                    let _ = response_tx.send(WorkerResponse::Error(format!("Read error: {}", e)));
-                }
-            }
-            last_read = Instant::now();
-        }
-
-        // Small sleep to prevent busy-waiting
-        thread::sleep(Duration::from_millis(10));
-    }
-}
-
 // UI rendering functions (unchanged)
 fn ui(f: &mut Frame, app: &mut App) {
     let main_chunks = Layout::default()
@@ -609,13 +903,24 @@ fn ui(f: &mut Frame, app: &mut App) {
         .constraints([Constraint::Length(35), Constraint::Min(20)])
         .split(main_chunks[1]);
 
+    // The fan pane only exists with a fan to drive
+    let fan_height = if app.fan.is_some() { 3 } else { 0 };
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(9), Constraint::Min(8)])
+        .constraints([
+            Constraint::Length(9),
+            Constraint::Length(fan_height),
+            Constraint::Length(7),
+            Constraint::Min(8),
+        ])
         .split(content_chunks[0]);
 
     render_current_readout(f, app, left_chunks[0]);
-    render_parameters(f, app, left_chunks[1]);
+    if app.fan.is_some() {
+        render_fan(f, app, left_chunks[1]);
+    }
+    render_statistics(f, app, left_chunks[2]);
+    render_parameters(f, app, left_chunks[3]);
 
     // Right side: Split into temp chart and PWM chart
     let right_chunks = Layout::default()
@@ -631,17 +936,26 @@ fn ui(f: &mut Frame, app: &mut App) {
 
     render_footer(f, app, main_chunks[2]);
 
-    if app.input_mode == InputMode::Editing {
+    if app.input_mode != InputMode::Normal {
         render_edit_popup(f, app);
     }
 }
 
 fn render_header(f: &mut Frame, app: &App, area: Rect) {
-    let tec_status = if app.tec_enabled { "ON" } else { "OFF" };
-    let tec_color = if app.tec_enabled {
-        Color::Green
+    let alarms = app.active_alarms();
+    if !alarms.is_empty() {
+        render_alarm_banner(f, &alarms, area);
+        return;
+    }
+
+    let (tec_status, tec_color) = if app.disconnect_reason.is_some() {
+        ("DISCONNECTED - reconnecting", Color::Red)
+    } else if !app.connected {
+        ("CONNECTING", Color::Yellow)
+    } else if app.monitor.is_enabled() {
+        ("ON", Color::Green)
     } else {
-        Color::Red
+        ("OFF", Color::Red)
     };
 
     let mut title_text = vec![
@@ -656,20 +970,21 @@ fn render_header(f: &mut Frame, app: &App, area: Rect) {
             tec_status,
             Style::default().fg(tec_color).add_modifier(Modifier::BOLD),
         ),
+        Span::raw(format!(" │ {}", app.port_name)),
     ];
 
     if let Some(ref readout) = app.current_readout {
         title_text.extend(vec![
             Span::raw(" │ "),
             Span::styled(
-                format!("{:.1}°C", readout.t_measured),
+                app.unit.format(readout.t_measured, 1),
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" → "),
             Span::styled(
-                format!("{:.1}°C", readout.t_set),
+                app.unit.format(readout.t_set, 1),
                 Style::default().fg(Color::Magenta),
             ),
         ]);
@@ -682,6 +997,30 @@ fn render_header(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(header, area);
 }
 
+/// Flashing red banner in place of the header while an alarm is active
+fn render_alarm_banner(f: &mut Frame, alarms: &[String], area: Rect) {
+    let flash_on = (time::now_ms() / 500).is_multiple_of(2);
+    let style = if flash_on {
+        Style::default()
+            .fg(Color::White)
+            .bg(Color::Red)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    };
+
+    let banner = Paragraph::new(format!("⚠ ALARM: {}", alarms.join(" │ ")))
+        .style(style)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red)),
+        )
+        .alignment(Alignment::Center);
+
+    f.render_widget(banner, area);
+}
+
 fn render_current_readout(f: &mut Frame, app: &App, area: Rect) {
     let content = if let Some(ref readout) = app.current_readout {
         let temp_diff = readout.t_measured - readout.t_set;
@@ -704,21 +1043,21 @@ fn render_current_readout(f: &mut Frame, app: &App, area: Rect) {
             Line::from(vec![
                 Span::raw("Measured: "),
                 Span::styled(
-                    format!("{:.2}°C", readout.t_measured),
+                    app.unit.format(readout.t_measured, 2),
                     Style::default().fg(temp_color).add_modifier(Modifier::BOLD),
                 ),
             ]),
             Line::from(vec![
                 Span::raw("Target:   "),
                 Span::styled(
-                    format!("{:.2}°C", readout.t_set),
+                    app.unit.format(readout.t_set, 2),
                     Style::default().fg(Color::Magenta),
                 ),
             ]),
             Line::from(vec![
                 Span::raw("Error:    "),
                 Span::styled(
-                    format!("{:+.2}°C", temp_diff),
+                    format!("{:+.2}{}", app.unit.convert_delta(temp_diff), app.unit.symbol()),
                     Style::default().fg(temp_color),
                 ),
             ]),
@@ -734,12 +1073,8 @@ fn render_current_readout(f: &mut Frame, app: &App, area: Rect) {
             Line::from(vec![
                 Span::raw("OC:       "),
                 Span::styled(
-                    if readout.oc {
-                        "Connected"
-                    } else {
-                        "Disconnected"
-                    },
-                    Style::default().fg(if readout.oc { Color::Green } else { Color::Red }),
+                    if readout.oc { "ALARM" } else { "OK" },
+                    Style::default().fg(if readout.oc { Color::Red } else { Color::Green }),
                 ),
             ]),
         ]
@@ -757,8 +1092,64 @@ fn render_current_readout(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(readout_widget, area);
 }
 
+fn render_fan(f: &mut Frame, app: &App, area: Rect) {
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .title(format!("Fan (</> ±{}%)", app.fan_step))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Gray)),
+        )
+        .gauge_style(Style::default().fg(Color::Cyan))
+        .percent(app.fan_percent.min(100) as u16)
+        .label(format!("{}%", app.fan_percent));
+
+    f.render_widget(gauge, area);
+}
+
+fn render_statistics(f: &mut Frame, app: &App, area: Rect) {
+    let stats = temp_stats(&app.chart_window());
+    let unit = app.unit;
+    // Min, max and mean are temperatures; std dev and deviation are
+    // differences, and only the deviation from the setpoint has a sign
+    enum Stat {
+        Temperature,
+        Spread,
+        Deviation,
+    }
+    let row = |label: &str, value: Option<f32>, stat: Stat| {
+        let text = match (value, stat) {
+            (Some(v), Stat::Temperature) => unit.format(v, 3),
+            (Some(v), Stat::Spread) => format!("{:.3}{}", unit.convert_delta(v), unit.symbol()),
+            (Some(v), Stat::Deviation) => format!("{:+.3}{}", unit.convert_delta(v), unit.symbol()),
+            (None, _) => "--".to_string(),
+        };
+        Line::from(vec![
+            Span::raw(format!("{:<10}", label)),
+            Span::styled(text, Style::default().fg(Color::White)),
+        ])
+    };
+
+    let content = vec![
+        row("Min:", stats.as_ref().map(|s| s.min), Stat::Temperature),
+        row("Max:", stats.as_ref().map(|s| s.max), Stat::Temperature),
+        row("Mean:", stats.as_ref().map(|s| s.mean), Stat::Temperature),
+        row("Std dev:", stats.as_ref().map(|s| s.std_dev), Stat::Spread),
+        row("Deviation:", stats.as_ref().map(|s| s.deviation), Stat::Deviation),
+    ];
+
+    let stats_widget = Paragraph::new(content).block(
+        Block::default()
+            .title(format!("Statistics ({})", window_label(app.chart_window_secs())))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Gray)),
+    );
+
+    f.render_widget(stats_widget, area);
+}
+
 fn render_parameters(f: &mut Frame, app: &mut App, area: Rect) {
-    let parameter_items: Vec<ListItem> = vec![
+    let parameter_items: Vec<ListItem> = [
         EditField::TSet,
         EditField::P,
         EditField::I,
@@ -774,7 +1165,14 @@ fn render_parameters(f: &mut Frame, app: &mut App, area: Rect) {
 
         let text = match field {
             EditField::TSet | EditField::TMin | EditField::TMax => {
-                format!("{:<10} {:>7.1}°C  ±{:.1}", label, value, step)
+                let unit = app.unit;
+                format!(
+                    "{:<10} {:>7.1}{:<3}±{:.1}",
+                    label,
+                    unit.convert(value),
+                    unit.symbol(),
+                    unit.convert_delta(step)
+                )
             }
             _ => {
                 format!("{:<10} {:>7.3}     ±{:.2}", label, value, step)
@@ -805,6 +1203,7 @@ fn render_parameters(f: &mut Frame, app: &mut App, area: Rect) {
 }
 
 fn render_chart(f: &mut Frame, app: &App, area: Rect) {
+    let window = window_label(app.chart_window_secs());
     if app.temp_history.is_empty() {
         let no_data = Paragraph::new("Collecting data...")
             .block(
@@ -817,23 +1216,14 @@ fn render_chart(f: &mut Frame, app: &App, area: Rect) {
         return;
     }
 
-    const HISTORY_WINDOW_SECS: f64 = 120.0; // Show last 2 minutes
-
-    let now = app.temp_history.back().unwrap().timestamp;
-    let cutoff_time = now - HISTORY_WINDOW_SECS;
-
-    // Filter to only show last 2 minutes
-    let recent_data: Vec<&TempData> = app
-        .temp_history
-        .iter()
-        .filter(|data| data.timestamp >= cutoff_time)
-        .collect();
+    // Only show the selected window (up to the pause point while paused)
+    let recent_data = app.chart_window();
 
     if recent_data.is_empty() {
         let no_data = Paragraph::new("Collecting data...")
             .block(
                 Block::default()
-                    .title("Temperature History (Last 2 min)")
+                    .title(format!("Temperature History (Last {})", window))
                     .borders(Borders::ALL),
             )
             .alignment(Alignment::Center);
@@ -843,28 +1233,46 @@ fn render_chart(f: &mut Frame, app: &App, area: Rect) {
 
     let min_time = recent_data.first().unwrap().timestamp;
 
+    let temp = |celsius: f32| app.unit.convert(celsius) as f64;
+
     let set_data: Vec<(f64, f64)> = recent_data
         .iter()
-        .map(|data| (data.timestamp - min_time, data.set_temp as f64))
+        .map(|data| (data.timestamp - min_time, temp(data.set_temp)))
         .collect();
 
     let measured_data: Vec<(f64, f64)> = recent_data
         .iter()
-        .map(|data| (data.timestamp - min_time, data.measured_temp as f64))
+        .map(|data| (data.timestamp - min_time, temp(data.measured_temp)))
         .collect();
 
+    let ([_, x_max], [min_temp, max_temp]) = chart_bounds(&recent_data, app.chart_window_secs(), app.unit);
+
     // Create Tmin and Tmax reference lines
-    let max_time = recent_data.last().unwrap().timestamp - min_time;
     let tmin_line: Vec<(f64, f64)> = vec![
-        (0.0, app.current_config.t_min as f64),
-        (max_time, app.current_config.t_min as f64),
+        (0.0, temp(app.current_config.t_min)),
+        (x_max, temp(app.current_config.t_min)),
     ];
     let tmax_line: Vec<(f64, f64)> = vec![
-        (0.0, app.current_config.t_max as f64),
-        (max_time, app.current_config.t_max as f64),
+        (0.0, temp(app.current_config.t_max)),
+        (x_max, temp(app.current_config.t_max)),
     ];
 
-    let datasets = vec![
+    // Dashed setpoint ± tolerance band, following the setpoint
+    let mut band_low: Vec<(f64, f64)> = Vec::new();
+    let mut band_high: Vec<(f64, f64)> = Vec::new();
+    if app.show_tolerance_band {
+        // A chart column is roughly one character cell, less the axis labels
+        let columns = area.width.saturating_sub(10) as usize;
+        for data in recent_data.iter() {
+            let x = data.timestamp - min_time;
+            if is_dash(x, x_max, columns) {
+                band_low.push((x, temp(data.set_temp - app.temp_tolerance)));
+                band_high.push((x, temp(data.set_temp + app.temp_tolerance)));
+            }
+        }
+    }
+
+    let mut datasets = vec![
         Dataset::default()
             .name("T Min")
             .marker(symbols::Marker::Dot)
@@ -890,32 +1298,46 @@ fn render_chart(f: &mut Frame, app: &App, area: Rect) {
             .graph_type(GraphType::Line)
             .data(&measured_data),
     ];
-
-    let all_temps: Vec<f64> = recent_data
-        .iter()
-        .flat_map(|data| vec![data.set_temp as f64, data.measured_temp as f64])
-        .collect();
-
-    let min_temp = all_temps.iter().fold(f64::INFINITY, |a, &b| a.min(b)) - 2.0;
-    let max_temp = all_temps.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b)) + 2.0;
+    if app.show_tolerance_band {
+        let tolerance = format!("±{}", format_delta(app.unit, app.temp_tolerance));
+        datasets.insert(
+            2,
+            Dataset::default()
+                .name(tolerance)
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(Color::DarkGray))
+                .graph_type(GraphType::Scatter)
+                .data(&band_high),
+        );
+        datasets.insert(
+            3,
+            Dataset::default()
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(Color::DarkGray))
+                .graph_type(GraphType::Scatter)
+                .data(&band_low),
+        );
+    }
 
     let chart = Chart::new(datasets)
         .block(
             Block::default()
-                .title("Temperature History (Last 2 min)")
+                .title(format!("Temperature History (Last {})", window))
                 .borders(Borders::ALL)
                 .border_style(Style::default().fg(Color::Gray)),
         )
         .x_axis(
             Axis::default()
-                .title("Time (s)")
+                .title("Time (m:ss)")
                 .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, max_time.min(HISTORY_WINDOW_SECS)]),
+                .labels(time_axis_labels(x_max))
+                .bounds([0.0, x_max]),
         )
         .y_axis(
             Axis::default()
-                .title("Temp (°C)")
+                .title(format!("Temp ({})", app.unit.symbol()))
                 .style(Style::default().fg(Color::Gray))
+                .labels(value_axis_labels([min_temp, max_temp], 1))
                 .bounds([min_temp, max_temp]),
         );
 
@@ -923,11 +1345,12 @@ fn render_chart(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_pwm_chart(f: &mut Frame, app: &App, area: Rect) {
+    let window = window_label(app.chart_window_secs());
     if app.temp_history.is_empty() {
         let no_data = Paragraph::new("Collecting data...")
             .block(
                 Block::default()
-                    .title("PWM Effort (Last 2 min)")
+                    .title(format!("PWM Effort (Last {})", window))
                     .borders(Borders::ALL),
             )
             .alignment(Alignment::Center);
@@ -935,23 +1358,14 @@ fn render_pwm_chart(f: &mut Frame, app: &App, area: Rect) {
         return;
     }
 
-    const HISTORY_WINDOW_SECS: f64 = 120.0; // Show last 2 minutes
-
-    let now = app.temp_history.back().unwrap().timestamp;
-    let cutoff_time = now - HISTORY_WINDOW_SECS;
-
-    // Filter to only show last 2 minutes
-    let recent_data: Vec<&TempData> = app
-        .temp_history
-        .iter()
-        .filter(|data| data.timestamp >= cutoff_time)
-        .collect();
+    // Only show the selected window (up to the pause point while paused)
+    let recent_data = app.chart_window();
 
     if recent_data.is_empty() {
         let no_data = Paragraph::new("Collecting data...")
             .block(
                 Block::default()
-                    .title("PWM Effort (Last 2 min)")
+                    .title(format!("PWM Effort (Last {})", window))
                     .borders(Borders::ALL),
             )
             .alignment(Alignment::Center);
@@ -960,7 +1374,7 @@ fn render_pwm_chart(f: &mut Frame, app: &App, area: Rect) {
     }
 
     let min_time = recent_data.first().unwrap().timestamp;
-    let max_time = recent_data.last().unwrap().timestamp - min_time;
+    let x_max = chart_x_max(time_span(&recent_data), app.chart_window_secs());
 
     let pwm_data: Vec<(f64, f64)> = recent_data
         .iter()
@@ -976,34 +1390,29 @@ fn render_pwm_chart(f: &mut Frame, app: &App, area: Rect) {
             .data(&pwm_data),
     ];
 
-    let all_pwm: Vec<f64> = recent_data.iter().map(|data| data.pwm as f64).collect();
-
-    let min_pwm = all_pwm
-        .iter()
-        .fold(f64::INFINITY, |a, &b| a.min(b))
-        .min(-5.0);
-    let max_pwm = all_pwm
-        .iter()
-        .fold(f64::NEG_INFINITY, |a, &b| a.max(b))
-        .max(5.0);
+    let (lowest, highest) = pwm_range(&recent_data).unwrap_or_default();
+    let min_pwm = (lowest as f64).min(-5.0);
+    let max_pwm = (highest as f64).max(5.0);
 
     let chart = Chart::new(datasets)
         .block(
             Block::default()
-                .title("PWM Effort (Last 2 min)")
+                .title(format!("PWM Effort (Last {})", window))
                 .borders(Borders::ALL)
                 .border_style(Style::default().fg(Color::Gray)),
         )
         .x_axis(
             Axis::default()
-                .title("Time (s)")
+                .title("Time (m:ss)")
                 .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, max_time.min(HISTORY_WINDOW_SECS)]),
+                .labels(time_axis_labels(x_max))
+                .bounds([0.0, x_max]),
         )
         .y_axis(
             Axis::default()
                 .title("PWM (%)")
                 .style(Style::default().fg(Color::Gray))
+                .labels(value_axis_labels([min_pwm, max_pwm], 0))
                 .bounds([min_pwm, max_pwm]),
         );
 
@@ -1013,26 +1422,60 @@ fn render_pwm_chart(f: &mut Frame, app: &App, area: Rect) {
 fn render_footer(f: &mut Frame, app: &App, area: Rect) {
     let mut status_spans = vec![];
 
+    if app.paused_at.is_some() {
+        status_spans.push(Span::styled(
+            "PAUSED",
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+        status_spans.push(Span::raw(" "));
+    }
+
     if let Some(ref setpoint_change) = app.current_setpoint_change {
         let elapsed = setpoint_change.start_time.elapsed().as_secs_f32();
         status_spans.push(Span::styled(
             format!(
-                "⏱ Tracking {:.1}°C for {:.1}s",
-                setpoint_change.target_temp, elapsed
+                "⏱ Tracking {} for {:.1}s",
+                app.unit.format(setpoint_change.target_temp, 1),
+                elapsed
             ),
             Style::default().fg(Color::Cyan),
         ));
-    } else if let Some(recent) = app.setpoint_history.back() {
-        if let Some(duration) = recent.duration {
-            status_spans.push(Span::styled(
-                format!(
-                    "✓ Last: {:.1}°C in {:.1}s",
-                    recent.target_temp,
-                    duration.as_secs_f32()
-                ),
-                Style::default().fg(Color::Green),
-            ));
+    } else if let Some(recent) = app.setpoint_history.back()
+        && let Some(duration) = recent.duration
+    {
+        status_spans.push(Span::styled(
+            format!(
+                "✓ Last: {} in {:.1}s",
+                app.unit.format(recent.target_temp, 1),
+                duration.as_secs_f32()
+            ),
+            Style::default().fg(Color::Green),
+        ));
+    }
+
+    if app.monitor.is_streaming() {
+        if !status_spans.is_empty() {
+            status_spans.push(Span::raw(" │ "));
         }
+        status_spans.push(Span::styled(
+            format!("streaming @ {} Hz", 1.0 / CYCLIC_PRINT_PERIOD.as_secs_f64()),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+
+    if let Some(ref preset) = app.active_preset {
+        if !status_spans.is_empty() {
+            status_spans.push(Span::raw(" │ "));
+        }
+        let label = if app.preset_unapplied {
+            format!("Preset: {} (not applied)", preset)
+        } else {
+            format!("Preset: {}", preset)
+        };
+        status_spans.push(Span::styled(label, Style::default().fg(Color::Magenta)));
     }
 
     if let Some(ref msg) = app.status_message {
@@ -1051,8 +1494,30 @@ fn render_footer(f: &mut Frame, app: &App, area: Rect) {
     status_spans.push(Span::raw(" Edit  "));
     status_spans.push(Span::styled("Space", Style::default().fg(Color::Cyan)));
     status_spans.push(Span::raw(" TEC  "));
+    status_spans.push(Span::styled("z", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Reset I  "));
+    status_spans.push(Span::styled("c", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Stream  "));
+    status_spans.push(Span::styled("p", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Pause  "));
+    status_spans.push(Span::styled("[]", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Zoom  "));
+    status_spans.push(Span::styled("b", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Band  "));
+    status_spans.push(Span::styled("s", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Save  "));
+    if app.fan.is_some() {
+        status_spans.push(Span::styled("<>", Style::default().fg(Color::Cyan)));
+        status_spans.push(Span::raw(" Fan  "));
+    }
+    status_spans.push(Span::styled("o", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Port  "));
+    status_spans.push(Span::styled("w/l/a", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(" Preset save/load/apply  "));
     status_spans.push(Span::styled("1-5", Style::default().fg(Color::Cyan)));
-    status_spans.push(Span::raw(" Step  "));
+    status_spans.push(Span::raw(format!(" Step ({})  ", format_delta(app.unit, app.temp_step))));
+    status_spans.push(Span::styled("u", Style::default().fg(Color::Cyan)));
+    status_spans.push(Span::raw(format!(" Unit ({})  ", app.unit.symbol())));
     status_spans.push(Span::styled("q", Style::default().fg(Color::Red)));
     status_spans.push(Span::raw(" Quit"));
 
@@ -1062,20 +1527,322 @@ fn render_footer(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(footer, area);
 }
 
+/// Read back a history written by `write_history_csv`, keeping the newest
+/// `capacity` readouts. Refuses a file over `MAX_CHECKPOINT_BYTES` or one
+/// whose newest readout is older than the widest chart window at `now`
+/// (seconds since the Unix epoch), since none of it would be shown.
+fn read_history_csv(path: &str, capacity: usize, now: f64) -> io::Result<RollingHistory> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_CHECKPOINT_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} bytes is more than a history checkpoint holds", size),
+        ));
+    }
+
+    let mut history = RollingHistory::new(capacity);
+    for (index, line) in std::fs::read_to_string(path)?.lines().enumerate().skip(1) {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {:?}", index + 1, line));
+        let values = line
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| invalid())?;
+        let [timestamp, set_temp, measured_temp, pwm] = values[..] else {
+            return Err(invalid());
+        };
+        // Keep the samples in order if the clock was stepped back
+        if history.latest().is_some_and(|latest| timestamp < latest.timestamp) {
+            continue;
+        }
+        history.push(TempData {
+            timestamp,
+            set_temp: set_temp as f32,
+            measured_temp: measured_temp as f32,
+            pwm: pwm as f32,
+        });
+    }
+
+    let widest_window = CHART_WINDOWS_SECS[CHART_WINDOWS_SECS.len() - 1];
+    if let Some(latest) = history.latest()
+        && now - latest.timestamp > widest_window
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("newest readout is {:.0} min old", (now - latest.timestamp) / 60.0),
+        ));
+    }
+    Ok(history)
+}
+
+fn write_history_csv(path: &str, history: &RollingHistory) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    writeln!(file, "timestamp,set_temp,measured_temp,pwm")?;
+    for data in history.iter() {
+        writeln!(
+            file,
+            "{:.3},{:.2},{:.2},{:.1}",
+            data.timestamp, data.set_temp, data.measured_temp, data.pwm
+        )?;
+    }
+    file.flush()
+}
+
+/// Whether `x` on a time axis ending at `x_max` falls on a dash rather than a
+/// gap, for dashed lines drawn as points: two columns on, two off
+fn is_dash(x: f64, x_max: f64, columns: usize) -> bool {
+    if x_max <= 0.0 || columns == 0 {
+        return true;
+    }
+    let column = (x / x_max * columns as f64) as usize;
+    column % 4 < 2
+}
+
+/// Fan speed one `step` up or down from `percent`, held within 0..=100
+fn step_fan_percent(percent: u8, step: u8, up: bool) -> u8 {
+    if up {
+        percent.saturating_add(step).min(100)
+    } else {
+        percent.saturating_sub(step)
+    }
+}
+
+/// A step or other temperature difference in `unit`, e.g. `0.9°F`
+fn format_delta(unit: TemperatureUnit, celsius: f32) -> String {
+    format!("{:.1}{}", unit.convert_delta(celsius), unit.symbol())
+}
+
+fn is_valid_preset_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Write `config` to `<dir>/<name>.yaml`, returning the path
+fn save_preset(dir: &str, name: &str, config: &TecConfig) -> io::Result<String> {
+    std::fs::create_dir_all(dir)?;
+    let path = format!("{}/{}.yaml", dir, name);
+    let yaml = serde_yaml::to_string(config).map_err(io::Error::other)?;
+    std::fs::write(&path, yaml)?;
+    Ok(path)
+}
+
+fn load_preset(dir: &str, name: &str) -> io::Result<TecConfig> {
+    let contents = std::fs::read_to_string(format!("{}/{}.yaml", dir, name))?;
+    serde_yaml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Preset names in `dir`, sorted; a missing directory has none
+fn list_presets(dir: &str) -> io::Result<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "yaml")
+            && let Some(stem) = path.file_stem()
+        {
+            names.push(stem.to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Statistics of the measured temperature, or `None` with fewer than two samples
+fn temp_stats(samples: &[&TempData]) -> Option<TempStats> {
+    let latest = samples.last()?;
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let n = samples.len() as f64;
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    let mut sum = 0.0;
+    for data in samples {
+        min = min.min(data.measured_temp);
+        max = max.max(data.measured_temp);
+        sum += data.measured_temp as f64;
+    }
+    let mean = sum / n;
+    let variance = samples
+        .iter()
+        .map(|data| (data.measured_temp as f64 - mean).powi(2))
+        .sum::<f64>()
+        / (n - 1.0);
+
+    Some(TempStats {
+        min,
+        max,
+        mean: mean as f32,
+        std_dev: variance.sqrt() as f32,
+        deviation: latest.measured_temp - latest.set_temp,
+    })
+}
+
+/// "1 min", "15 min", ...
+fn window_label(secs: f64) -> String {
+    format!("{} min", (secs / 60.0).round() as u32)
+}
+
+/// Time and temperature axis bounds of the temperature chart for the
+/// readouts in a `window`-second view, with 2° of headroom in `unit`
+fn chart_bounds(history: &[&TempData], window: f64, unit: TemperatureUnit) -> ([f64; 2], [f64; 2]) {
+    let x_max = chart_x_max(time_span(history), window);
+    // Every unit is increasing in °C, so converting the range keeps it ordered
+    let (lowest, highest) = temp_range(history).unwrap_or_default();
+    let temp = |celsius: f32| unit.convert(celsius) as f64;
+    let y = axis_bounds(temp(lowest) - 2.0, temp(highest) + 2.0, MIN_TEMP_SPAN);
+    ([0.0, x_max], y)
+}
+
+/// End of the time axis for `span` seconds of data in a `window`-second
+/// chart, never so short that the axis collapses
+fn chart_x_max(span: f64, window: f64) -> f64 {
+    let span = if span.is_finite() { span } else { 0.0 };
+    span.min(window).max(MIN_CHART_SPAN_SECS)
+}
+
+/// `[low, high]` widened evenly about its middle to at least `min_span`;
+/// a range that is not finite is replaced by `min_span` about zero
+fn axis_bounds(low: f64, high: f64, min_span: f64) -> [f64; 2] {
+    if !(low.is_finite() && high.is_finite()) {
+        return [-min_span / 2.0, min_span / 2.0];
+    }
+    let pad = (min_span - (high - low)).max(0.0) / 2.0;
+    [low - pad, high + pad]
+}
+
+/// `count` evenly spaced values from `low` to `high`, both included
+fn axis_ticks(low: f64, high: f64, count: usize) -> Vec<f64> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![low],
+        _ => (0..count)
+            .map(|tick| low + (high - low) * tick as f64 / (count - 1) as f64)
+            .collect(),
+    }
+}
+
+/// Evenly spaced m:ss ticks along a time axis ending at `x_max`; short
+/// spans get fewer so the ticks stay on whole seconds
+fn time_axis_labels(x_max: f64) -> Vec<String> {
+    let count = if x_max >= 20.0 { 5 } else { 3 };
+    axis_ticks(0.0, x_max, count)
+        .into_iter()
+        .map(|secs| {
+            let secs = secs.round() as u64;
+            format!("{}:{:02}", secs / 60, secs % 60)
+        })
+        .collect()
+}
+
+/// Evenly spaced ticks along a value axis spanning `bounds`
+fn value_axis_labels(bounds: [f64; 2], precision: usize) -> Vec<String> {
+    axis_ticks(bounds[0], bounds[1], VALUE_AXIS_TICKS)
+        .into_iter()
+        .map(|value| format!("{:.*}", precision, value))
+        .collect()
+}
+
+/// Parse the edit popup's text for `field` and check it against the rest of
+/// `config` and the setpoint soft limits, giving the value in °C or why it
+/// would be rejected
+fn check_edit_value(
+    text: &str,
+    field: EditField,
+    unit: TemperatureUnit,
+    config: &TecConfig,
+    bounds: SetpointBounds,
+) -> Result<f32, String> {
+    let value: f32 = text.trim().parse().map_err(|_| "not a number".to_string())?;
+    let value = if field.is_temperature() {
+        unit.to_celsius(value)
+    } else {
+        value
+    };
+
+    let mut candidate = config.clone();
+    match field {
+        EditField::TSet => candidate.t_set = value,
+        EditField::P => candidate.p = value,
+        EditField::I => candidate.i = value,
+        EditField::D => candidate.d = value,
+        EditField::TMin => candidate.t_min = value,
+        EditField::TMax => candidate.t_max = value,
+    }
+    match candidate.validate() {
+        Err(TecError::InvalidArgument(reason)) => return Err(reason),
+        Err(e) => return Err(e.to_string()),
+        Ok(()) => {}
+    }
+    if field == EditField::TSet && !(config.t_min..=config.t_max).contains(&value) {
+        return Err(format!(
+            "setpoint must be within T min {} and T max {}",
+            unit.format(config.t_min, 1),
+            unit.format(config.t_max, 1)
+        ));
+    }
+    if field == EditField::TSet && !bounds.contains(value) {
+        return Err(format!(
+            "setpoint is outside the soft limits {}..{}",
+            unit.format(bounds.min, 1),
+            unit.format(bounds.max, 1)
+        ));
+    }
+    Ok(value)
+}
+
 fn render_edit_popup(f: &mut Frame, app: &App) {
     let area = centered_rect(40, 20, f.area());
     f.render_widget(Clear, area);
 
-    let edit_title = format!(
-        "Edit {} (Enter to save, Esc to cancel)",
-        app.edit_field.label()
-    );
-    let edit_popup = Paragraph::new(app.edit_value.as_str())
-        .style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+    let edit_title = if app.input_mode == InputMode::NamingPreset {
+        "Save preset as (Enter to save, Esc to cancel)".to_string()
+    } else if app.input_mode == InputMode::ChangingPort {
+        format!("Reopen on port (Enter alone for {}, Esc to cancel)", app.port_name)
+    } else {
+        let unit = if app.edit_field.is_temperature() {
+            format!(" in {}", app.unit.symbol())
+        } else {
+            String::new()
+        };
+        format!(
+            "Edit {}{} (Enter to save, Esc to cancel)",
+            app.edit_field.label(),
+            unit
         )
+    };
+    let mut lines = vec![Line::from(Span::styled(
+        app.edit_value.as_str(),
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    // Live check of what Enter would apply
+    if app.input_mode == InputMode::Editing && !app.edit_value.trim().is_empty() {
+        let feedback = match check_edit_value(
+            &app.edit_value,
+            app.edit_field,
+            app.unit,
+            &app.current_config,
+            app.monitor.setpoint_bounds(),
+        ) {
+            Ok(value) if app.edit_field.is_temperature() => {
+                Span::styled(format!("= {}", app.unit.format(value, 2)), Style::default().fg(Color::Green))
+            }
+            Ok(value) => Span::styled(format!("= {:.2}", value), Style::default().fg(Color::Green)),
+            Err(reason) => Span::styled(format!("invalid: {}", reason), Style::default().fg(Color::Red)),
+        };
+        lines.push(Line::from(feedback));
+    }
+    let edit_popup = Paragraph::new(lines)
         .block(
             Block::default()
                 .title(edit_title)
@@ -1106,8 +1873,45 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-pub fn run_tui() -> Result<(), Box<dyn Error>> {
-    let port_name = "/dev/serial0";
+pub fn run(options: &TuiOptions) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "mqtt")]
+    let mqtt_config = match options.mqtt_broker {
+        Some(ref broker) => Some(mqtt::MqttConfig::new(
+            broker,
+            options.mqtt_prefix.as_deref().unwrap_or(mqtt::DEFAULT_TOPIC_PREFIX),
+        )?),
+        None => None,
+    };
+    #[cfg(feature = "influx")]
+    let influx_config = match options.influx_url {
+        Some(ref url) => {
+            let mut config = influx::InfluxConfig::new(
+                url,
+                options.influx_station.as_deref().unwrap_or(influx::DEFAULT_STATION),
+                &options.port_name,
+            )?;
+            config.token = std::env::var("INFLUX_TOKEN").ok();
+            Some(config)
+        }
+        None => None,
+    };
+
+    let fan = match options.fan_step {
+        Some(step) => Some((Fan::new(0, 0, DEFAULT_FAN_FREQUENCY_HZ)?, step)),
+        None => None,
+    };
+
+    // Load the log before taking over the terminal so errors stay readable
+    let (port_name, monitor) = match options.replay {
+        Some(ref path) => {
+            let source = ReplaySource::from_csv(path, options.replay_speed)?;
+            (format!("replay of {}", path), TecMonitor::replay(source))
+        }
+        None => (
+            options.port_name.clone(),
+            TecMonitor::connect(&options.port_name, options.read_interval),
+        ),
+    };
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -1115,15 +1919,39 @@ pub fn run_tui() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(port_name)?;
+    let mut app = App::new(&port_name, monitor)?;
+    app.temp_tolerance = options.temp_tolerance;
+    if let Some(ref path) = options.history_file {
+        app.restore_history(path);
+    }
+    if let Some((fan, step)) = fan {
+        app.attach_fan(fan, step);
+    }
+    if let Some(limit) = options.pwm_limit {
+        app.monitor.set_pwm_limit(limit, DEFAULT_PWM_GRACE);
+    }
+    if let Some(bounds) = options.setpoint_bounds {
+        app.monitor.set_setpoint_bounds(bounds);
+    }
+    let event_poll = EVENT_POLL_INTERVAL.min(options.read_interval);
+
+    #[cfg(feature = "mqtt")]
+    if let Some(config) = mqtt_config {
+        mqtt::start(config, app.monitor.subscribe(), app.monitor.command_sender());
+    }
+    #[cfg(feature = "influx")]
+    if let Some(config) = influx_config {
+        influx::start(config, app.monitor.subscribe());
+    }
 
     // Main loop - only redraw when necessary
     loop {
-        // Process any responses from worker thread
+        // Process any events from the monitor thread
         app.process_responses();
 
         // Send pending config if debounce period has elapsed
         app.send_config_if_pending();
+        app.checkpoint_history(false);
 
         // Only redraw if needed
         if app.needs_redraw {
@@ -1132,21 +1960,23 @@ pub fn run_tui() -> Result<(), Box<dyn Error>> {
         }
 
         // Handle input with longer timeout (reduces CPU usage)
-        if crossterm::event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            let _ = app.command_tx.send(WorkerCommand::Shutdown);
-                            break;
-                        }
-                        _ => app.handle_key_input(key.code),
+        if crossterm::event::poll(event_poll)? {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Char('q') => {
+                        app.monitor.shutdown();
+                        app.checkpoint_history(true);
+                        break;
                     }
+                    _ => app.handle_key_input(key.code),
                 }
             }
         } else {
-            // Even without events, update footer if tracking setpoint
-            if app.current_setpoint_change.is_some() {
+            // Even without events, update footer if tracking setpoint and
+            // keep an alarm banner flashing
+            if app.current_setpoint_change.is_some() || !app.active_alarms().is_empty() {
                 app.needs_redraw = true;
             }
         }
@@ -1163,3 +1993,229 @@ pub fn run_tui() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch::ScratchDir;
+
+    #[test]
+    fn test_temp_stats() {
+        let samples: Vec<TempData> = [24.0, 25.0, 26.0, 27.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &measured_temp)| TempData {
+                timestamp: i as f64,
+                set_temp: 25.0,
+                measured_temp,
+                pwm: 0.0,
+            })
+            .collect();
+        let refs: Vec<&TempData> = samples.iter().collect();
+
+        let stats = temp_stats(&refs).unwrap();
+        assert_eq!(stats.min, 24.0);
+        assert_eq!(stats.max, 27.0);
+        assert_eq!(stats.mean, 25.5);
+        assert!((stats.std_dev - 1.291).abs() < 0.001);
+        assert_eq!(stats.deviation, 2.0);
+
+        assert_eq!(temp_stats(&refs[..1]), None);
+        assert_eq!(temp_stats(&[]), None);
+    }
+
+    #[test]
+    fn test_chart_axes_never_collapse() {
+        // A single readout has no time span
+        assert_eq!(chart_x_max(0.0, 120.0), MIN_CHART_SPAN_SECS);
+        assert_eq!(chart_x_max(45.0, 120.0), 45.0);
+        assert_eq!(chart_x_max(500.0, 120.0), 120.0);
+        assert_eq!(chart_x_max(f64::NAN, 120.0), MIN_CHART_SPAN_SECS);
+
+        assert_eq!(axis_bounds(25.0, 25.0, 4.0), [23.0, 27.0]);
+        assert_eq!(axis_bounds(20.0, 30.0, 4.0), [20.0, 30.0]);
+        assert_eq!(axis_bounds(f64::NAN, 30.0, 4.0), [-2.0, 2.0]);
+    }
+
+    #[test]
+    fn test_chart_bounds() {
+        let sample = |timestamp: f64, set_temp: f32, measured_temp: f32| TempData {
+            timestamp,
+            set_temp,
+            measured_temp,
+            pwm: 0.0,
+        };
+        let celsius = TemperatureUnit::Celsius;
+        let bounds = |samples: &[TempData], unit| {
+            let refs: Vec<&TempData> = samples.iter().collect();
+            chart_bounds(&refs, 120.0, unit)
+        };
+
+        assert_eq!(bounds(&[], celsius), ([0.0, MIN_CHART_SPAN_SECS], [-2.0, 2.0]));
+        assert_eq!(
+            bounds(&[sample(100.0, 25.0, 25.0)], celsius),
+            ([0.0, MIN_CHART_SPAN_SECS], [23.0, 27.0])
+        );
+        let flat = [sample(0.0, 30.0, 30.0), sample(20.0, 30.0, 30.0), sample(40.0, 30.0, 30.0)];
+        assert_eq!(bounds(&flat, celsius), ([0.0, 40.0], [28.0, 32.0]));
+
+        let heating = [sample(0.0, 40.0, 22.0), sample(30.0, 40.0, 31.5), sample(60.0, 40.0, 39.0)];
+        assert_eq!(bounds(&heating, celsius), ([0.0, 60.0], [20.0, 42.0]));
+        // 22..40 °C is 71.6..104 °F
+        let (_, [low, high]) = bounds(&heating, TemperatureUnit::Fahrenheit);
+        assert!((low - 69.6).abs() < 1e-4 && (high - 106.0).abs() < 1e-4);
+        // Older readouts than the window don't stretch the time axis
+        let long = [sample(0.0, 25.0, 25.0), sample(300.0, 25.0, 25.0)];
+        assert_eq!(bounds(&long, celsius).0, [0.0, 120.0]);
+    }
+
+    #[test]
+    fn test_centered_rect() {
+        let area = Rect::new(0, 0, 100, 50);
+        assert_eq!(centered_rect(60, 20, area), Rect::new(20, 20, 60, 10));
+        assert_eq!(centered_rect(100, 100, area), area);
+
+        // Offset areas stay offset
+        let area = Rect::new(10, 5, 80, 40);
+        assert_eq!(centered_rect(50, 50, area), Rect::new(30, 15, 40, 20));
+    }
+
+    #[test]
+    fn test_axis_labels() {
+        assert_eq!(axis_ticks(0.0, 10.0, 3), [0.0, 5.0, 10.0]);
+        assert_eq!(axis_ticks(2.0, 2.0, 1), [2.0]);
+        assert!(axis_ticks(0.0, 1.0, 0).is_empty());
+
+        assert_eq!(time_axis_labels(120.0), ["0:00", "0:30", "1:00", "1:30", "2:00"]);
+        assert_eq!(time_axis_labels(MIN_CHART_SPAN_SECS), ["0:00", "0:05", "0:10"]);
+        assert_eq!(time_axis_labels(900.0)[4], "15:00");
+
+        assert_eq!(value_axis_labels([23.0, 27.0], 1), ["23.0", "24.0", "25.0", "26.0", "27.0"]);
+        assert_eq!(value_axis_labels([-100.0, 100.0], 0), ["-100", "-50", "0", "50", "100"]);
+    }
+
+    #[test]
+    fn test_is_dash() {
+        // 40 columns over 40 s: one column per second
+        let dashes: Vec<bool> = (0..8).map(|x| is_dash(x as f64 + 0.5, 40.0, 40)).collect();
+        assert_eq!(dashes, [true, true, false, false, true, true, false, false]);
+        assert!(is_dash(5.0, 0.0, 40));
+        assert!(is_dash(3.0, 40.0, 0));
+    }
+
+    #[test]
+    fn test_step_fan_percent() {
+        assert_eq!(step_fan_percent(40, 10, true), 50);
+        assert_eq!(step_fan_percent(40, 10, false), 30);
+        assert_eq!(step_fan_percent(95, 10, true), 100);
+        assert_eq!(step_fan_percent(250, 10, true), 100);
+        assert_eq!(step_fan_percent(5, 10, false), 0);
+    }
+
+    #[test]
+    fn test_check_edit_value() {
+        let config = TecConfig::default();
+        let celsius = TemperatureUnit::Celsius;
+        let check = |text: &str, field, unit| check_edit_value(text, field, unit, &config, SetpointBounds::default());
+
+        assert_eq!(check(" 7.5 ", EditField::P, celsius), Ok(7.5));
+        assert!(check("7.5x", EditField::P, celsius).is_err());
+        assert!(check("25", EditField::I, celsius).unwrap_err().contains("I gain"));
+        assert!(check("-1", EditField::D, celsius).is_err());
+
+        // Default limits are 0..35 °C
+        assert_eq!(check("30", EditField::TSet, celsius), Ok(30.0));
+        assert!(check("40", EditField::TSet, celsius).is_err());
+        assert!(check("36", EditField::TMin, celsius).is_err());
+        assert!(check("-5", EditField::TMax, celsius).is_err());
+        assert_eq!(check("50", EditField::TMax, celsius), Ok(50.0));
+        // Within the device limits but not the soft limits
+        let wide = TecConfig { t_max: 100.0, ..TecConfig::default() };
+        assert!(check_edit_value("80", EditField::TSet, celsius, &wide, SetpointBounds::default()).is_err());
+        let bounds = SetpointBounds::new(0.0, 90.0).unwrap();
+        assert_eq!(check_edit_value("80", EditField::TSet, celsius, &wide, bounds), Ok(80.0));
+
+        // Temperatures are typed in the display unit and returned in °C
+        let value = check("86", EditField::TSet, TemperatureUnit::Fahrenheit).unwrap();
+        assert!((value - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_preset_round_trip() {
+        let scratch = ScratchDir::new("presets");
+        let dir = scratch.file("presets");
+        assert!(list_presets(&dir).unwrap().is_empty());
+
+        let config = TecConfig {
+            t_set: 32.5,
+            p: 8.0,
+            ..TecConfig::default()
+        };
+        save_preset(&dir, "warm", &config).unwrap();
+        save_preset(&dir, "cold", &TecConfig::default()).unwrap();
+
+        let names = list_presets(&dir).unwrap();
+        let loaded = load_preset(&dir, "warm").unwrap();
+
+        assert_eq!(names, ["cold", "warm"]);
+        assert_eq!(loaded.t_set, 32.5);
+        assert_eq!(loaded.p, 8.0);
+        assert!(is_valid_preset_name("slow-ramp_2"));
+        assert!(!is_valid_preset_name("../etc"));
+    }
+
+    #[test]
+    fn test_write_history_csv() {
+        let scratch = ScratchDir::new("history");
+        let path = scratch.file("history.csv");
+        let mut history = RollingHistory::new(10);
+        history.push(TempData {
+            timestamp: 1_700_000_000.25,
+            set_temp: 30.0,
+            measured_temp: 29.456,
+            pwm: -12.34,
+        });
+
+        write_history_csv(&path, &history).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        assert_eq!(
+            contents,
+            "timestamp,set_temp,measured_temp,pwm\n1700000000.250,30.00,29.46,-12.3\n"
+        );
+    }
+
+    #[test]
+    fn test_read_history_csv() {
+        let scratch = ScratchDir::new("checkpoint");
+        let path = scratch.file("history.csv");
+        let now = 1_700_000_100.0;
+        let mut history = RollingHistory::new(10);
+        for i in 0..5 {
+            history.push(TempData {
+                timestamp: 1_700_000_000.0 + i as f64,
+                set_temp: 30.0,
+                measured_temp: 25.0 + i as f32,
+                pwm: 50.0,
+            });
+        }
+        write_history_csv(&path, &history).unwrap();
+
+        let restored = read_history_csv(&path, 10, now).unwrap();
+        assert_eq!(restored.iter().cloned().collect::<Vec<_>>(), history.iter().cloned().collect::<Vec<_>>());
+        // Only the newest readouts fit a smaller history
+        let capped = read_history_csv(&path, 3, now).unwrap();
+        assert_eq!(capped.len(), 3);
+        assert_eq!(capped.iter().next().unwrap().measured_temp, 27.0);
+
+        let stale = read_history_csv(&path, 10, now + 7200.0).unwrap_err();
+        assert!(stale.to_string().contains("min old"), "{}", stale);
+
+        std::fs::write(&path, "timestamp,set_temp,measured_temp,pwm\n1700000000.000,30.00\n").unwrap();
+        assert_eq!(read_history_csv(&path, 10, now).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::write(&path, vec![b'0'; MAX_CHECKPOINT_BYTES as usize + 1]).unwrap();
+        assert_eq!(read_history_csv(&path, 10, now).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read_history_csv(&path, 10, now).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
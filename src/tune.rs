@@ -0,0 +1,261 @@
+use crate::monitor::{DEFAULT_PWM_GRACE, MonitorCommand, MonitorEvent, TecCommand, TecMonitor};
+use crate::tec::{PwmLimit, SetpointBounds, TecConfig, TecReadout};
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Longest wait for a typed command before printing new readouts
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const HELP: &str = "\
+Commands:
+  p <value>   set the proportional coefficient
+  i <value>   set the integral coefficient
+  d <value>   set the derivative coefficient
+  t <value>   set the temperature setpoint in °C
+  ramp <value> <rate>
+              step the setpoint to value at rate °C/s
+  on / off    enable or disable the TEC
+  show        print the configuration being applied
+  port [path] reopen the port, or move to the one at path
+  q           disable the TEC and quit";
+
+/// One line typed at the tuning prompt
+#[derive(Debug, Clone, PartialEq)]
+enum TuneCommand {
+    P(f32),
+    I(f32),
+    D(f32),
+    TSet(f32),
+    /// Step the setpoint to a target at a rate in °C/s
+    Ramp { target: f32, rate_c_per_s: f32 },
+    Enable,
+    Disable,
+    Show,
+    /// Reopen the port, or move to another
+    Port(Option<String>),
+    Help,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Result<TuneCommand, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let name = words.first().copied().unwrap_or("help");
+    let value = |index: usize| -> Result<f32, String> {
+        let text = words.get(index).ok_or_else(|| format!("{} needs a value", name))?;
+        match text.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("Invalid value for {}: {:?}", name, text)),
+        }
+    };
+
+    let command = match name.to_lowercase().as_str() {
+        "p" => TuneCommand::P(value(1)?),
+        "i" => TuneCommand::I(value(1)?),
+        "d" => TuneCommand::D(value(1)?),
+        "t" => TuneCommand::TSet(value(1)?),
+        "ramp" => TuneCommand::Ramp {
+            target: value(1)?,
+            rate_c_per_s: value(2)?,
+        },
+        "on" => TuneCommand::Enable,
+        "off" => TuneCommand::Disable,
+        "show" => TuneCommand::Show,
+        "port" => TuneCommand::Port(words.get(1).map(|path| path.to_string())),
+        "help" | "?" => TuneCommand::Help,
+        "q" | "quit" => TuneCommand::Quit,
+        _ => return Err(format!("Unknown command: {:?} (type help)", name)),
+    };
+    Ok(command)
+}
+
+fn format_readout(readout: &TecReadout) -> String {
+    format!(
+        "T_set {:6.2}  T {:6.2}  PWM {:6.1}%  P {:.2}  I {:.2}  D {:.2}{}",
+        readout.t_set,
+        readout.t_measured,
+        readout.pwm,
+        readout.p,
+        readout.i,
+        readout.d,
+        if readout.oc { "  OVERCURRENT" } else { "" }
+    )
+}
+
+/// Print live readouts from `port_name` while PID coefficients and the
+/// setpoint are changed from stdin, one command per line
+pub fn run(
+    port_name: &str,
+    interval: Duration,
+    pwm_limit: Option<PwmLimit>,
+    setpoint_bounds: Option<SetpointBounds>,
+) -> Result<(), Box<dyn Error>> {
+    let mut monitor = TecMonitor::connect(port_name, interval);
+    let mut port_name = port_name.to_string();
+    if let Some(limit) = pwm_limit {
+        monitor.set_pwm_limit(limit, DEFAULT_PWM_GRACE);
+    }
+    if let Some(bounds) = setpoint_bounds {
+        monitor.set_setpoint_bounds(bounds);
+    }
+    let mut config = TecConfig::default();
+    let mut enabled = false;
+
+    // Stdin blocks, so lines arrive over a channel to keep readouts flowing
+    let (line_tx, line_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line_tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    println!("Tuning TEC on {}", port_name);
+    println!("{}", HELP);
+
+    loop {
+        while let Some(event) = monitor.try_recv_event() {
+            match event {
+                MonitorEvent::Readout(readout) => println!("{}", format_readout(&readout)),
+                MonitorEvent::Error(e) => eprintln!("{}", e),
+                MonitorEvent::Status(status) => println!("{}", status),
+                MonitorEvent::Config(_) => {}
+                MonitorEvent::Connected => {
+                    println!("Connected to {}", port_name);
+                    // The controller comes up disabled with its defaults
+                    monitor.send(TecCommand::SetConfig(config.clone()));
+                    if enabled {
+                        monitor.send(TecCommand::Enable);
+                    }
+                }
+                MonitorEvent::Disconnected(reason) => eprintln!("Disconnected: {}", reason),
+                MonitorEvent::PortChanged(port) => {
+                    println!("Switched to {}", port);
+                    port_name = port;
+                }
+            }
+        }
+
+        let line = match line_rx.recv_timeout(INPUT_POLL_INTERVAL) {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => continue,
+            // Stdin closed: treat like quit
+            Err(RecvTimeoutError::Disconnected) => "q".to_string(),
+        };
+
+        let mut updated = config.clone();
+        let mut pid_only = true;
+        let mut ramp_rate = None;
+        match parse_command(&line) {
+            Ok(TuneCommand::P(p)) => updated.p = p,
+            Ok(TuneCommand::I(i)) => updated.i = i,
+            Ok(TuneCommand::D(d)) => updated.d = d,
+            Ok(TuneCommand::TSet(t_set)) => {
+                updated.t_set = t_set;
+                pid_only = false;
+            }
+            Ok(TuneCommand::Ramp { target, rate_c_per_s }) => {
+                updated.t_set = target;
+                ramp_rate = Some(rate_c_per_s);
+            }
+            Ok(TuneCommand::Enable) => {
+                enabled = true;
+                monitor.send(TecCommand::Enable);
+                continue;
+            }
+            Ok(TuneCommand::Disable) => {
+                enabled = false;
+                monitor.send(TecCommand::Disable);
+                continue;
+            }
+            Ok(TuneCommand::Show) => {
+                println!(
+                    "T_set {:.2}  P {:.2}  I {:.2}  D {:.2}  T_min {:.1}  T_max {:.1}  TEC {}",
+                    config.t_set,
+                    config.p,
+                    config.i,
+                    config.d,
+                    config.t_min,
+                    config.t_max,
+                    if enabled { "on" } else { "off" }
+                );
+                continue;
+            }
+            Ok(TuneCommand::Port(new_port)) => {
+                monitor.send(MonitorCommand::ChangePort(new_port));
+                continue;
+            }
+            Ok(TuneCommand::Help) => {
+                println!("{}", HELP);
+                continue;
+            }
+            Ok(TuneCommand::Quit) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        }
+
+        match updated.validate() {
+            Ok(()) => {
+                config = updated;
+                monitor.send(if let Some(rate_c_per_s) = ramp_rate {
+                    TecCommand::RampTo {
+                        target: config.t_set,
+                        rate_c_per_s,
+                    }
+                } else if pid_only {
+                    TecCommand::SetPid {
+                        p: config.p,
+                        i: config.i,
+                        d: config.d,
+                    }
+                } else {
+                    TecCommand::SetConfig(config.clone())
+                });
+            }
+            Err(e) => eprintln!("Not applied: {}", e),
+        }
+    }
+
+    monitor.send(TecCommand::Disable);
+    monitor.shutdown();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("p 5.5"), Ok(TuneCommand::P(5.5)));
+        assert_eq!(parse_command(" D  0.25 "), Ok(TuneCommand::D(0.25)));
+        assert_eq!(parse_command("t -4"), Ok(TuneCommand::TSet(-4.0)));
+        assert_eq!(
+            parse_command("ramp 30 0.5"),
+            Ok(TuneCommand::Ramp {
+                target: 30.0,
+                rate_c_per_s: 0.5
+            })
+        );
+        assert_eq!(parse_command("on"), Ok(TuneCommand::Enable));
+        assert_eq!(parse_command("quit"), Ok(TuneCommand::Quit));
+        assert_eq!(parse_command("port"), Ok(TuneCommand::Port(None)));
+        assert_eq!(
+            parse_command("port /dev/ttyUSB1"),
+            Ok(TuneCommand::Port(Some("/dev/ttyUSB1".to_string())))
+        );
+
+        assert!(parse_command("i").is_err());
+        assert!(parse_command("i fast").is_err());
+        assert!(parse_command("ramp 30").is_err());
+        assert!(parse_command("p inf").is_err());
+        assert!(parse_command("warm 30").is_err());
+    }
+}